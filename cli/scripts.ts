@@ -1,9 +1,11 @@
 import { Program, web3 } from '@project-serum/anchor';
 import * as anchor from '@project-serum/anchor';
 import {
+    Ed25519Program,
     Keypair,
     PublicKey,
     SystemProgram,
+    SYSVAR_INSTRUCTIONS_PUBKEY,
     SYSVAR_RENT_PUBKEY,
     Transaction,
     TransactionInstruction,
@@ -12,20 +14,32 @@ import {
 import { TOKEN_PROGRAM_ID, AccountLayout, MintLayout, ASSOCIATED_TOKEN_PROGRAM_ID } from "@solana/spl-token";
 
 import fs from 'fs';
-import { CollectionPool, GlobalPool, RafflePool } from './types';
+import { CollectionPool, DiscountTier, GlobalPool, RafflePool, ReferrerPool, RoyaltyShare } from './types';
 import { publicKey } from '@project-serum/anchor/dist/cjs/utils';
 import { Raffle } from '../target/types/raffle';
 
 const GLOBAL_AUTHORITY_SEED = "global-authority";
+const ESCROW_AUTHORITY_SEED = "escrow-authority";
+const RAFFLE_SEED = "raffle";
+const REFERRER_SEED = "referrer";
+const VOUCHER_SEED = "voucher";
+const RECEIPT_SEED = "receipt";
+const DURATION_SEED = "duration";
 const TREASURY_WALLET = new PublicKey('Am9xhPPVCfDZFDabcGgmQ8GTMdsbqEt1qVXbyhTxybAp');
 const PROGRAM_ID = "3qJm618bPvosqjFZqMjrUYgVXNKGDPBmTVRMqHar92DK";
 
 const METAPLEX = new PublicKey('metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s');
 
-const RAFFLE_SIZE = 64168;
-const COLLECTION_SIZE = 12816;
+const COLLECTION_SIZE = 12824;
+const RAFFLE_SIZE = 64552;
+// one instruction allocates 10KB of the raffle PDA at most
+const ALLOCATE_STEPS = 7;
 const DECIMALS = 1000000000;
 
+const PAYMENT_SOL = 0;
+const PAYMENT_BURN_TOKEN = 1;
+const PAYMENT_BURN_NFT = 2;
+
 anchor.setProvider(anchor.AnchorProvider.local(web3.clusterApiUrl('devnet')));
 const solConnection = anchor.getProvider().connection;
 const payer = anchor.AnchorProvider.local().wallet;
@@ -45,7 +59,7 @@ program = new anchor.Program(idl, programId);
 console.log('ProgramId: ', program.programId.toBase58());
 
 const main = async () => {
-    const [globalAuthority, bump] = await PublicKey.findProgramAddress(
+    const [globalAuthority, _] = await PublicKey.findProgramAddress(
         [Buffer.from(GLOBAL_AUTHORITY_SEED)],
         program.programId
     );
@@ -54,13 +68,14 @@ const main = async () => {
 
     await initProject();
     // await addCollection(payer.publicKey, new PublicKey('GYq1mi8dh18nRAHbtdDuWiVRu4oAuSNzxoy3qStqX4RA'));
-    // await createRaffle(payer.publicKey, new PublicKey("FLuGogNV1UPns65SCz8ZLBnPx1P9EtcjVphvbyg2t6ix"), 1, 1654249100, 100);
-    // await buyTicket(payer.publicKey, new PublicKey("FLuGogNV1UPns65SCz8ZLBnPx1P9EtcjVphvbyg2t6ix"), 5);
-    // await revealWinner(payer.publicKey, new PublicKey("FLuGogNV1UPns65SCz8ZLBnPx1P9EtcjVphvbyg2t6ix"));
-    // await claimReward(payer.publicKey, new PublicKey("FLuGogNV1UPns65SCz8ZLBnPx1P9EtcjVphvbyg2t6ix"));
-    // await withdrawNft(payer.publicKey, new PublicKey("FLuGogNV1UPns65SCz8ZLBnPx1P9EtcjVphvbyg2t6ix"));
-
-    // const pool = await getRaffleState(new PublicKey("FLuGogNV1UPns65SCz8ZLBnPx1P9EtcjVphvbyg2t6ix"));
+    // await createRaffle(payer.publicKey, new PublicKey("FLuGogNV1UPns65SCz8ZLBnPx1P9EtcjVphvbyg2t6ix"), 0, 1, 0, 1654249100, 100, [{ minTickets: new anchor.BN(10), discountPercent: new anchor.BN(10) }]);
+    // const raffleKey = await getRaffleKey(payer.publicKey, new PublicKey("FLuGogNV1UPns65SCz8ZLBnPx1P9EtcjVphvbyg2t6ix"), 0);
+    // await buyTicket(payer.publicKey, raffleKey, 5);
+    // await revealWinner(payer.publicKey, raffleKey);
+    // await claimReward(payer.publicKey, raffleKey);
+    // await withdrawNft(payer.publicKey, raffleKey);
+
+    // const pool = await getStateByKey(raffleKey);
     // console.log(pool);
 }
 
@@ -69,7 +84,7 @@ const main = async () => {
  * @returns Init accounts for this project
  */
 export const initProject = async () => {
-    const [globalAuthority, bump] = await PublicKey.findProgramAddress(
+    const [globalAuthority, _] = await PublicKey.findProgramAddress(
         [Buffer.from(GLOBAL_AUTHORITY_SEED)],
         program.programId
     );
@@ -92,8 +107,7 @@ export const initProject = async () => {
         programId: program.programId,
     });
 
-    const tx = await program.rpc.initialize(
-        bump, {
+    const tx = await program.rpc.initialize({
         accounts: {
             admin: payer.publicKey,
             globalAuthority,
@@ -128,10 +142,15 @@ export const addCollection = async (
         "collection-pool",
         program.programId,
     );
+    const [globalAuthority, _] = await PublicKey.findProgramAddress(
+        [Buffer.from(GLOBAL_AUTHORITY_SEED)],
+        program.programId
+    );
     const tx = await program.rpc.addCollection(
         {
             accounts: {
                 admin: userAddress,
+                globalAuthority,
                 collection,
                 collectionId
             },
@@ -143,23 +162,292 @@ export const addCollection = async (
     console.log("txHash =", tx);
 }
 
+/**
+ * @dev Set the percent of the commission fee paid to referrers
+ * @param userAddress The super admin's address
+ * @param referralShare The referral percent of the commission fee
+ */
+export const setReferralShare = async (
+    userAddress: PublicKey,
+    referralShare: number
+) => {
+    const [globalAuthority, _] = await PublicKey.findProgramAddress(
+        [Buffer.from(GLOBAL_AUTHORITY_SEED)],
+        program.programId
+    );
+    const tx = await program.rpc.setReferralShare(
+        new anchor.BN(referralShare),
+        {
+            accounts: {
+                admin: userAddress,
+                globalAuthority,
+            },
+            instructions: [],
+            signers: [],
+        });
+    await solConnection.confirmTransaction(tx, "confirmed");
+
+    console.log("txHash =", tx);
+}
+
+/**
+ * @dev Set the pauser who can pause the program besides the super admin
+ * @param userAddress The super admin's address
+ * @param pauser The pauser's address
+ */
+export const setPauser = async (
+    userAddress: PublicKey,
+    pauser: PublicKey
+) => {
+    const [globalAuthority, _] = await PublicKey.findProgramAddress(
+        [Buffer.from(GLOBAL_AUTHORITY_SEED)],
+        program.programId
+    );
+    const tx = await program.rpc.setPauser(
+        pauser,
+        {
+            accounts: {
+                admin: userAddress,
+                globalAuthority,
+            },
+            instructions: [],
+            signers: [],
+        });
+    await solConnection.confirmTransaction(tx, "confirmed");
+
+    console.log("txHash =", tx);
+}
+
+/**
+ * @dev Pause or unpause creating raffles and buying tickets
+ * @param userAddress The super admin's or pauser's address
+ * @param paused Pause the program or not
+ */
+export const setPaused = async (
+    userAddress: PublicKey,
+    paused: boolean
+) => {
+    const [globalAuthority, _] = await PublicKey.findProgramAddress(
+        [Buffer.from(GLOBAL_AUTHORITY_SEED)],
+        program.programId
+    );
+    const tx = await program.rpc.setPaused(
+        paused,
+        {
+            accounts: {
+                authority: userAddress,
+                globalAuthority,
+            },
+            instructions: [],
+            signers: [],
+        });
+    await solConnection.confirmTransaction(tx, "confirmed");
+
+    console.log("txHash =", tx);
+}
+
+/**
+ * @dev Set the authority who signs the free ticket vouchers
+ * @param userAddress The super admin's address
+ * @param voucherAuthority The voucher signer's address
+ */
+export const setVoucherAuthority = async (
+    userAddress: PublicKey,
+    voucherAuthority: PublicKey
+) => {
+    const [globalAuthority, _] = await PublicKey.findProgramAddress(
+        [Buffer.from(GLOBAL_AUTHORITY_SEED)],
+        program.programId
+    );
+    const tx = await program.rpc.setVoucherAuthority(
+        voucherAuthority,
+        {
+            accounts: {
+                admin: userAddress,
+                globalAuthority,
+            },
+            instructions: [],
+            signers: [],
+        });
+    await solConnection.confirmTransaction(tx, "confirmed");
+
+    console.log("txHash =", tx);
+}
+
+/**
+ * @dev Set the claim window of the winners, then the winner can be rerolled
+ * @param userAddress The super admin's address
+ * @param claimWindow Seconds for the winner to claim, 0 means no deadline
+ */
+export const setClaimWindow = async (
+    userAddress: PublicKey,
+    claimWindow: number
+) => {
+    const [globalAuthority, _] = await PublicKey.findProgramAddress(
+        [Buffer.from(GLOBAL_AUTHORITY_SEED)],
+        program.programId
+    );
+    const tx = await program.rpc.setClaimWindow(
+        new anchor.BN(claimWindow),
+        {
+            accounts: {
+                admin: userAddress,
+                globalAuthority,
+            },
+            instructions: [],
+            signers: [],
+        });
+    await solConnection.confirmTransaction(tx, "confirmed");
+
+    console.log("txHash =", tx);
+}
+
+/**
+ * @dev Set the bounds of the raffle duration from its start to its end
+ * @param userAddress The super admin's address
+ * @param minDuration The minimum duration in seconds
+ * @param maxDuration The maximum duration in seconds, 0 means no limit
+ */
+export const setRaffleDuration = async (
+    userAddress: PublicKey,
+    minDuration: number,
+    maxDuration: number
+) => {
+    const [globalAuthority, _] = await PublicKey.findProgramAddress(
+        [Buffer.from(GLOBAL_AUTHORITY_SEED)],
+        program.programId
+    );
+    const tx = await program.rpc.setRaffleDuration(
+        new anchor.BN(minDuration),
+        new anchor.BN(maxDuration),
+        {
+            accounts: {
+                admin: userAddress,
+                globalAuthority,
+            },
+            instructions: [],
+            signers: [],
+        });
+    await solConnection.confirmTransaction(tx, "confirmed");
+
+    console.log("txHash =", tx);
+}
+
+/**
+ * @dev Set the duration bounds of a collection overriding the global ones
+ * @param userAddress The super admin's address
+ * @param collectionId The collection address
+ * @param minDuration The minimum duration in seconds
+ * @param maxDuration The maximum duration in seconds, 0 means no limit
+ */
+export const setCollectionDuration = async (
+    userAddress: PublicKey,
+    collectionId: PublicKey,
+    minDuration: number,
+    maxDuration: number
+) => {
+    const [globalAuthority, _] = await PublicKey.findProgramAddress(
+        [Buffer.from(GLOBAL_AUTHORITY_SEED)],
+        program.programId
+    );
+    const collectionDuration = await getCollectionDurationKey(collectionId);
+    const accountInfo = await solConnection.getAccountInfo(collectionDuration);
+
+    let tx;
+    if (accountInfo === null) {
+        tx = await program.rpc.initCollectionDuration(
+            new anchor.BN(minDuration),
+            new anchor.BN(maxDuration),
+            {
+                accounts: {
+                    admin: userAddress,
+                    globalAuthority,
+                    collectionId,
+                    collectionDuration,
+                    systemProgram: SystemProgram.programId,
+                    rent: SYSVAR_RENT_PUBKEY,
+                },
+                instructions: [],
+                signers: [],
+            });
+    } else {
+        tx = await program.rpc.setCollectionDuration(
+            new anchor.BN(minDuration),
+            new anchor.BN(maxDuration),
+            {
+                accounts: {
+                    admin: userAddress,
+                    globalAuthority,
+                    collectionDuration,
+                },
+                instructions: [],
+                signers: [],
+            });
+    }
+    await solConnection.confirmTransaction(tx, "confirmed");
+
+    console.log("txHash =", tx);
+}
+
+/**
+ * @dev Register the referrer PDA to earn referral rewards
+ * @param userAddress The referrer's address
+ */
+export const initReferrer = async (
+    userAddress: PublicKey
+) => {
+    const referrerPool = await getReferrerKey(userAddress);
+    const tx = await program.rpc.initReferrer(
+        {
+            accounts: {
+                referrer: userAddress,
+                referrerPool,
+                systemProgram: SystemProgram.programId,
+                rent: SYSVAR_RENT_PUBKEY,
+            },
+            instructions: [],
+            signers: [],
+        });
+    await solConnection.confirmTransaction(tx, "confirmed");
+
+    console.log("txHash =", tx);
+}
+
 /**
  * @dev CreateRaffle function
  * @param userAddress The raffle creator's address
  * @param nft_mint The nft_mint address
+ * @param nonce The creator's nonce to derive the raffle address with the nft_mint
  * @param ticketPriceSol The ticket price by SOL 
+ * @param startTimestamp The scheduled start timestamp of the ticket sales, 0 to start now
  * @param endTimestamp The raffle end timestamp
  * @param max The max entrants of this raffle
+ * @param discounts The quantity discount tiers sorted by minTickets
+ * @param useNftCreators Split the creator share among the NFT's creators like on secondary sales
+ * @param royalties The custom royalty split of the creator share by basis points
+ * @param paymentMode Pay tickets by SOL, by burning tokens or by burning NFTs
+ * @param burnTarget The token mint to burn, or the collection of the NFTs to burn
+ * @param returnUnclaimed Return the NFT to the creator instead of rerolling the winner who doesn't claim
+ * @param instantWin Draw the winner as soon as the tickets are sold out
  */
 export const createRaffle = async (
     userAddress: PublicKey,
     nft_mint: PublicKey,
+    nonce: number,
     ticketPriceSol: number,
+    startTimestamp: number,
     endTimestamp: number,
-    max: number
+    max: number,
+    discounts: DiscountTier[] = [],
+    useNftCreators: boolean = false,
+    royalties: RoyaltyShare[] = [],
+    paymentMode: number = PAYMENT_SOL,
+    burnTarget: PublicKey = PublicKey.default,
+    returnUnclaimed: boolean = false,
+    instantWin: boolean = false
 ) => {
 
-    const [globalAuthority, bump] = await PublicKey.findProgramAddress(
+    const [globalAuthority, _] = await PublicKey.findProgramAddress(
         [Buffer.from(GLOBAL_AUTHORITY_SEED)],
         program.programId
     );
@@ -173,63 +461,77 @@ export const createRaffle = async (
 
     let ownerNftAccount = await getAssociatedTokenAccount(userAddress, nft_mint);
 
+    const raffle = await getRaffleKey(userAddress, nft_mint, nonce);
+    console.log("Raffle = ", raffle.toBase58());
+
+    // the raffle PDA is allocated by its own transaction ahead
+    const raffleInfo = await solConnection.getAccountInfo(raffle);
+    if (raffleInfo === null || raffleInfo.data.length < RAFFLE_SIZE) {
+        const allocateAccounts = {
+            payer: payer.publicKey,
+            creator: userAddress,
+            nftMintAddress: nft_mint,
+            raffle,
+            systemProgram: SystemProgram.programId,
+        };
+        let allocateIxs = [];
+        for (let i = 1; i < ALLOCATE_STEPS; i++) {
+            allocateIxs.push(program.instruction.allocateRaffle(new anchor.BN(nonce), {
+                accounts: allocateAccounts,
+            }));
+        }
+        const allocateTx = await program.rpc.allocateRaffle(new anchor.BN(nonce), {
+            accounts: allocateAccounts,
+            instructions: allocateIxs,
+            signers: [],
+        });
+        await solConnection.confirmTransaction(allocateTx, "confirmed");
+        console.log("allocate txHash =", allocateTx);
+    }
+
+    const escrowAuthority = await getEscrowAuthority(raffle);
     let ix0 = await getATokenAccountsNeedCreate(
         solConnection,
         userAddress,
-        globalAuthority,
+        escrowAuthority,
         [nft_mint]
     );
     console.log("Dest NFT Account = ", ix0.destinationAccounts[0].toBase58());
 
-    let raffle;
-    let i;
-
-    for (i = 10; i > 0; i--) {
-        raffle = await PublicKey.createWithSeed(
-            userAddress,
-            nft_mint.toBase58().slice(0, i),
-            program.programId,
-        );
-        let state = await getStateByKey(raffle);
-        if (state === null) {
-            console.log(i);
-            break;
-        }
-    }
-    console.log(i);
-
-    let ix = SystemProgram.createAccountWithSeed({
-        fromPubkey: userAddress,
-        basePubkey: userAddress,
-        seed: nft_mint.toBase58().slice(0, i),
-        newAccountPubkey: raffle,
-        lamports: await solConnection.getMinimumBalanceForRentExemption(RAFFLE_SIZE),
-        space: RAFFLE_SIZE,
-        programId: program.programId,
-    });
-
     const metadataAddr = await getMetadataAddr(nft_mint);
+    const collectionDuration = await getCollectionDurationKey(await getNftCollection(nft_mint));
 
     const tx = await program.rpc.createRaffle(
-        bump,
-        new anchor.BN(ticketPriceSol * DECIMALS),
+        new anchor.BN(nonce),
+        new anchor.BN(paymentMode === PAYMENT_SOL ? ticketPriceSol * DECIMALS : ticketPriceSol),
+        new anchor.BN(startTimestamp),
         new anchor.BN(endTimestamp),
         new anchor.BN(max),
+        discounts,
+        useNftCreators,
+        royalties,
+        new anchor.BN(paymentMode),
+        burnTarget,
+        returnUnclaimed,
+        instantWin,
         {
             accounts: {
                 admin: payer.publicKey,
                 globalAuthority,
                 raffle,
+                escrowAuthority,
                 collection,
+                collectionDuration,
                 ownerTempNftAccount: ownerNftAccount,
                 destNftTokenAccount: ix0.destinationAccounts[0],
                 nftMintAddress: nft_mint,
                 mintMetadata: metadataAddr,
                 tokenProgram: TOKEN_PROGRAM_ID,
+                systemProgram: SystemProgram.programId,
+                rent: SYSVAR_RENT_PUBKEY,
                 tokenMetadataProgram: METAPLEX
             },
             instructions: [
-                ix,
                 ...ix0.instructions,
             ],
             signers: [],
@@ -243,26 +545,39 @@ export const createRaffle = async (
 /**
  * @dev BuyTicket function
  * @param userAddress The use's address
- * @param nft_mint The nft_mint address
+ * @param raffleKey The raffle address
  * @param amount The amount of ticket to buy
+ * @param referrer The optional referrer's address
  */
 export const buyTicket = async (
     userAddress: PublicKey,
-    nft_mint: PublicKey,
-    amount: number
+    raffleKey: PublicKey,
+    amount: number,
+    referrer: PublicKey | null = null
 ) => {
-    const [globalAuthority, bump] = await PublicKey.findProgramAddress(
+    const [globalAuthority, _] = await PublicKey.findProgramAddress(
         [Buffer.from(GLOBAL_AUTHORITY_SEED)],
         program.programId
     );
 
-    const raffleKey = await getRaffleKey(nft_mint);
     let raffleState = await getStateByKey(raffleKey);
 
     const creator = raffleState.creator;
 
+    let remainingAccounts = [];
+    for (let i = 0; i < raffleState.royaltyCount.toNumber(); i++) {
+        remainingAccounts.push(
+            { pubkey: raffleState.royalties[i].recipient, isSigner: false, isWritable: true }
+        );
+    }
+    if (referrer !== null) {
+        remainingAccounts.push(
+            { pubkey: referrer, isSigner: false, isWritable: true },
+            { pubkey: await getReferrerKey(referrer), isSigner: false, isWritable: true },
+        );
+    }
+
     const tx = await program.rpc.buyTickets(
-        bump,
         new anchor.BN(amount),
         {
             accounts: {
@@ -271,8 +586,10 @@ export const buyTicket = async (
                 globalAuthority,
                 creator,
                 treasuryWallet: TREASURY_WALLET,
+                receipt: await getReceiptKey(raffleKey, userAddress),
                 systemProgram: SystemProgram.programId,
             },
+            remainingAccounts,
             instructions: [],
             signers: [],
         });
@@ -283,23 +600,34 @@ export const buyTicket = async (
 }
 
 /**
- * @dev RevealWinner function
- * @param userAddress The user's address to call this function
- * @param raffleKey The raffleKey address
+ * @dev Buy tickets by burning the raffle's token
+ * @param userAddress The buyer's address
+ * @param raffleKey The raffle address
+ * @param amount The amount of ticket to buy
  */
-export const revealWinner = async (
+export const burnTokensForTickets = async (
     userAddress: PublicKey,
-    nft_mint: PublicKey,
+    raffleKey: PublicKey,
+    amount: number
 ) => {
-    const raffleKey = await getRaffleKey(nft_mint);
+    const [globalAuthority, _] = await PublicKey.findProgramAddress(
+        [Buffer.from(GLOBAL_AUTHORITY_SEED)],
+        program.programId
+    );
+    const raffleState = await getStateByKey(raffleKey);
+    const burnMint = raffleState.burnTarget;
+    const buyerTokenAccount = await getAssociatedTokenAccount(userAddress, burnMint);
 
-    console.log(userAddress.toBase58());
-    console.log(raffleKey.toBase58());
-    const tx = await program.rpc.revealWinner(
+    const tx = await program.rpc.burnTokensForTickets(
+        new anchor.BN(amount),
         {
             accounts: {
                 buyer: userAddress,
                 raffle: raffleKey,
+                globalAuthority,
+                burnMint,
+                buyerTokenAccount,
+                tokenProgram: TOKEN_PROGRAM_ID,
             },
             instructions: [],
             signers: [],
@@ -310,69 +638,291 @@ export const revealWinner = async (
 }
 
 /**
- * @dev ClaimReward function
- * @param userAddress The winner's address
- * @param nft_mint The nft_mint address
+ * @dev Buy one ticket by burning an NFT of the raffle's burn collection
+ * @param userAddress The buyer's address
+ * @param raffleKey The raffle address
+ * @param nft_mint The mint of the NFT to burn
  */
-export const claimReward = async (
+export const burnNftForTicket = async (
     userAddress: PublicKey,
-    nft_mint: PublicKey,
+    raffleKey: PublicKey,
+    nft_mint: PublicKey
 ) => {
-    const [globalAuthority, bump] = await PublicKey.findProgramAddress(
+    const [globalAuthority, _] = await PublicKey.findProgramAddress(
         [Buffer.from(GLOBAL_AUTHORITY_SEED)],
         program.programId
     );
+    const buyerTokenAccount = await getAssociatedTokenAccount(userAddress, nft_mint);
+    const mintMetadata = await getMetadataAddr(nft_mint);
 
-    const raffleKey = await getRaffleKey(nft_mint);
-    const srcNftTokenAccount = await getAssociatedTokenAccount(globalAuthority, nft_mint);
-
-    let ix0 = await getATokenAccountsNeedCreate(
-        solConnection,
-        userAddress,
-        userAddress,
-        [nft_mint]
-    );
-    console.log("Claimer's NFT Account: ", ix0.destinationAccounts[0]);
-
-    let tx = await program.rpc.claimReward(
-        bump,
+    const tx = await program.rpc.burnNftForTicket(
         {
             accounts: {
-                claimer: userAddress,
-                globalAuthority,
+                buyer: userAddress,
                 raffle: raffleKey,
-                claimerNftTokenAccount: ix0.destinationAccounts[0],
-                srcNftTokenAccount,
-                nftMintAddress: nft_mint,
+                globalAuthority,
+                burnMint: nft_mint,
+                buyerTokenAccount,
+                mintMetadata,
                 tokenProgram: TOKEN_PROGRAM_ID,
             },
-            instructions: [
-                ...ix0.instructions
-            ],
+            instructions: [],
             signers: [],
         });
     await solConnection.confirmTransaction(tx, "confirmed");
 
     console.log("txHash =", tx);
+}
 
+/**
+ * @dev Get the voucher message which the voucher authority signs
+ * @param raffleKey The raffle address
+ * @param userAddress The claimer's address
+ * @param voucherId The unique id of the voucher
+ * @param amount The amount of the free tickets
+ */
+export const getVoucherMessage = (
+    raffleKey: PublicKey,
+    userAddress: PublicKey,
+    voucherId: number,
+    amount: number
+): Buffer => {
+    return Buffer.concat([
+        raffleKey.toBuffer(),
+        userAddress.toBuffer(),
+        new anchor.BN(amount).toArrayLike(Buffer, "le", 8),
+        new anchor.BN(voucherId).toArrayLike(Buffer, "le", 8),
+    ]);
 }
 
 /**
- * @dev WithdrawNFT function
- * @param userAddress The creator's address
- * @param nft_mint The nft_mint address
+ * @dev ClaimFreeTicket function
+ * @param userAddress The claimer's address
+ * @param raffleKey The raffle address
+ * @param voucherId The unique id of the voucher
+ * @param amount The amount of the free tickets
+ * @param voucherAuthority The voucher signer's address
+ * @param signature The voucher authority's signature of the voucher message
  */
-export const withdrawNft = async (
+export const claimFreeTicket = async (
     userAddress: PublicKey,
-    nft_mint: PublicKey,
+    raffleKey: PublicKey,
+    voucherId: number,
+    amount: number,
+    voucherAuthority: PublicKey,
+    signature: Uint8Array
 ) => {
-    const [globalAuthority, bump] = await PublicKey.findProgramAddress(
+    const [globalAuthority, _] = await PublicKey.findProgramAddress(
         [Buffer.from(GLOBAL_AUTHORITY_SEED)],
         program.programId
     );
+    const [voucher, __] = await PublicKey.findProgramAddress(
+        [Buffer.from(VOUCHER_SEED), new anchor.BN(voucherId).toArrayLike(Buffer, "le", 8)],
+        program.programId
+    );
 
-    const raffleKey = await getRaffleKey(nft_mint);
-    const srcNftTokenAccount = await getAssociatedTokenAccount(globalAuthority, nft_mint);
+    const ix = Ed25519Program.createInstructionWithPublicKey({
+        publicKey: voucherAuthority.toBytes(),
+        message: getVoucherMessage(raffleKey, userAddress, voucherId, amount),
+        signature,
+    });
+
+    const tx = await program.rpc.claimFreeTicket(
+        new anchor.BN(voucherId),
+        new anchor.BN(amount),
+        {
+            accounts: {
+                claimer: userAddress,
+                globalAuthority,
+                raffle: raffleKey,
+                voucher,
+                instructionsSysvar: SYSVAR_INSTRUCTIONS_PUBKEY,
+                systemProgram: SystemProgram.programId,
+                rent: SYSVAR_RENT_PUBKEY,
+            },
+            instructions: [
+                ix
+            ],
+            signers: [],
+        });
+    await solConnection.confirmTransaction(tx, "confirmed");
+
+    console.log("txHash =", tx);
+}
+
+/**
+ * @dev RevealWinner function
+ * @param userAddress The user's address to call this function
+ * @param raffleKey The raffleKey address
+ */
+export const revealWinner = async (
+    userAddress: PublicKey,
+    raffleKey: PublicKey,
+) => {
+    const [globalAuthority, _] = await PublicKey.findProgramAddress(
+        [Buffer.from(GLOBAL_AUTHORITY_SEED)],
+        program.programId
+    );
+
+    console.log(userAddress.toBase58());
+    console.log(raffleKey.toBase58());
+    const tx = await program.rpc.revealWinner(
+        {
+            accounts: {
+                buyer: userAddress,
+                raffle: raffleKey,
+                globalAuthority,
+            },
+            instructions: [],
+            signers: [],
+        });
+    await solConnection.confirmTransaction(tx, "confirmed");
+
+    console.log("txHash =", tx);
+}
+
+/**
+ * @dev Reroll the winner who didn't claim before the claim deadline
+ * @param userAddress The caller's address
+ * @param raffleKey The raffle address
+ */
+export const rerollWinner = async (
+    userAddress: PublicKey,
+    raffleKey: PublicKey,
+) => {
+    const [globalAuthority, _] = await PublicKey.findProgramAddress(
+        [Buffer.from(GLOBAL_AUTHORITY_SEED)],
+        program.programId
+    );
+    const raffleState = await getStateByKey(raffleKey);
+    const nft_mint = raffleState.nftMint;
+    const escrowAuthority = await getEscrowAuthority(raffleKey);
+    const srcNftTokenAccount = await getAssociatedTokenAccount(escrowAuthority, nft_mint);
+
+    let ix0 = await getATokenAccountsNeedCreate(
+        solConnection,
+        userAddress,
+        raffleState.creator,
+        [nft_mint]
+    );
+
+    const tx = await program.rpc.rerollWinner(
+        {
+            accounts: {
+                caller: userAddress,
+                globalAuthority,
+                raffle: raffleKey,
+                escrowAuthority,
+                creatorNftTokenAccount: ix0.destinationAccounts[0],
+                srcNftTokenAccount,
+                tokenProgram: TOKEN_PROGRAM_ID,
+            },
+            instructions: [
+                ...ix0.instructions
+            ],
+            signers: [],
+        });
+    await solConnection.confirmTransaction(tx, "confirmed");
+
+    console.log("txHash =", tx);
+}
+
+/**
+ * @dev SettleRaffle function, delivers the prize to the winner and pays the settle bounty
+ * @param userAddress The cranker's address
+ * @param raffleKey The raffle address
+ */
+export const settleRaffle = async (
+    userAddress: PublicKey,
+    raffleKey: PublicKey,
+) => {
+    const raffleState = await getStateByKey(raffleKey);
+    const nft_mint = raffleState.nftMint;
+    const winner = raffleState.winner;
+    const escrowAuthority = await getEscrowAuthority(raffleKey);
+    const srcNftTokenAccount = await getAssociatedTokenAccount(escrowAuthority, nft_mint);
+    const winnerNftTokenAccount = await getAssociatedTokenAccount(winner, nft_mint);
+
+    const tx = await program.rpc.settleRaffle(
+        {
+            accounts: {
+                cranker: userAddress,
+                raffle: raffleKey,
+                escrowAuthority,
+                winner,
+                winnerNftTokenAccount,
+                srcNftTokenAccount,
+                nftMintAddress: nft_mint,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+                systemProgram: SystemProgram.programId,
+                rent: SYSVAR_RENT_PUBKEY,
+            },
+            instructions: [],
+            signers: [],
+        });
+    await solConnection.confirmTransaction(tx, "confirmed");
+
+    console.log("txHash =", tx);
+}
+
+/**
+ * @dev ClaimReward function
+ * @param userAddress The winner's address
+ * @param raffleKey The raffle address
+ */
+export const claimReward = async (
+    userAddress: PublicKey,
+    raffleKey: PublicKey,
+) => {
+    const raffleState = await getStateByKey(raffleKey);
+    const nft_mint = raffleState.nftMint;
+    const escrowAuthority = await getEscrowAuthority(raffleKey);
+    const srcNftTokenAccount = await getAssociatedTokenAccount(escrowAuthority, nft_mint);
+
+    let ix0 = await getATokenAccountsNeedCreate(
+        solConnection,
+        userAddress,
+        userAddress,
+        [nft_mint]
+    );
+    console.log("Claimer's NFT Account: ", ix0.destinationAccounts[0]);
+
+    let tx = await program.rpc.claimReward(
+        {
+            accounts: {
+                claimer: userAddress,
+                raffle: raffleKey,
+                escrowAuthority,
+                claimerNftTokenAccount: ix0.destinationAccounts[0],
+                srcNftTokenAccount,
+                nftMintAddress: nft_mint,
+                tokenProgram: TOKEN_PROGRAM_ID,
+            },
+            instructions: [
+                ...ix0.instructions
+            ],
+            signers: [],
+        });
+    await solConnection.confirmTransaction(tx, "confirmed");
+
+    console.log("txHash =", tx);
+
+}
+
+/**
+ * @dev WithdrawNFT function
+ * @param userAddress The creator's address
+ * @param raffleKey The raffle address
+ */
+export const withdrawNft = async (
+    userAddress: PublicKey,
+    raffleKey: PublicKey,
+) => {
+    const raffleState = await getStateByKey(raffleKey);
+    const nft_mint = raffleState.nftMint;
+    const escrowAuthority = await getEscrowAuthority(raffleKey);
+    const srcNftTokenAccount = await getAssociatedTokenAccount(escrowAuthority, nft_mint);
 
     let ix0 = await getATokenAccountsNeedCreate(
         solConnection,
@@ -383,12 +933,11 @@ export const withdrawNft = async (
     console.log("Creator's NFT Account: ", ix0.destinationAccounts[0].toBase58());
     console.log(raffleKey.toBase58());
 
-    let tx = await program.rpc.withdrawNft(
-        bump, {
+    let tx = await program.rpc.withdrawNft({
         accounts: {
             claimer: userAddress,
-            globalAuthority,
             raffle: raffleKey,
+            escrowAuthority,
             claimerNftTokenAccount: ix0.destinationAccounts[0],
             srcNftTokenAccount,
             nftMintAddress: nft_mint,
@@ -406,77 +955,241 @@ export const withdrawNft = async (
 }
 
 
-export const getRaffleKey = async (
-    nft_mint: PublicKey
-): Promise<PublicKey | null> => {
-    let poolAccounts = await solConnection.getParsedProgramAccounts(
-        program.programId,
+/**
+ * @dev Freeze or unfreeze the raffle
+ * @param userAddress The super admin's address
+ * @param raffleKey The raffle address
+ * @param frozen Freeze the raffle or not
+ */
+export const setRaffleFrozen = async (
+    userAddress: PublicKey,
+    raffleKey: PublicKey,
+    frozen: boolean
+) => {
+    const [globalAuthority, _] = await PublicKey.findProgramAddress(
+        [Buffer.from(GLOBAL_AUTHORITY_SEED)],
+        program.programId
+    );
+    const tx = await program.rpc.setRaffleFrozen(
+        frozen,
         {
-            filters: [
-                {
-                    dataSize: RAFFLE_SIZE
-                },
-                {
-                    memcmp: {
-                        "offset": 40,
-                        "bytes": nft_mint.toBase58()
-                    }
-                }
-            ]
-        }
+            accounts: {
+                admin: userAddress,
+                globalAuthority,
+                raffle: raffleKey,
+            },
+            instructions: [],
+            signers: [],
+        });
+    await solConnection.confirmTransaction(tx, "confirmed");
+
+    console.log("txHash =", tx);
+}
+
+/**
+ * @dev Cancel the frozen raffle, return the NFT to the creator and open refunds
+ * @param userAddress The super admin's address who deposits the refunds
+ * @param raffleKey The raffle address
+ */
+export const forceCancelRaffle = async (
+    userAddress: PublicKey,
+    raffleKey: PublicKey
+) => {
+    const [globalAuthority, _] = await PublicKey.findProgramAddress(
+        [Buffer.from(GLOBAL_AUTHORITY_SEED)],
+        program.programId
     );
+    const raffleState = await getStateByKey(raffleKey);
+    const nft_mint = raffleState.nftMint;
+    const escrowAuthority = await getEscrowAuthority(raffleKey);
+    const srcNftTokenAccount = await getAssociatedTokenAccount(escrowAuthority, nft_mint);
 
-    if (poolAccounts.length !== 0) {
-        let len = poolAccounts.length;
-        let max = 0;
-        let maxId = 0;
-        for (let i = 0; i < len; i++) {
-            let state = await getStateByKey(poolAccounts[i].pubkey);
-            if (state.endTimestamp.toNumber() > max) {
-                max = state.endTimestamp.toNumber();
-                maxId = i;
-            }
-        }
-        let raffleKey = poolAccounts[maxId].pubkey;
-        return raffleKey;
-    } else {
-        return null;
-    }
+    let ix0 = await getATokenAccountsNeedCreate(
+        solConnection,
+        userAddress,
+        raffleState.creator,
+        [nft_mint]
+    );
+    console.log("Creator's NFT Account: ", ix0.destinationAccounts[0].toBase58());
+
+    const tx = await program.rpc.forceCancelRaffle(
+        {
+            accounts: {
+                admin: userAddress,
+                globalAuthority,
+                raffle: raffleKey,
+                escrowAuthority,
+                creatorNftTokenAccount: ix0.destinationAccounts[0],
+                srcNftTokenAccount,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                systemProgram: SystemProgram.programId,
+            },
+            instructions: [
+                ...ix0.instructions
+            ],
+            signers: [],
+        });
+    await solConnection.confirmTransaction(tx, "confirmed");
+
+    console.log("txHash =", tx);
 }
 
-export const getRaffleState = async (
-    nft_mint: PublicKey
-): Promise<RafflePool | null> => {
+/**
+ * @dev Claim the refund of the tickets from the cancelled raffle
+ * @param userAddress The buyer's address
+ * @param raffleKey The raffle address
+ */
+export const claimRefund = async (
+    userAddress: PublicKey,
+    raffleKey: PublicKey
+) => {
+    const tx = await program.rpc.claimRefund(
+        {
+            accounts: {
+                buyer: userAddress,
+                raffle: raffleKey,
+                receipt: await getReceiptKey(raffleKey, userAddress),
+            },
+            instructions: [],
+            signers: [],
+        });
+    await solConnection.confirmTransaction(tx, "confirmed");
 
-    let poolAccounts = await solConnection.getParsedProgramAccounts(
-        program.programId,
+    console.log("txHash =", tx);
+}
+
+/**
+ * @dev Grow the global authority of the first release to the current layout
+ * @param userAddress The super admin's address
+ */
+export const migrateGlobal = async (
+    userAddress: PublicKey
+) => {
+    const [globalAuthority, _] = await PublicKey.findProgramAddress(
+        [Buffer.from(GLOBAL_AUTHORITY_SEED)],
+        program.programId
+    );
+
+    const tx = await program.rpc.migrateGlobal(
         {
-            filters: [
-                {
-                    dataSize: RAFFLE_SIZE
-                },
-                {
-                    memcmp: {
-                        "offset": 40,
-                        "bytes": nft_mint.toBase58()
-                    }
-                }
-            ]
-        }
+            accounts: {
+                admin: userAddress,
+                globalAuthority,
+                systemProgram: SystemProgram.programId,
+            },
+            instructions: [],
+            signers: [],
+        });
+    await solConnection.confirmTransaction(tx, "confirmed");
+
+    console.log("txHash =", tx);
+}
+
+/**
+ * @dev Convert a raffle of the first release to the current layout
+ * The NFT of a running or drawn raffle moves from the global authority's ATA to the escrow's ATA,
+ * migrateGlobal has to run first
+ * @param userAddress The payer's address, it funds the rent of the larger account
+ * @param raffleKey The raffle address
+ */
+export const migrateRaffle = async (
+    userAddress: PublicKey,
+    raffleKey: PublicKey
+) => {
+    const [globalAuthority, _] = await PublicKey.findProgramAddress(
+        [Buffer.from(GLOBAL_AUTHORITY_SEED)],
+        program.programId
     );
-    if (poolAccounts.length !== 0) {
-        let rentalKey = poolAccounts[0].pubkey;
+    // the first release layout can't be fetched with the current IDL, read its nft_mint and claimed
+    const raffleInfo = await solConnection.getAccountInfo(raffleKey);
+    const nft_mint = new PublicKey(raffleInfo.data.slice(40, 72));
+    const claimed = raffleInfo.data.readUInt32LE(120);
+    const escrowAuthority = await getEscrowAuthority(raffleKey);
+    const srcNftTokenAccount = await getAssociatedTokenAccount(globalAuthority, nft_mint);
 
-        try {
-            let rentalState = await program.account.rafflePool.fetch(rentalKey);
-            return rentalState as unknown as RafflePool;
-        } catch {
-            return null;
-        }
-    } else {
-        return null;
-    }
+    let ix0 = await getATokenAccountsNeedCreate(
+        solConnection,
+        userAddress,
+        escrowAuthority,
+        [nft_mint]
+    );
+
+    const tx = await program.rpc.migrateRaffle(
+        {
+            accounts: {
+                payer: userAddress,
+                globalAuthority,
+                raffle: raffleKey,
+                escrowAuthority,
+                srcNftTokenAccount,
+                destNftTokenAccount: ix0.destinationAccounts[0],
+                tokenProgram: TOKEN_PROGRAM_ID,
+                systemProgram: SystemProgram.programId,
+            },
+            instructions: claimed === 0 || claimed === 2 ? [...ix0.instructions] : [],
+            signers: [],
+        });
+    await solConnection.confirmTransaction(tx, "confirmed");
+
+    console.log("txHash =", tx);
+}
+
+/**
+ * @dev Convert the collection pool created before the version header to the current layout
+ * @param userAddress The super admin's address
+ */
+export const migrateCollection = async (
+    userAddress: PublicKey
+) => {
+    const [globalAuthority, _] = await PublicKey.findProgramAddress(
+        [Buffer.from(GLOBAL_AUTHORITY_SEED)],
+        program.programId
+    );
+    let collection = await PublicKey.createWithSeed(
+        userAddress,
+        "collection-pool",
+        program.programId,
+    );
+
+    const tx = await program.rpc.migrateCollection(
+        {
+            accounts: {
+                admin: userAddress,
+                globalAuthority,
+                collection,
+                systemProgram: SystemProgram.programId,
+            },
+            instructions: [],
+            signers: [],
+        });
+    await solConnection.confirmTransaction(tx, "confirmed");
+
+    console.log("txHash =", tx);
 }
+
+/**
+ * @dev Derive the raffle PDA from its creator, nft mint and nonce
+ * @param creator The raffle creator's address
+ * @param nft_mint The nft_mint address
+ * @param nonce The creator's nonce used at createRaffle
+ */
+export const getRaffleKey = async (
+    creator: PublicKey,
+    nft_mint: PublicKey,
+    nonce: number
+): Promise<PublicKey> => {
+    const [raffleKey, _] = await PublicKey.findProgramAddress(
+        [
+            Buffer.from(RAFFLE_SEED),
+            creator.toBuffer(),
+            nft_mint.toBuffer(),
+            new anchor.BN(nonce).toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+    );
+    return raffleKey;
+}
+
 export const getStateByKey = async (
     raffleKey: PublicKey
 ): Promise<RafflePool | null> => {
@@ -518,6 +1231,112 @@ export const getCollectionState = async (): Promise<CollectionPool | null> => {
     }
 }
 
+/**
+ * @dev Get the referrer PDA which tracks the referral earnings
+ * @param referrer The referrer's address
+ */
+export const getReferrerKey = async (
+    referrer: PublicKey
+): Promise<PublicKey> => {
+    const [referrerPool, _] = await PublicKey.findProgramAddress(
+        [Buffer.from(REFERRER_SEED), referrer.toBuffer()],
+        program.programId
+    );
+    return referrerPool;
+}
+
+/**
+ * @dev Get the receipt PDA which records the SOL a buyer paid for the raffle's tickets
+ * @param raffleKey The raffle address
+ * @param buyer The buyer's address
+ */
+export const getReceiptKey = async (
+    raffleKey: PublicKey,
+    buyer: PublicKey
+): Promise<PublicKey> => {
+    const [receipt, _] = await PublicKey.findProgramAddress(
+        [Buffer.from(RECEIPT_SEED), raffleKey.toBuffer(), buyer.toBuffer()],
+        program.programId
+    );
+    return receipt;
+}
+
+export const getReferrerState = async (
+    referrer: PublicKey
+): Promise<ReferrerPool | null> => {
+    try {
+        let state = await program.account.referrerPool.fetch(await getReferrerKey(referrer));
+        return state as unknown as ReferrerPool;
+    } catch {
+        return null;
+    }
+}
+
+/**
+ * @dev Get the PDA of the collection's duration bounds
+ * @param collectionId The collection address
+ */
+export const getCollectionDurationKey = async (
+    collectionId: PublicKey
+): Promise<PublicKey> => {
+    const [collectionDuration, _] = await PublicKey.findProgramAddress(
+        [Buffer.from(DURATION_SEED), collectionId.toBuffer()],
+        program.programId
+    );
+    return collectionDuration;
+}
+
+/**
+ * @dev Get the NFT's collection like the program does: the first verified
+ * creator of the NFT metadata which is in the collection pool
+ * @param nft_mint The NFT mint address
+ */
+export const getNftCollection = async (
+    nft_mint: PublicKey
+): Promise<PublicKey> => {
+    const collectionState = await getCollectionState();
+    const collections = collectionState.collections
+        .slice(0, collectionState.count.toNumber())
+        .map((collection) => collection.toBase58());
+
+    const metadata = await solConnection.getAccountInfo(await getMetadataAddr(nft_mint));
+    const data = metadata.data;
+    // key, update authority, mint, then name, symbol and uri strings
+    let offset = 1 + 32 + 32;
+    for (let i = 0; i < 3; i++) {
+        offset += 4 + data.readUInt32LE(offset);
+    }
+    // seller fee basis points, then the optional creators
+    offset += 2;
+    if (data[offset] === 1) {
+        const creatorCount = data.readUInt32LE(offset + 1);
+        offset += 5;
+        for (let i = 0; i < creatorCount; i++) {
+            const address = new PublicKey(data.slice(offset, offset + 32));
+            const verified = data[offset + 32] === 1;
+            if (verified && collections.includes(address.toBase58())) {
+                return address;
+            }
+            offset += 34;
+        }
+    }
+    throw new Error("The NFT is not in the allowed collections");
+}
+
+/**
+ * @dev Get the escrow PDA which holds the prize NFT of the raffle
+ * @param raffleKey The raffle account address
+ */
+export const getEscrowAuthority = async (
+    raffleKey: PublicKey
+): Promise<PublicKey> => {
+    const [escrowAuthority, _] = await PublicKey.findProgramAddress(
+        [Buffer.from(ESCROW_AUTHORITY_SEED), raffleKey.toBuffer()],
+        program.programId
+    );
+    return escrowAuthority;
+}
+
 const getAssociatedTokenAccount = async (ownerPubkey: PublicKey, mintPk: PublicKey): Promise<PublicKey> => {
     let associatedTokenAccountPubkey = (await PublicKey.findProgramAddress(
         [
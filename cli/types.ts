@@ -3,14 +3,40 @@ import { PublicKey } from '@solana/web3.js';
 
 export interface GlobalPool {
     superAdmin: PublicKey,
+    referralShare: anchor.BN,
+    pauser: PublicKey,
+    paused: boolean,
+    voucherAuthority: PublicKey,
+    claimWindow: anchor.BN,
+    minDuration: anchor.BN,
+    maxDuration: anchor.BN,
+    bump: number,
+}
+
+export interface ReferrerPool {
+    referrer: PublicKey,
+    totalEarned: anchor.BN,
+    referralCount: anchor.BN,
 }
 
 export interface CollectionPool {
+    version: anchor.BN,
     count: anchor.BN,
     collections: PublicKey[],
 }
 
+export interface DiscountTier {
+    minTickets: anchor.BN,
+    discountPercent: anchor.BN,
+}
+
+export interface RoyaltyShare {
+    recipient: PublicKey,
+    basisPoints: anchor.BN,
+}
+
 export interface RafflePool {
+    version: anchor.BN,
     creator: PublicKey,
     nftMint: PublicKey,
     count: anchor.BN,
@@ -22,5 +48,18 @@ export interface RafflePool {
     claimed: anchor.BN,
     winnerIndex: anchor.BN,
     winner: PublicKey,
+    discountCount: anchor.BN,
+    discounts: DiscountTier[],
+    royaltyCount: anchor.BN,
+    royalties: RoyaltyShare[],
+    frozen: anchor.BN,
+    totalPaid: anchor.BN,
+    paymentMode: anchor.BN,
+    burnTarget: PublicKey,
+    claimDeadline: anchor.BN,
+    returnUnclaimed: anchor.BN,
+    settleBounty: anchor.BN,
+    instantWin: anchor.BN,
     entrants: PublicKey[],
+    nonce: anchor.BN,
 }
\ No newline at end of file
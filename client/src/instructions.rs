@@ -0,0 +1,500 @@
+use anchor_lang::{system_program, InstructionData, ToAccountMetas};
+use anchor_spl::{associated_token::get_associated_token_address, token};
+use raffle::account::{CreateRaffleParams, RafflePool};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    sysvar,
+};
+
+use crate::pda::*;
+
+fn instruction(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: raffle::ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+// the collection pool has to be allocated by the admin with `pda::collection_pool` ahead
+pub fn initialize(admin: &Pubkey, collection: &Pubkey) -> Instruction {
+    let global_authority = global_authority().0;
+    instruction(
+        raffle::accounts::Initialize {
+            admin: *admin,
+            global_authority,
+            collection: *collection,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        },
+        raffle::instruction::Initialize {},
+    )
+}
+
+pub fn add_collection(admin: &Pubkey, collection: &Pubkey, collection_id: &Pubkey) -> Instruction {
+    instruction(
+        raffle::accounts::AddCollection {
+            admin: *admin,
+            global_authority: global_authority().0,
+            collection: *collection,
+            collection_id: *collection_id,
+        },
+        raffle::instruction::AddCollection {},
+    )
+}
+
+fn global_config(admin: &Pubkey) -> raffle::accounts::SetGlobalConfig {
+    raffle::accounts::SetGlobalConfig {
+        admin: *admin,
+        global_authority: global_authority().0,
+    }
+}
+
+pub fn set_referral_share(admin: &Pubkey, referral_share: u64) -> Instruction {
+    instruction(
+        global_config(admin),
+        raffle::instruction::SetReferralShare { referral_share },
+    )
+}
+
+pub fn set_pauser(admin: &Pubkey, pauser: &Pubkey) -> Instruction {
+    instruction(
+        global_config(admin),
+        raffle::instruction::SetPauser { pauser: *pauser },
+    )
+}
+
+// signed by the super admin or the pauser
+pub fn set_paused(authority: &Pubkey, paused: bool) -> Instruction {
+    instruction(
+        raffle::accounts::SetPaused {
+            authority: *authority,
+            global_authority: global_authority().0,
+        },
+        raffle::instruction::SetPaused { paused },
+    )
+}
+
+pub fn set_voucher_authority(admin: &Pubkey, voucher_authority: &Pubkey) -> Instruction {
+    instruction(
+        global_config(admin),
+        raffle::instruction::SetVoucherAuthority {
+            voucher_authority: *voucher_authority,
+        },
+    )
+}
+
+pub fn set_claim_window(admin: &Pubkey, claim_window: i64) -> Instruction {
+    instruction(
+        global_config(admin),
+        raffle::instruction::SetClaimWindow { claim_window },
+    )
+}
+
+pub fn set_raffle_duration(admin: &Pubkey, min_duration: i64, max_duration: i64) -> Instruction {
+    instruction(
+        global_config(admin),
+        raffle::instruction::SetRaffleDuration {
+            min_duration,
+            max_duration,
+        },
+    )
+}
+
+pub fn init_collection_duration(
+    admin: &Pubkey,
+    collection_id: &Pubkey,
+    min_duration: i64,
+    max_duration: i64,
+) -> Instruction {
+    instruction(
+        raffle::accounts::InitCollectionDuration {
+            admin: *admin,
+            global_authority: global_authority().0,
+            collection_id: *collection_id,
+            collection_duration: collection_duration(collection_id).0,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        },
+        raffle::instruction::InitCollectionDuration {
+            min_duration,
+            max_duration,
+        },
+    )
+}
+
+pub fn set_collection_duration(
+    admin: &Pubkey,
+    collection_id: &Pubkey,
+    min_duration: i64,
+    max_duration: i64,
+) -> Instruction {
+    instruction(
+        raffle::accounts::SetCollectionDuration {
+            admin: *admin,
+            global_authority: global_authority().0,
+            collection_duration: collection_duration(collection_id).0,
+        },
+        raffle::instruction::SetCollectionDuration {
+            min_duration,
+            max_duration,
+        },
+    )
+}
+
+pub fn init_referrer(referrer: &Pubkey) -> Instruction {
+    instruction(
+        raffle::accounts::InitReferrer {
+            referrer: *referrer,
+            referrer_pool: referrer_pool(referrer).0,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        },
+        raffle::instruction::InitReferrer {},
+    )
+}
+
+// anyone can pay the rent of the raffle PDA of the creator
+pub fn allocate_raffle(
+    payer: &Pubkey,
+    creator: &Pubkey,
+    nft_mint: &Pubkey,
+    nonce: u64,
+) -> Instruction {
+    instruction(
+        raffle::accounts::AllocateRaffle {
+            payer: *payer,
+            creator: *creator,
+            nft_mint_address: *nft_mint,
+            raffle: raffle_address(creator, nft_mint, nonce).0,
+            system_program: system_program::ID,
+        },
+        raffle::instruction::AllocateRaffle { nonce },
+    )
+}
+
+// the raffle PDA has to be allocated by these instructions before `create_raffle`
+// each of them grows it by the most one instruction can allocate
+pub fn allocate_raffle_steps(
+    payer: &Pubkey,
+    creator: &Pubkey,
+    nft_mint: &Pubkey,
+    nonce: u64,
+) -> Vec<Instruction> {
+    vec![allocate_raffle(payer, creator, nft_mint, nonce); RafflePool::ALLOCATE_STEPS]
+}
+
+// the escrow's NFT account has to exist before `create_raffle`
+pub fn create_escrow_token_account(
+    creator: &Pubkey,
+    raffle: &Pubkey,
+    nft_mint: &Pubkey,
+) -> Instruction {
+    spl_associated_token_account::create_associated_token_account(
+        creator,
+        &escrow_authority(raffle).0,
+        nft_mint,
+    )
+}
+
+// `collection_id` is the verified creator of the NFT which is whitelisted in the collection pool
+pub fn create_raffle(
+    creator: &Pubkey,
+    collection: &Pubkey,
+    collection_id: &Pubkey,
+    owner_nft_token_account: &Pubkey,
+    nft_mint: &Pubkey,
+    params: CreateRaffleParams,
+) -> Instruction {
+    let global_authority = global_authority().0;
+    let raffle = raffle_address(creator, nft_mint, params.nonce).0;
+    instruction(
+        raffle::accounts::CreateRaffle {
+            admin: *creator,
+            global_authority,
+            raffle,
+            escrow_authority: escrow_authority(&raffle).0,
+            collection: *collection,
+            collection_duration: collection_duration(collection_id).0,
+            owner_temp_nft_account: *owner_nft_token_account,
+            dest_nft_token_account: escrow_token_account(&raffle, nft_mint),
+            nft_mint_address: *nft_mint,
+            mint_metadata: metadata_address(nft_mint),
+            token_program: token::ID,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+            token_metadata_program: metaplex_token_metadata::id(),
+        },
+        raffle::instruction::CreateRaffle::from(params),
+    )
+}
+
+// passes the raffle's royalty recipients and the optional referrer with its pool
+pub fn buy_tickets(
+    buyer: &Pubkey,
+    raffle: &Pubkey,
+    raffle_state: &RafflePool,
+    amount: u64,
+    referrer: Option<&Pubkey>,
+) -> Instruction {
+    let global_authority = global_authority().0;
+    let mut ix = instruction(
+        raffle::accounts::BuyTickets {
+            buyer: *buyer,
+            raffle: *raffle,
+            global_authority,
+            creator: raffle_state.creator,
+            treasury_wallet: treasury_wallet(),
+            receipt: receipt(raffle, buyer).0,
+            system_program: system_program::ID,
+        },
+        raffle::instruction::BuyTickets { amount },
+    );
+    for royalty in &raffle_state.royalties[..raffle_state.royalty_count as usize] {
+        ix.accounts.push(AccountMeta::new(royalty.recipient, false));
+    }
+    if let Some(referrer) = referrer {
+        ix.accounts.push(AccountMeta::new(*referrer, false));
+        ix.accounts
+            .push(AccountMeta::new(referrer_pool(referrer).0, false));
+    }
+    ix
+}
+
+pub fn burn_tokens_for_tickets(
+    buyer: &Pubkey,
+    raffle: &Pubkey,
+    burn_mint: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    instruction(
+        raffle::accounts::BurnTokensForTickets {
+            buyer: *buyer,
+            raffle: *raffle,
+            global_authority: global_authority().0,
+            burn_mint: *burn_mint,
+            buyer_token_account: get_associated_token_address(buyer, burn_mint),
+            token_program: token::ID,
+        },
+        raffle::instruction::BurnTokensForTickets { amount },
+    )
+}
+
+pub fn burn_nft_for_ticket(buyer: &Pubkey, raffle: &Pubkey, burn_mint: &Pubkey) -> Instruction {
+    instruction(
+        raffle::accounts::BurnNftForTicket {
+            buyer: *buyer,
+            raffle: *raffle,
+            global_authority: global_authority().0,
+            burn_mint: *burn_mint,
+            buyer_token_account: get_associated_token_address(buyer, burn_mint),
+            mint_metadata: metadata_address(burn_mint),
+            token_program: token::ID,
+        },
+        raffle::instruction::BurnNftForTicket {},
+    )
+}
+
+// has to follow the ed25519 instruction of the voucher authority signing
+// `raffle::utils::voucher_message` in the same transaction
+pub fn claim_free_ticket(
+    claimer: &Pubkey,
+    raffle: &Pubkey,
+    voucher_id: u64,
+    amount: u64,
+) -> Instruction {
+    instruction(
+        raffle::accounts::ClaimFreeTicket {
+            claimer: *claimer,
+            global_authority: global_authority().0,
+            raffle: *raffle,
+            voucher: voucher(voucher_id).0,
+            instructions_sysvar: sysvar::instructions::ID,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        },
+        raffle::instruction::ClaimFreeTicket { voucher_id, amount },
+    )
+}
+
+pub fn reveal_winner(payer: &Pubkey, raffle: &Pubkey) -> Instruction {
+    instruction(
+        raffle::accounts::RevealWinner {
+            buyer: *payer,
+            raffle: *raffle,
+            global_authority: global_authority().0,
+        },
+        raffle::instruction::RevealWinner {},
+    )
+}
+
+// the NFT goes back to the creator's ATA when no active tickets are left
+pub fn reroll_winner(caller: &Pubkey, raffle: &Pubkey, raffle_state: &RafflePool) -> Instruction {
+    instruction(
+        raffle::accounts::RerollWinner {
+            caller: *caller,
+            global_authority: global_authority().0,
+            raffle: *raffle,
+            escrow_authority: escrow_authority(raffle).0,
+            creator_nft_token_account: get_associated_token_address(
+                &raffle_state.creator,
+                &raffle_state.nft_mint,
+            ),
+            src_nft_token_account: escrow_token_account(raffle, &raffle_state.nft_mint),
+            token_program: token::ID,
+        },
+        raffle::instruction::RerollWinner {},
+    )
+}
+
+pub fn claim_reward(
+    claimer: &Pubkey,
+    raffle: &Pubkey,
+    raffle_state: &RafflePool,
+    claimer_nft_token_account: &Pubkey,
+) -> Instruction {
+    instruction(
+        raffle::accounts::ClaimReward {
+            claimer: *claimer,
+            raffle: *raffle,
+            escrow_authority: escrow_authority(raffle).0,
+            claimer_nft_token_account: *claimer_nft_token_account,
+            src_nft_token_account: escrow_token_account(raffle, &raffle_state.nft_mint),
+            nft_mint_address: raffle_state.nft_mint,
+            token_program: token::ID,
+        },
+        raffle::instruction::ClaimReward {},
+    )
+}
+
+// sends the NFT to the winner's ATA, creating it when needed
+pub fn settle_raffle(cranker: &Pubkey, raffle: &Pubkey, raffle_state: &RafflePool) -> Instruction {
+    instruction(
+        raffle::accounts::SettleRaffle {
+            cranker: *cranker,
+            raffle: *raffle,
+            escrow_authority: escrow_authority(raffle).0,
+            winner: raffle_state.winner,
+            winner_nft_token_account: get_associated_token_address(
+                &raffle_state.winner,
+                &raffle_state.nft_mint,
+            ),
+            src_nft_token_account: escrow_token_account(raffle, &raffle_state.nft_mint),
+            nft_mint_address: raffle_state.nft_mint,
+            token_program: token::ID,
+            associated_token_program: spl_associated_token_account::ID,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        },
+        raffle::instruction::SettleRaffle {},
+    )
+}
+
+pub fn withdraw_nft(
+    claimer: &Pubkey,
+    raffle: &Pubkey,
+    raffle_state: &RafflePool,
+    claimer_nft_token_account: &Pubkey,
+) -> Instruction {
+    instruction(
+        raffle::accounts::WithdrawNft {
+            claimer: *claimer,
+            raffle: *raffle,
+            escrow_authority: escrow_authority(raffle).0,
+            claimer_nft_token_account: *claimer_nft_token_account,
+            src_nft_token_account: escrow_token_account(raffle, &raffle_state.nft_mint),
+            nft_mint_address: raffle_state.nft_mint,
+            token_program: token::ID,
+        },
+        raffle::instruction::WithdrawNft {},
+    )
+}
+
+pub fn set_raffle_frozen(admin: &Pubkey, raffle: &Pubkey, frozen: bool) -> Instruction {
+    instruction(
+        raffle::accounts::SetRaffleFrozen {
+            admin: *admin,
+            global_authority: global_authority().0,
+            raffle: *raffle,
+        },
+        raffle::instruction::SetRaffleFrozen { frozen },
+    )
+}
+
+// returns the NFT to the creator's ATA
+pub fn force_cancel_raffle(
+    admin: &Pubkey,
+    raffle: &Pubkey,
+    raffle_state: &RafflePool,
+) -> Instruction {
+    instruction(
+        raffle::accounts::ForceCancelRaffle {
+            admin: *admin,
+            global_authority: global_authority().0,
+            raffle: *raffle,
+            escrow_authority: escrow_authority(raffle).0,
+            creator_nft_token_account: get_associated_token_address(
+                &raffle_state.creator,
+                &raffle_state.nft_mint,
+            ),
+            src_nft_token_account: escrow_token_account(raffle, &raffle_state.nft_mint),
+            token_program: token::ID,
+            system_program: system_program::ID,
+        },
+        raffle::instruction::ForceCancelRaffle {},
+    )
+}
+
+pub fn claim_refund(buyer: &Pubkey, raffle: &Pubkey) -> Instruction {
+    instruction(
+        raffle::accounts::ClaimRefund {
+            buyer: *buyer,
+            raffle: *raffle,
+            receipt: receipt(raffle, buyer).0,
+        },
+        raffle::instruction::ClaimRefund {},
+    )
+}
+
+// grows the global authority of the first release to the current layout
+pub fn migrate_global(admin: &Pubkey) -> Instruction {
+    instruction(
+        raffle::accounts::MigrateGlobal {
+            admin: *admin,
+            global_authority: global_authority().0,
+            system_program: system_program::ID,
+        },
+        raffle::instruction::MigrateGlobal {},
+    )
+}
+
+// converts a raffle of the first release, see `state::is_legacy_raffle`
+// its NFT moves to the escrow's ATA, which `create_escrow_token_account` creates first
+pub fn migrate_raffle(payer: &Pubkey, raffle: &Pubkey, raffle_state: &RafflePool) -> Instruction {
+    instruction(
+        raffle::accounts::MigrateRaffle {
+            payer: *payer,
+            global_authority: global_authority().0,
+            raffle: *raffle,
+            escrow_authority: escrow_authority(raffle).0,
+            src_nft_token_account: legacy_nft_token_account(&raffle_state.nft_mint),
+            dest_nft_token_account: escrow_token_account(raffle, &raffle_state.nft_mint),
+            token_program: token::ID,
+            system_program: system_program::ID,
+        },
+        raffle::instruction::MigrateRaffle {},
+    )
+}
+
+pub fn migrate_collection(admin: &Pubkey, collection: &Pubkey) -> Instruction {
+    instruction(
+        raffle::accounts::MigrateCollection {
+            admin: *admin,
+            global_authority: global_authority().0,
+            collection: *collection,
+            system_program: system_program::ID,
+        },
+        raffle::instruction::MigrateCollection {},
+    )
+}
@@ -0,0 +1,16 @@
+//! Rust client for the raffle program.
+//!
+//! - `pda` derives the program's addresses
+//! - `instructions` builds one instruction per program handler
+//! - `state` decodes the program accounts
+//!
+//! The account types, constants and errors are the program crate's own.
+
+// the decoders return the program's anchor errors, which are larger than clippy's limit
+#![allow(clippy::result_large_err)]
+
+pub mod instructions;
+pub mod pda;
+pub mod state;
+
+pub use raffle::{account, constants, error, id, ID};
@@ -0,0 +1,84 @@
+use anchor_spl::associated_token::get_associated_token_address;
+use raffle::constants::*;
+use solana_program::pubkey::{Pubkey, PubkeyError};
+
+// the seed the admin allocates the collection pool with in `initProject`
+pub const COLLECTION_POOL_SEED: &str = "collection-pool";
+
+pub fn global_authority() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[GLOBAL_AUTHORITY_SEED.as_bytes()], &raffle::ID)
+}
+
+pub fn collection_pool(admin: &Pubkey) -> Result<Pubkey, PubkeyError> {
+    Pubkey::create_with_seed(admin, COLLECTION_POOL_SEED, &raffle::ID)
+}
+
+pub fn raffle_address(creator: &Pubkey, nft_mint: &Pubkey, nonce: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            RAFFLE_SEED.as_bytes(),
+            creator.as_ref(),
+            nft_mint.as_ref(),
+            &nonce.to_le_bytes(),
+        ],
+        &raffle::ID,
+    )
+}
+
+pub fn escrow_authority(raffle: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[ESCROW_AUTHORITY_SEED.as_bytes(), raffle.as_ref()],
+        &raffle::ID,
+    )
+}
+
+// the escrow's NFT account is the ATA of the escrow authority
+pub fn escrow_token_account(raffle: &Pubkey, nft_mint: &Pubkey) -> Pubkey {
+    get_associated_token_address(&escrow_authority(raffle).0, nft_mint)
+}
+
+// the first release kept the NFTs of all raffles in ATAs of the global authority
+pub fn legacy_nft_token_account(nft_mint: &Pubkey) -> Pubkey {
+    get_associated_token_address(&global_authority().0, nft_mint)
+}
+
+pub fn referrer_pool(referrer: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[REFERRER_SEED.as_bytes(), referrer.as_ref()], &raffle::ID)
+}
+
+pub fn voucher(voucher_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[VOUCHER_SEED.as_bytes(), &voucher_id.to_le_bytes()],
+        &raffle::ID,
+    )
+}
+
+pub fn receipt(raffle: &Pubkey, buyer: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[RECEIPT_SEED.as_bytes(), raffle.as_ref(), buyer.as_ref()],
+        &raffle::ID,
+    )
+}
+
+pub fn collection_duration(collection_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[DURATION_SEED.as_bytes(), collection_id.as_ref()],
+        &raffle::ID,
+    )
+}
+
+pub fn metadata_address(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            metaplex_token_metadata::state::PREFIX.as_bytes(),
+            metaplex_token_metadata::id().as_ref(),
+            mint.as_ref(),
+        ],
+        &metaplex_token_metadata::id(),
+    )
+    .0
+}
+
+pub fn treasury_wallet() -> Pubkey {
+    TREASURY_WALLET.parse().unwrap()
+}
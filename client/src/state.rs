@@ -0,0 +1,79 @@
+use std::mem::size_of;
+
+use anchor_lang::{error::ErrorCode, AccountDeserialize, Result, ZeroCopy};
+use bytemuck::Zeroable;
+use raffle::account::*;
+use solana_program::pubkey::Pubkey;
+
+pub fn global_pool(data: &[u8]) -> Result<GlobalPool> {
+    GlobalPool::try_deserialize(&mut &data[..])
+}
+
+pub fn referrer_pool(data: &[u8]) -> Result<ReferrerPool> {
+    ReferrerPool::try_deserialize(&mut &data[..])
+}
+
+pub fn voucher_pool(data: &[u8]) -> Result<VoucherPool> {
+    VoucherPool::try_deserialize(&mut &data[..])
+}
+
+pub fn collection_duration(data: &[u8]) -> Result<CollectionDuration> {
+    CollectionDuration::try_deserialize(&mut &data[..])
+}
+
+pub fn collection_pool(data: &[u8]) -> Result<CollectionPool> {
+    if is_legacy_collection(data) {
+        // the fields before the version header, read with version 0
+        check_discriminator::<CollectionPool>(data)?;
+        let mut collection = CollectionPool::zeroed();
+        bytemuck::bytes_of_mut(&mut collection)[U64_LENGTH..]
+            .copy_from_slice(&data[DISCRIMINATOR_LENGTH..]);
+        return Ok(collection);
+    }
+    load_zero_copy(data)
+}
+
+pub fn raffle_pool(data: &[u8]) -> Result<RafflePool> {
+    if is_legacy_raffle(data) {
+        // the first release layout remapped like `migrate_raffle`, read with version 0
+        check_discriminator::<RafflePool>(data)?;
+        let mut raffle = RafflePool::zeroed();
+        raffle.set_legacy_fields(&LegacyRaffleHeader::read(data));
+        raffle.version = 0;
+        let entrants = data[RafflePool::LEGACY_ENTRANTS_OFFSET..].chunks(PUBLIC_KEY_LENGTH);
+        for (entrant, key) in raffle.entrants.iter_mut().zip(entrants) {
+            *entrant = Pubkey::new(key);
+        }
+        return Ok(raffle);
+    }
+    load_zero_copy(data)
+}
+
+// the raffle still has the layout of the first release and needs `migrate_raffle`
+pub fn is_legacy_raffle(data: &[u8]) -> bool {
+    data.len() == RafflePool::LEGACY_LEN
+}
+
+// the collection pool still has the layout before the version header and needs `migrate_collection`
+pub fn is_legacy_collection(data: &[u8]) -> bool {
+    data.len() == CollectionPool::LEGACY_LEN
+}
+
+fn check_discriminator<T: ZeroCopy>(data: &[u8]) -> Result<()> {
+    if data[..DISCRIMINATOR_LENGTH] != T::discriminator() {
+        return Err(ErrorCode::AccountDiscriminatorMismatch.into());
+    }
+    Ok(())
+}
+
+// copies a zero copy account out of its data like `AccountLoader::load` reads it
+fn load_zero_copy<T: ZeroCopy>(data: &[u8]) -> Result<T> {
+    let len = DISCRIMINATOR_LENGTH + size_of::<T>();
+    if data.len() < len {
+        return Err(ErrorCode::AccountDidNotDeserialize.into());
+    }
+    check_discriminator::<T>(data)?;
+    let mut account = T::zeroed();
+    bytemuck::bytes_of_mut(&mut account).copy_from_slice(&data[DISCRIMINATOR_LENGTH..len]);
+    Ok(account)
+}
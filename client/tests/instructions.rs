@@ -0,0 +1,421 @@
+use anchor_lang::AnchorDeserialize;
+use raffle::{
+    account::{CreateRaffleParams, DiscountTier, RafflePool, RoyaltyShare},
+    constants::*,
+};
+use raffle_client::{instructions::*, pda};
+use solana_program::{
+    hash::hash,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+
+// the first 8 bytes of sha256("global:<handler>") which anchor dispatches on
+fn sighash(handler: &str) -> [u8; 8] {
+    let mut sighash = [0; 8];
+    sighash.copy_from_slice(&hash(format!("global:{}", handler).as_bytes()).to_bytes()[..8]);
+    sighash
+}
+
+// the handler's arguments after checking the instruction calls it
+fn decode_args<T: AnchorDeserialize>(instruction: &Instruction, handler: &str) -> T {
+    assert_eq!(instruction.program_id, raffle_client::ID);
+    assert_eq!(instruction.data[..8], sighash(handler), "{}", handler);
+    T::try_from_slice(&instruction.data[8..]).unwrap()
+}
+
+fn raffle_state() -> RafflePool {
+    let mut raffle = RafflePool {
+        creator: Pubkey::new_unique(),
+        nft_mint: Pubkey::new_unique(),
+        winner: Pubkey::new_unique(),
+        royalty_count: 2,
+        ..RafflePool::default()
+    };
+    raffle.royalties[0].recipient = Pubkey::new_unique();
+    raffle.royalties[1].recipient = Pubkey::new_unique();
+    raffle
+}
+
+#[test]
+fn pdas_use_the_seeds_of_the_program() {
+    let creator = Pubkey::new_unique();
+    let nft_mint = Pubkey::new_unique();
+    let raffle = pda::raffle_address(&creator, &nft_mint, 7);
+    let escrow_authority = pda::escrow_authority(&raffle.0);
+
+    assert_eq!(
+        pda::global_authority(),
+        Pubkey::find_program_address(&[b"global-authority"], &raffle::ID)
+    );
+    assert_eq!(
+        raffle,
+        Pubkey::find_program_address(
+            &[
+                b"raffle",
+                creator.as_ref(),
+                nft_mint.as_ref(),
+                &7u64.to_le_bytes()
+            ],
+            &raffle::ID
+        )
+    );
+    assert_ne!(raffle, pda::raffle_address(&creator, &nft_mint, 8));
+    assert_eq!(
+        escrow_authority,
+        Pubkey::find_program_address(&[b"escrow-authority", raffle.0.as_ref()], &raffle::ID)
+    );
+    assert_eq!(
+        pda::referrer_pool(&creator),
+        Pubkey::find_program_address(&[b"referrer", creator.as_ref()], &raffle::ID)
+    );
+    assert_eq!(
+        pda::voucher(42),
+        Pubkey::find_program_address(&[b"voucher", &42u64.to_le_bytes()], &raffle::ID)
+    );
+    assert_eq!(
+        pda::receipt(&raffle.0, &creator),
+        Pubkey::find_program_address(
+            &[b"receipt", raffle.0.as_ref(), creator.as_ref()],
+            &raffle::ID
+        )
+    );
+    assert_eq!(
+        pda::collection_duration(&nft_mint),
+        Pubkey::find_program_address(&[b"duration", nft_mint.as_ref()], &raffle::ID)
+    );
+    assert_eq!(
+        pda::collection_pool(&creator).unwrap(),
+        Pubkey::create_with_seed(&creator, "collection-pool", &raffle::ID).unwrap()
+    );
+    assert_eq!(
+        pda::escrow_token_account(&raffle.0, &nft_mint),
+        Pubkey::find_program_address(
+            &[
+                escrow_authority.0.as_ref(),
+                anchor_spl::token::ID.as_ref(),
+                nft_mint.as_ref()
+            ],
+            &spl_associated_token_account::ID
+        )
+        .0
+    );
+    assert_eq!(
+        pda::metadata_address(&nft_mint),
+        Pubkey::find_program_address(
+            &[
+                b"metadata",
+                metaplex_token_metadata::id().as_ref(),
+                nft_mint.as_ref()
+            ],
+            &metaplex_token_metadata::id()
+        )
+        .0
+    );
+    assert_eq!(pda::treasury_wallet().to_string(), TREASURY_WALLET);
+    assert_eq!(
+        pda::legacy_nft_token_account(&nft_mint),
+        anchor_spl::associated_token::get_associated_token_address(
+            &pda::global_authority().0,
+            &nft_mint
+        )
+    );
+
+    // the program checks the raffle PDA with its stored bump
+    assert_eq!(
+        Pubkey::create_program_address(
+            &[
+                b"raffle",
+                creator.as_ref(),
+                nft_mint.as_ref(),
+                &7u64.to_le_bytes(),
+                &[raffle.1]
+            ],
+            &raffle::ID
+        )
+        .unwrap(),
+        raffle.0
+    );
+}
+
+#[test]
+fn instructions_call_their_handler() {
+    let admin = Pubkey::new_unique();
+    let user = Pubkey::new_unique();
+    let raffle = Pubkey::new_unique();
+    let collection = Pubkey::new_unique();
+    let mint = Pubkey::new_unique();
+    let state = raffle_state();
+
+    let instructions = [
+        (initialize(&admin, &collection), "initialize"),
+        (add_collection(&admin, &collection, &mint), "add_collection"),
+        (set_referral_share(&admin, 10), "set_referral_share"),
+        (set_pauser(&admin, &user), "set_pauser"),
+        (set_paused(&admin, true), "set_paused"),
+        (
+            set_voucher_authority(&admin, &user),
+            "set_voucher_authority",
+        ),
+        (set_claim_window(&admin, DAY), "set_claim_window"),
+        (set_raffle_duration(&admin, DAY, 0), "set_raffle_duration"),
+        (
+            init_collection_duration(&admin, &mint, DAY, 0),
+            "init_collection_duration",
+        ),
+        (
+            set_collection_duration(&admin, &mint, DAY, 0),
+            "set_collection_duration",
+        ),
+        (init_referrer(&user), "init_referrer"),
+        (allocate_raffle(&user, &admin, &mint, 0), "allocate_raffle"),
+        (
+            create_raffle(
+                &admin,
+                &collection,
+                &mint,
+                &user,
+                &mint,
+                CreateRaffleParams::default(),
+            ),
+            "create_raffle",
+        ),
+        (buy_tickets(&user, &raffle, &state, 1, None), "buy_tickets"),
+        (
+            burn_tokens_for_tickets(&user, &raffle, &mint, 1),
+            "burn_tokens_for_tickets",
+        ),
+        (
+            burn_nft_for_ticket(&user, &raffle, &mint),
+            "burn_nft_for_ticket",
+        ),
+        (claim_free_ticket(&user, &raffle, 1, 1), "claim_free_ticket"),
+        (reveal_winner(&user, &raffle), "reveal_winner"),
+        (reroll_winner(&user, &raffle, &state), "reroll_winner"),
+        (claim_reward(&user, &raffle, &state, &mint), "claim_reward"),
+        (settle_raffle(&user, &raffle, &state), "settle_raffle"),
+        (withdraw_nft(&user, &raffle, &state, &mint), "withdraw_nft"),
+        (
+            set_raffle_frozen(&admin, &raffle, true),
+            "set_raffle_frozen",
+        ),
+        (
+            force_cancel_raffle(&admin, &raffle, &state),
+            "force_cancel_raffle",
+        ),
+        (claim_refund(&user, &raffle), "claim_refund"),
+        (migrate_global(&admin), "migrate_global"),
+        (migrate_raffle(&user, &raffle, &state), "migrate_raffle"),
+        (
+            migrate_collection(&admin, &collection),
+            "migrate_collection",
+        ),
+    ];
+    for (instruction, handler) in &instructions {
+        assert_eq!(instruction.program_id, raffle_client::ID);
+        assert_eq!(instruction.data[..8], sighash(handler), "{}", handler);
+        // the first account signs every instruction
+        assert!(instruction.accounts[0].is_signer, "{}", handler);
+    }
+}
+
+#[test]
+fn create_raffle_encodes_its_arguments() {
+    let creator = Pubkey::new_unique();
+    let collection = Pubkey::new_unique();
+    let collection_id = Pubkey::new_unique();
+    let owner_nft_token_account = Pubkey::new_unique();
+    let nft_mint = Pubkey::new_unique();
+    let burn_target = Pubkey::new_unique();
+    let royalty_recipient = Pubkey::new_unique();
+
+    let instruction = create_raffle(
+        &creator,
+        &collection,
+        &collection_id,
+        &owner_nft_token_account,
+        &nft_mint,
+        CreateRaffleParams {
+            nonce: 3,
+            ticket_price_sol: 100_000,
+            start_timestamp: 10,
+            end_timestamp: 10 + DAY,
+            max_entrants: 500,
+            discounts: vec![DiscountTier {
+                min_tickets: 5,
+                discount_percent: 10,
+            }],
+            use_nft_creators: false,
+            royalties: vec![RoyaltyShare {
+                recipient: royalty_recipient,
+                basis_points: 250,
+            }],
+            payment_mode: PAYMENT_BURN_TOKEN,
+            burn_target,
+            return_unclaimed: true,
+            instant_win: true,
+        },
+    );
+    let args: raffle::instruction::CreateRaffle = decode_args(&instruction, "create_raffle");
+    assert_eq!(args.nonce, 3);
+    assert_eq!(args.ticket_price_sol, 100_000);
+    assert_eq!(args.start_timestamp, 10);
+    assert_eq!(args.end_timestamp, 10 + DAY);
+    assert_eq!(args.max_entrants, 500);
+    assert_eq!(args.discounts.len(), 1);
+    assert_eq!(args.discounts[0].min_tickets, 5);
+    assert_eq!(args.discounts[0].discount_percent, 10);
+    assert!(!args.use_nft_creators);
+    assert_eq!(args.royalties.len(), 1);
+    assert_eq!(args.royalties[0].recipient, royalty_recipient);
+    assert_eq!(args.royalties[0].basis_points, 250);
+    assert_eq!(args.payment_mode, PAYMENT_BURN_TOKEN);
+    assert_eq!(args.burn_target, burn_target);
+    assert!(args.return_unclaimed);
+    assert!(args.instant_win);
+
+    let raffle = pda::raffle_address(&creator, &nft_mint, 3).0;
+    let accounts: Vec<Pubkey> = instruction
+        .accounts
+        .iter()
+        .map(|account| account.pubkey)
+        .collect();
+    assert_eq!(
+        accounts,
+        [
+            creator,
+            pda::global_authority().0,
+            raffle,
+            pda::escrow_authority(&raffle).0,
+            collection,
+            pda::collection_duration(&collection_id).0,
+            owner_nft_token_account,
+            pda::escrow_token_account(&raffle, &nft_mint),
+            nft_mint,
+            pda::metadata_address(&nft_mint),
+            anchor_spl::token::ID,
+            system_program::ID,
+            sysvar::rent::ID,
+            metaplex_token_metadata::id(),
+        ]
+    );
+    assert!(instruction.accounts[0].is_signer);
+}
+
+#[test]
+fn buy_tickets_appends_the_royalty_recipients_and_the_referrer() {
+    let buyer = Pubkey::new_unique();
+    let raffle = Pubkey::new_unique();
+    let referrer = Pubkey::new_unique();
+    let state = raffle_state();
+
+    let instruction = buy_tickets(&buyer, &raffle, &state, 12, Some(&referrer));
+    let args: raffle::instruction::BuyTickets = decode_args(&instruction, "buy_tickets");
+    assert_eq!(args.amount, 12);
+    assert_eq!(
+        instruction.accounts,
+        [
+            AccountMeta::new(buyer, true),
+            AccountMeta::new(raffle, false),
+            AccountMeta::new(pda::global_authority().0, false),
+            AccountMeta::new(state.creator, false),
+            AccountMeta::new(pda::treasury_wallet(), false),
+            AccountMeta::new(pda::receipt(&raffle, &buyer).0, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+            AccountMeta::new(state.royalties[0].recipient, false),
+            AccountMeta::new(state.royalties[1].recipient, false),
+            AccountMeta::new(referrer, false),
+            AccountMeta::new(pda::referrer_pool(&referrer).0, false),
+        ]
+    );
+
+    // without a referrer only the royalty recipients follow the program accounts
+    let instruction = buy_tickets(&buyer, &raffle, &state, 12, None);
+    assert_eq!(instruction.accounts.len(), 9);
+}
+
+#[test]
+fn migrate_raffle_moves_the_nft_from_the_global_authority_to_the_escrow() {
+    let payer = Pubkey::new_unique();
+    let raffle = Pubkey::new_unique();
+    let state = raffle_state();
+
+    let instruction = migrate_raffle(&payer, &raffle, &state);
+    assert_eq!(
+        instruction.accounts,
+        [
+            AccountMeta::new(payer, true),
+            AccountMeta::new_readonly(pda::global_authority().0, false),
+            AccountMeta::new(raffle, false),
+            AccountMeta::new_readonly(pda::escrow_authority(&raffle).0, false),
+            AccountMeta::new(pda::legacy_nft_token_account(&state.nft_mint), false),
+            AccountMeta::new(pda::escrow_token_account(&raffle, &state.nft_mint), false),
+            AccountMeta::new_readonly(anchor_spl::token::ID, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ]
+    );
+}
+
+#[test]
+fn instructions_encode_their_arguments() {
+    let admin = Pubkey::new_unique();
+    let user = Pubkey::new_unique();
+    let raffle = Pubkey::new_unique();
+
+    let args: raffle::instruction::ClaimFreeTicket = decode_args(
+        &claim_free_ticket(&user, &raffle, 77, 3),
+        "claim_free_ticket",
+    );
+    assert_eq!((args.voucher_id, args.amount), (77, 3));
+    let instruction = claim_free_ticket(&user, &raffle, 77, 3);
+    assert_eq!(instruction.accounts[3].pubkey, pda::voucher(77).0);
+    assert_eq!(instruction.accounts[4].pubkey, sysvar::instructions::ID);
+
+    let args: raffle::instruction::BurnTokensForTickets = decode_args(
+        &burn_tokens_for_tickets(&user, &raffle, &admin, 4),
+        "burn_tokens_for_tickets",
+    );
+    assert_eq!(args.amount, 4);
+
+    let args: raffle::instruction::SetRaffleDuration = decode_args(
+        &set_raffle_duration(&admin, DAY, 7 * DAY),
+        "set_raffle_duration",
+    );
+    assert_eq!((args.min_duration, args.max_duration), (DAY, 7 * DAY));
+
+    let args: raffle::instruction::SetClaimWindow =
+        decode_args(&set_claim_window(&admin, 3 * DAY), "set_claim_window");
+    assert_eq!(args.claim_window, 3 * DAY);
+
+    let args: raffle::instruction::SetReferralShare =
+        decode_args(&set_referral_share(&admin, 25), "set_referral_share");
+    assert_eq!(args.referral_share, 25);
+
+    let args: raffle::instruction::SetPaused = decode_args(&set_paused(&admin, true), "set_paused");
+    assert!(args.paused);
+
+    let args: raffle::instruction::SetRaffleFrozen = decode_args(
+        &set_raffle_frozen(&admin, &raffle, true),
+        "set_raffle_frozen",
+    );
+    assert!(args.frozen);
+
+    let args: raffle::instruction::SetVoucherAuthority = decode_args(
+        &set_voucher_authority(&admin, &user),
+        "set_voucher_authority",
+    );
+    assert_eq!(args.voucher_authority, user);
+
+    // every step allocates the same raffle PDA
+    let steps = allocate_raffle_steps(&user, &admin, &raffle, 9);
+    assert_eq!(steps.len(), RafflePool::ALLOCATE_STEPS);
+    for step in &steps {
+        let args: raffle::instruction::AllocateRaffle = decode_args(step, "allocate_raffle");
+        assert_eq!(args.nonce, 9);
+        assert_eq!(
+            step.accounts[3].pubkey,
+            pda::raffle_address(&admin, &raffle, 9).0
+        );
+    }
+}
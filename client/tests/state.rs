@@ -0,0 +1,176 @@
+use anchor_lang::{AccountSerialize, Discriminator};
+use raffle::{
+    account::{
+        CollectionPool, GlobalPool, RafflePool, ReferrerPool, DISCRIMINATOR_LENGTH, U64_LENGTH,
+    },
+    constants::*,
+};
+use raffle_client::state;
+use solana_program::pubkey::Pubkey;
+
+// the account data like the program stores a zero copy account
+fn zero_copy_data<T: bytemuck::Pod + Discriminator>(account: &T) -> Vec<u8> {
+    let mut data = T::discriminator().to_vec();
+    data.extend_from_slice(bytemuck::bytes_of(account));
+    data
+}
+
+// the same account without the version header in front of its fields
+fn legacy_data<T: bytemuck::Pod + Discriminator>(account: &T) -> Vec<u8> {
+    let mut data = T::discriminator().to_vec();
+    data.extend_from_slice(&bytemuck::bytes_of(account)[U64_LENGTH..]);
+    data
+}
+
+fn drawn_raffle() -> RafflePool {
+    let mut raffle = RafflePool {
+        creator: Pubkey::new_unique(),
+        nft_mint: Pubkey::new_unique(),
+        max_entrants: 100,
+        ticket_price_sol: 1_000_000,
+        end_timestamp: 1_700_000_000,
+        nonce: 4,
+        ..RafflePool::default()
+    };
+    raffle.add_entrant(Pubkey::new_unique(), 3).unwrap();
+    raffle.add_entrant(Pubkey::new_unique(), 2).unwrap();
+    raffle.draw_winner(1_700_000_000).unwrap();
+    raffle
+}
+
+#[test]
+fn raffle_pool_decodes_the_current_layout() {
+    let raffle = drawn_raffle();
+    let data = zero_copy_data(&raffle);
+    assert_eq!(data.len(), RafflePool::LEN);
+    assert!(!state::is_legacy_raffle(&data));
+
+    let decoded = state::raffle_pool(&data).unwrap();
+    assert_eq!(bytemuck::bytes_of(&decoded), bytemuck::bytes_of(&raffle));
+    assert_eq!(decoded.version, RAFFLE_VERSION);
+    assert_eq!(
+        decoded.winner,
+        raffle.entrants[raffle.winner_index as usize]
+    );
+    assert_eq!(decoded.nonce, 4);
+}
+
+// a drawn raffle of the first release, its fields in front of the 2000 entrants and no nonce
+fn baseline_raffle_data(creator: &Pubkey, nft_mint: &Pubkey, entrants: &[Pubkey]) -> Vec<u8> {
+    let mut data = RafflePool::discriminator().to_vec();
+    data.extend_from_slice(creator.as_ref());
+    data.extend_from_slice(nft_mint.as_ref());
+    let count = entrants.len() as u64;
+    for field in [count, 2, 100, 1_699_000_000, 1_700_000_000, 1_000_000, 2, 1] {
+        data.extend_from_slice(&field.to_le_bytes());
+    }
+    data.extend_from_slice(entrants[1].as_ref());
+    for entrant in entrants {
+        data.extend_from_slice(entrant.as_ref());
+    }
+    data.resize(64168, 0);
+    data
+}
+
+#[test]
+fn raffle_pool_decodes_the_baseline_layout_as_version_0() {
+    let creator = Pubkey::new_unique();
+    let nft_mint = Pubkey::new_unique();
+    let buyers = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let entrants = [buyers[0], buyers[1], buyers[0]];
+    let data = baseline_raffle_data(&creator, &nft_mint, &entrants);
+    assert_eq!(data.len(), RafflePool::LEGACY_LEN);
+    assert!(state::is_legacy_raffle(&data));
+
+    let decoded = state::raffle_pool(&data).unwrap();
+    assert_eq!(decoded.version, 0);
+    assert_eq!(decoded.creator, creator);
+    assert_eq!(decoded.nft_mint, nft_mint);
+    assert_eq!(decoded.count, 3);
+    assert_eq!(decoded.no_repeat, 2);
+    assert_eq!(decoded.max_entrants, 100);
+    assert_eq!(decoded.start_timestamp, 1_699_000_000);
+    assert_eq!(decoded.end_timestamp, 1_700_000_000);
+    assert_eq!(decoded.ticket_price_sol, 1_000_000);
+    assert_eq!(decoded.claimed, 2);
+    assert_eq!(decoded.winner_index, 1);
+    assert_eq!(decoded.winner, buyers[1]);
+    // the first release kept no receipts to refund the tickets from
+    assert_eq!(decoded.total_paid, 0);
+    assert_eq!(decoded.payment_mode, PAYMENT_SOL);
+    assert_eq!(decoded.nonce, 0);
+    assert_eq!(decoded.entrants[..3], entrants);
+    assert_eq!(decoded.entrants[3], Pubkey::default());
+}
+
+#[test]
+fn raffle_pool_rejects_other_accounts() {
+    let raffle = drawn_raffle();
+    let mut data = zero_copy_data(&raffle);
+    data[..DISCRIMINATOR_LENGTH].copy_from_slice(&CollectionPool::discriminator());
+    assert!(state::raffle_pool(&data).is_err());
+
+    let data = zero_copy_data(&raffle);
+    assert!(state::raffle_pool(&data[..RafflePool::LEN - 1]).is_err());
+    assert!(state::raffle_pool(&data[..RafflePool::LEGACY_LEN - 1]).is_err());
+    assert!(state::raffle_pool(&[]).is_err());
+}
+
+#[test]
+fn collection_pool_decodes_the_current_and_legacy_layouts() {
+    let mut collection = CollectionPool::default();
+    let collection_id = Pubkey::new_unique();
+    collection.append(collection_id);
+
+    let data = zero_copy_data(&collection);
+    assert_eq!(data.len(), CollectionPool::LEN);
+    assert!(!state::is_legacy_collection(&data));
+    let decoded = state::collection_pool(&data).unwrap();
+    assert_eq!(decoded.version, COLLECTION_VERSION);
+    assert_eq!(decoded.count, 1);
+    assert_eq!(decoded.collections[0], collection_id);
+
+    let data = legacy_data(&collection);
+    assert_eq!(data.len(), CollectionPool::LEGACY_LEN);
+    assert!(state::is_legacy_collection(&data));
+    let decoded = state::collection_pool(&data).unwrap();
+    assert_eq!(decoded.version, 0);
+    assert_eq!(decoded.count, 1);
+    assert_eq!(decoded.collections[0], collection_id);
+}
+
+#[test]
+fn anchor_accounts_decode_their_serialized_data() {
+    let global = GlobalPool {
+        super_admin: Pubkey::new_unique(),
+        referral_share: 20,
+        claim_window: DAY,
+        min_duration: DAY,
+        bump: 254,
+        ..GlobalPool::default()
+    };
+    let mut data = vec![];
+    global.try_serialize(&mut data).unwrap();
+    assert_eq!(data.len(), GlobalPool::LEN);
+    let decoded = state::global_pool(&data).unwrap();
+    assert_eq!(decoded.super_admin, global.super_admin);
+    assert_eq!(decoded.referral_share, 20);
+    assert_eq!(decoded.claim_window, DAY);
+    assert_eq!(decoded.bump, 254);
+
+    // the global authority of the first release has to be migrated first
+    assert!(state::global_pool(&data[..GlobalPool::LEGACY_LEN]).is_err());
+
+    let referrer = ReferrerPool {
+        referrer: Pubkey::new_unique(),
+        total_earned: 5_000,
+        referral_count: 2,
+    };
+    let mut data = vec![];
+    referrer.try_serialize(&mut data).unwrap();
+    let decoded = state::referrer_pool(&data).unwrap();
+    assert_eq!(decoded.referrer, referrer.referrer);
+    assert_eq!(decoded.total_earned, 5_000);
+    // an account of another type isn't read as a referrer pool
+    assert!(state::global_pool(&data).is_err());
+}
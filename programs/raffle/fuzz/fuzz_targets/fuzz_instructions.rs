@@ -0,0 +1,261 @@
+// Throws random instruction data and account permutations at every handler
+// of the raffle program and checks after each transaction that
+// - the prize NFT only leaves the escrow to the raffle's winner or creator
+// - no wallet loses lamports without signing the transaction
+// - the raffle's ticket accounting stays consistent
+//
+// Run with `cargo hfuzz run fuzz_instructions` in this directory.
+
+use std::collections::HashSet;
+
+use anchor_spl::associated_token::get_associated_token_address;
+use arbitrary::Arbitrary;
+use honggfuzz::fuzz;
+use raffle::constants::*;
+use solana_program::{hash::hash, pubkey::Pubkey, system_program, sysvar};
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    signature::{Keypair, Signer},
+};
+
+#[path = "../../tests/common/mod.rs"]
+mod common;
+
+use common::*;
+
+const HANDLERS: &[&str] = &[
+    "initialize",
+    "add_collection",
+    "set_referral_share",
+    "set_pauser",
+    "set_paused",
+    "set_voucher_authority",
+    "set_claim_window",
+    "set_raffle_duration",
+    "init_collection_duration",
+    "set_collection_duration",
+    "init_referrer",
+    "allocate_raffle",
+    "create_raffle",
+    "buy_tickets",
+    "burn_tokens_for_tickets",
+    "burn_nft_for_ticket",
+    "claim_free_ticket",
+    "reveal_winner",
+    "reroll_winner",
+    "claim_reward",
+    "settle_raffle",
+    "withdraw_nft",
+    "set_raffle_frozen",
+    "force_cancel_raffle",
+    "claim_refund",
+    "migrate_global",
+    "migrate_raffle",
+    "migrate_collection",
+];
+
+#[derive(Debug, Arbitrary)]
+struct FuzzAccount {
+    index: u8,
+    is_signer: bool,
+    is_writable: bool,
+}
+
+#[derive(Debug, Arbitrary)]
+struct FuzzInstruction {
+    handler: u8,
+    data: Vec<u8>,
+    accounts: Vec<FuzzAccount>,
+    // seconds to move the clock forward before the instruction
+    warp: u32,
+}
+
+#[derive(Debug, Arbitrary)]
+struct FuzzData {
+    instructions: Vec<FuzzInstruction>,
+}
+
+struct FuzzEnv {
+    test: RaffleTest,
+    wallets: Vec<Keypair>,
+    accounts: Vec<Pubkey>,
+    raffle: Pubkey,
+    escrow_nft: Pubkey,
+    nft_accounts: Vec<Pubkey>,
+    timestamp: i64,
+}
+
+impl FuzzEnv {
+    // a running raffle with tickets of two buyers, every wallet has an empty
+    // ATA of the prize NFT to receive it, so settle_raffle can pay the winner
+    async fn new() -> FuzzEnv {
+        let mut test = RaffleTest::start_initialized().await;
+        let (creator, nft, raffle) = test.create_default_raffle(raffle_params()).await;
+        let buyer = test.new_wallet(10 * LAMPORTS_PER_SOL);
+        let other_buyer = test.new_wallet(10 * LAMPORTS_PER_SOL);
+        let attacker = test.new_wallet(10 * LAMPORTS_PER_SOL);
+        test.buy_tickets(&buyer, &raffle, 3).await.unwrap();
+        test.buy_tickets(&other_buyer, &raffle, 2).await.unwrap();
+
+        let wallets = vec![
+            clone_keypair(&test.admin),
+            creator,
+            buyer,
+            other_buyer,
+            attacker,
+        ];
+        let nft_accounts: Vec<Pubkey> = wallets
+            .iter()
+            .map(|wallet| {
+                let nft_account = get_associated_token_address(&wallet.pubkey(), &nft.mint);
+                test.set_token_account(&nft_account, &nft.mint, &wallet.pubkey(), 0);
+                nft_account
+            })
+            .collect();
+        let escrow_nft = escrow_token_account(&raffle, &nft.mint);
+
+        let mut accounts: Vec<Pubkey> = wallets.iter().map(|wallet| wallet.pubkey()).collect();
+        accounts.extend(nft_accounts.iter());
+        accounts.extend([
+            raffle,
+            escrow_authority(&raffle),
+            escrow_nft,
+            nft.mint,
+            nft.metadata,
+            global_authority().0,
+            test.collection,
+            test.collection_id,
+            collection_duration(&test.collection_id),
+            treasury_wallet(),
+            system_program::id(),
+            spl_token::id(),
+            anchor_spl::associated_token::ID,
+            metaplex_token_metadata::id(),
+            sysvar::rent::id(),
+            sysvar::instructions::id(),
+            Pubkey::new_unique(),
+        ]);
+
+        FuzzEnv {
+            test,
+            wallets,
+            accounts,
+            raffle,
+            escrow_nft,
+            nft_accounts,
+            timestamp: NOW,
+        }
+    }
+
+    fn instruction(&self, fuzz_instruction: &FuzzInstruction) -> (Instruction, Vec<&Keypair>) {
+        let handler = HANDLERS[fuzz_instruction.handler as usize % HANDLERS.len()];
+        let mut data = hash(format!("global:{}", handler).as_bytes()).to_bytes()[..8].to_vec();
+        data.extend_from_slice(&fuzz_instruction.data);
+
+        let mut signers: Vec<&Keypair> = vec![];
+        let accounts = fuzz_instruction
+            .accounts
+            .iter()
+            .map(|account| {
+                let pubkey = self.accounts[account.index as usize % self.accounts.len()];
+                // only the wallets can sign
+                let wallet = self.wallets.iter().find(|wallet| wallet.pubkey() == pubkey);
+                let is_signer = account.is_signer && wallet.is_some();
+                if let Some(wallet) = wallet.filter(|_| is_signer) {
+                    if !signers.iter().any(|signer| signer.pubkey() == pubkey) {
+                        signers.push(wallet);
+                    }
+                }
+                AccountMeta {
+                    pubkey,
+                    is_signer,
+                    is_writable: account.is_writable,
+                }
+            })
+            .collect();
+
+        let instruction = Instruction {
+            program_id: raffle::id(),
+            accounts,
+            data,
+        };
+        (instruction, signers)
+    }
+
+    async fn run(&mut self, fuzz_instruction: &FuzzInstruction) {
+        if fuzz_instruction.warp > 0 {
+            self.timestamp += i64::from(fuzz_instruction.warp);
+            self.test.set_timestamp(self.timestamp).await;
+        }
+
+        let wallets: Vec<Pubkey> = self.wallets.iter().map(|wallet| wallet.pubkey()).collect();
+        let mut balances = vec![];
+        for wallet in &wallets {
+            balances.push(self.test.balance(wallet).await);
+        }
+        let escrow_amount = self.test.token_amount(&self.escrow_nft).await;
+        let mut nft_amounts = vec![];
+        for nft_account in self.nft_accounts.clone() {
+            nft_amounts.push(self.test.token_amount(&nft_account).await);
+        }
+
+        let (instruction, signers) = self.instruction(fuzz_instruction);
+        let signed: HashSet<Pubkey> = signers.iter().map(|signer| signer.pubkey()).collect();
+        let signers: Vec<Keypair> = signers.into_iter().map(clone_keypair).collect();
+        let signer_refs: Vec<&Keypair> = signers.iter().collect();
+        let _ = self.test.process(&[instruction], &signer_refs).await;
+
+        // no wallet loses lamports without signing
+        for (wallet, balance) in wallets.iter().zip(balances) {
+            if !signed.contains(wallet) {
+                assert!(
+                    self.test.balance(wallet).await >= balance,
+                    "{} lost lamports without signing",
+                    wallet
+                );
+            }
+        }
+
+        // the NFT only leaves the escrow to the winner or the creator
+        let state = self.test.raffle_state(&self.raffle).await;
+        let escrow_sent =
+            escrow_amount.saturating_sub(self.test.token_amount(&self.escrow_nft).await);
+        let mut received = 0;
+        for (index, nft_account) in self.nft_accounts.clone().iter().enumerate() {
+            let amount = self.test.token_amount(nft_account).await;
+            if amount > nft_amounts[index] {
+                let owner = wallets[index];
+                assert!(
+                    owner == state.winner || owner == state.creator,
+                    "the NFT was sent to {} who is not the winner or the creator",
+                    owner
+                );
+                received += amount - nft_amounts[index];
+            }
+        }
+        assert_eq!(escrow_sent, received);
+
+        // the ticket accounting stays consistent
+        assert!(state.count <= state.max_entrants);
+        assert!(state.max_entrants <= MAX_ENTRANTS as u64);
+        assert!(state.active_tickets() <= state.count);
+        if state.claimed == 2 {
+            assert!(state.winner_index < state.count);
+            assert_eq!(state.winner, state.entrants[state.winner_index as usize]);
+        }
+    }
+}
+
+fn main() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    loop {
+        fuzz!(|data: FuzzData| {
+            runtime.block_on(async {
+                let mut env = FuzzEnv::new().await;
+                for fuzz_instruction in data.instructions.iter().take(16) {
+                    env.run(fuzz_instruction).await;
+                }
+            });
+        });
+    }
+}
@@ -1,51 +1,243 @@
 use anchor_lang::prelude::*;
+use solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE;
 use std::clone::Clone;
-use std::result::Result;
 
 use crate::constants::*;
 use crate::error::*;
+use crate::utils::*;
 
 #[account]
 #[derive(Default)]
 pub struct GlobalPool {
-    pub super_admin: Pubkey, // 32
+    pub super_admin: Pubkey,       // 32
+    pub referral_share: u64,       // 8
+    pub pauser: Pubkey,            // 32
+    pub paused: bool,              // 1
+    pub voucher_authority: Pubkey, // 32
+    pub claim_window: i64,         // 8
+    pub min_duration: i64,         // 8
+    pub max_duration: i64,         // 8
+    pub bump: u8,                  // 1
 }
 
 pub const DISCRIMINATOR_LENGTH: usize = 8;
 pub const PUBLIC_KEY_LENGTH: usize = 32;
+pub const U64_LENGTH: usize = 8;
+pub const BOOL_LENGTH: usize = 1;
+pub const U8_LENGTH: usize = 1;
 
 impl GlobalPool {
-    pub const LEN: usize = DISCRIMINATOR_LENGTH + PUBLIC_KEY_LENGTH;
+    pub const LEN: usize =
+        DISCRIMINATOR_LENGTH + PUBLIC_KEY_LENGTH * 3 + U64_LENGTH * 4 + BOOL_LENGTH + U8_LENGTH;
+    // the first release only stored the super admin
+    pub const LEGACY_LEN: usize = DISCRIMINATOR_LENGTH + PUBLIC_KEY_LENGTH;
+}
+
+// overrides the global raffle duration bounds for the NFTs of one collection
+#[account]
+#[derive(Default)]
+pub struct CollectionDuration {
+    pub collection: Pubkey, // 32
+    pub min_duration: i64,  // 8
+    pub max_duration: i64,  // 8
+}
+
+impl CollectionDuration {
+    pub const LEN: usize = DISCRIMINATOR_LENGTH + PUBLIC_KEY_LENGTH + U64_LENGTH * 2;
+}
+
+#[account]
+#[derive(Default)]
+pub struct ReferrerPool {
+    pub referrer: Pubkey,    // 32
+    pub total_earned: u64,   // 8
+    pub referral_count: u64, // 8
+}
+
+impl ReferrerPool {
+    pub const LEN: usize = DISCRIMINATOR_LENGTH + PUBLIC_KEY_LENGTH + U64_LENGTH * 2;
+}
+
+#[account]
+#[derive(Default)]
+pub struct VoucherPool {
+    pub raffle: Pubkey,  // 32
+    pub claimer: Pubkey, // 32
+    pub amount: u64,     // 8
+}
+
+impl VoucherPool {
+    pub const LEN: usize = DISCRIMINATOR_LENGTH + PUBLIC_KEY_LENGTH * 2 + U64_LENGTH;
+}
+
+// the SOL a buyer paid for the tickets of one raffle, refunded if the raffle is cancelled
+#[account]
+#[derive(Default)]
+pub struct TicketReceipt {
+    pub raffle: Pubkey, // 32
+    pub buyer: Pubkey,  // 32
+    pub paid: u64,      // 8
+}
+
+impl TicketReceipt {
+    pub const LEN: usize = DISCRIMINATOR_LENGTH + PUBLIC_KEY_LENGTH * 2 + U64_LENGTH;
 }
 
 #[account(zero_copy)]
 pub struct CollectionPool {
-    // 32*400+8+8 = 12816
+    // 32*400+8+8+8 = 12824
+    pub version: u64,                          // 8
     pub count: u64,                            // 8
     pub collections: [Pubkey; MAX_COLLECTION], //32*400
 }
 
+#[zero_copy]
+#[derive(Default, AnchorSerialize, AnchorDeserialize)]
+pub struct DiscountTier {
+    pub min_tickets: u64,      //8
+    pub discount_percent: u64, //8
+}
+
+#[zero_copy]
+#[derive(Default, AnchorSerialize, AnchorDeserialize)]
+pub struct RoyaltyShare {
+    pub recipient: Pubkey, //32
+    pub basis_points: u64, //8
+}
+
+// the arguments of create_raffle, shared by the CPI interface, the client and the tests
+pub struct CreateRaffleParams {
+    pub nonce: u64,
+    pub ticket_price_sol: u64,
+    pub start_timestamp: i64,
+    pub end_timestamp: i64,
+    pub max_entrants: u64,
+    pub discounts: Vec<DiscountTier>,
+    pub use_nft_creators: bool,
+    pub royalties: Vec<RoyaltyShare>,
+    pub payment_mode: u64,
+    pub burn_target: Pubkey,
+    pub return_unclaimed: bool,
+    pub instant_win: bool,
+}
+
+impl Default for CreateRaffleParams {
+    fn default() -> Self {
+        CreateRaffleParams {
+            nonce: 0,
+            ticket_price_sol: 0,
+            start_timestamp: 0,
+            end_timestamp: 0,
+            max_entrants: MAX_ENTRANTS as u64,
+            discounts: vec![],
+            use_nft_creators: false,
+            royalties: vec![],
+            payment_mode: PAYMENT_SOL,
+            burn_target: Pubkey::default(),
+            return_unclaimed: false,
+            instant_win: false,
+        }
+    }
+}
+
+impl From<CreateRaffleParams> for crate::instruction::CreateRaffle {
+    fn from(params: CreateRaffleParams) -> Self {
+        crate::instruction::CreateRaffle {
+            nonce: params.nonce,
+            ticket_price_sol: params.ticket_price_sol,
+            start_timestamp: params.start_timestamp,
+            end_timestamp: params.end_timestamp,
+            max_entrants: params.max_entrants,
+            discounts: params.discounts,
+            use_nft_creators: params.use_nft_creators,
+            royalties: params.royalties,
+            payment_mode: params.payment_mode,
+            burn_target: params.burn_target,
+            return_unclaimed: params.return_unclaimed,
+            instant_win: params.instant_win,
+        }
+    }
+}
+
+// the fields in front of the entrants of a raffle of the first release
+// it had no version header, no nonce and none of the settings added since then
+#[derive(Clone, Copy, Default)]
+pub struct LegacyRaffleHeader {
+    pub creator: Pubkey,       //32
+    pub nft_mint: Pubkey,      //32
+    pub count: u64,            //8
+    pub no_repeat: u64,        //8
+    pub max_entrants: u64,     //8
+    pub start_timestamp: i64,  //8
+    pub end_timestamp: i64,    //8
+    pub ticket_price_sol: u64, //8
+    pub claimed: u64,          //8
+    pub winner_index: u64,     //8
+    pub winner: Pubkey,        //32
+}
+
+impl LegacyRaffleHeader {
+    pub const LEN: usize = PUBLIC_KEY_LENGTH * 3 + U64_LENGTH * 8;
+
+    // reads the header behind the discriminator of the account data
+    pub fn read(data: &[u8]) -> LegacyRaffleHeader {
+        let pubkey_at = |offset: usize| Pubkey::new(&data[offset..offset + PUBLIC_KEY_LENGTH]);
+        let u64_at = |index: usize| {
+            let offset = DISCRIMINATOR_LENGTH + PUBLIC_KEY_LENGTH * 2 + U64_LENGTH * index;
+            u64::from_le_bytes(data[offset..offset + U64_LENGTH].try_into().unwrap())
+        };
+        LegacyRaffleHeader {
+            creator: pubkey_at(DISCRIMINATOR_LENGTH),
+            nft_mint: pubkey_at(DISCRIMINATOR_LENGTH + PUBLIC_KEY_LENGTH),
+            count: u64_at(0),
+            no_repeat: u64_at(1),
+            max_entrants: u64_at(2),
+            start_timestamp: u64_at(3) as i64,
+            end_timestamp: u64_at(4) as i64,
+            ticket_price_sol: u64_at(5),
+            claimed: u64_at(6),
+            winner_index: u64_at(7),
+            winner: pubkey_at(DISCRIMINATOR_LENGTH + PUBLIC_KEY_LENGTH * 2 + U64_LENGTH * 8),
+        }
+    }
+}
+
 #[account(zero_copy)]
 pub struct RafflePool {
-    // 32*2000+8*9 +96 = 64168
-    pub creator: Pubkey,                  //32
-    pub nft_mint: Pubkey,                 //32
-    pub count: u64,                       //8
-    pub no_repeat: u64,                   //8
-    pub max_entrants: u64,                //8
-    pub start_timestamp: i64,             //8
-    pub end_timestamp: i64,               //8
-    pub ticket_price_sol: u64,            //8
-    pub claimed: u64,                     //8
-    pub winner_index: u64,                //8
-    pub winner: Pubkey,                   //32
-    pub entrants: [Pubkey; MAX_ENTRANTS], //32*2000
+    // 32*2000+8*19+16*4+40*5 +128 = 64544
+    pub version: u64,                                      //8
+    pub creator: Pubkey,                                   //32
+    pub nft_mint: Pubkey,                                  //32
+    pub count: u64,                                        //8
+    pub no_repeat: u64,                                    //8
+    pub max_entrants: u64,                                 //8
+    pub start_timestamp: i64,                              //8
+    pub end_timestamp: i64,                                //8
+    pub ticket_price_sol: u64,                             //8
+    pub claimed: u64,                                      //8
+    pub winner_index: u64,                                 //8
+    pub winner: Pubkey,                                    //32
+    pub discount_count: u64,                               //8
+    pub discounts: [DiscountTier; MAX_DISCOUNT_TIERS],     //16*4
+    pub royalty_count: u64,                                //8
+    pub royalties: [RoyaltyShare; MAX_ROYALTY_RECIPIENTS], //40*5
+    pub frozen: u64,                                       //8
+    pub total_paid: u64,                                   //8
+    pub payment_mode: u64,                                 //8
+    pub burn_target: Pubkey,                               //32
+    pub claim_deadline: i64,                               //8
+    pub return_unclaimed: u64,                             //8
+    pub settle_bounty: u64,                                //8
+    pub instant_win: u64,                                  //8
+    pub entrants: [Pubkey; MAX_ENTRANTS],                  //32*2000
+    pub nonce: u64,                                        //8
 }
 
 impl Default for RafflePool {
     #[inline]
     fn default() -> RafflePool {
         RafflePool {
+            version: RAFFLE_VERSION,
             creator: Pubkey::default(),
             nft_mint: Pubkey::default(),
             count: 0,
@@ -57,7 +249,20 @@ impl Default for RafflePool {
             claimed: 0,
             winner_index: 0,
             winner: Pubkey::default(),
+            discount_count: 0,
+            discounts: [DiscountTier::default(); MAX_DISCOUNT_TIERS],
+            royalty_count: 0,
+            royalties: [RoyaltyShare::default(); MAX_ROYALTY_RECIPIENTS],
+            frozen: 0,
+            total_paid: 0,
+            payment_mode: PAYMENT_SOL,
+            burn_target: Pubkey::default(),
+            claim_deadline: 0,
+            return_unclaimed: 0,
+            settle_bounty: 0,
+            instant_win: 0,
             entrants: [Pubkey::default(); MAX_ENTRANTS],
+            nonce: 0,
         }
     }
 }
@@ -66,18 +271,205 @@ impl Default for CollectionPool {
     #[inline]
     fn default() -> CollectionPool {
         CollectionPool {
+            version: COLLECTION_VERSION,
             count: 0,
             collections: [Pubkey::default(); MAX_COLLECTION],
         }
     }
 }
 impl RafflePool {
+    pub const LEN: usize = DISCRIMINATOR_LENGTH + std::mem::size_of::<RafflePool>();
+    // the raffle of the first release, 64168 bytes
+    pub const LEGACY_LEN: usize =
+        DISCRIMINATOR_LENGTH + LegacyRaffleHeader::LEN + PUBLIC_KEY_LENGTH * MAX_ENTRANTS;
+    // where the entrants start in the current and in the first release layout
+    pub const ENTRANTS_OFFSET: usize =
+        RafflePool::LEN - U64_LENGTH - PUBLIC_KEY_LENGTH * MAX_ENTRANTS;
+    pub const LEGACY_ENTRANTS_OFFSET: usize = DISCRIMINATOR_LENGTH + LegacyRaffleHeader::LEN;
+    // allocate_raffle instructions to grow the raffle PDA to its full size
+    pub const ALLOCATE_STEPS: usize = (RafflePool::LEN - 1) / MAX_PERMITTED_DATA_INCREASE + 1;
+
+    // the raffle has the current layout and can be loaded, other raffles must be migrated first
+    pub fn is_current(account: &AccountInfo) -> bool {
+        has_layout(account, RafflePool::LEN, RAFFLE_VERSION)
+    }
+
+    // the fields of a first release raffle, the settings added since then keep their defaults
+    // it paid no discounts, so the SOL paid is the price of the tickets sold
+    pub fn set_legacy_fields(&mut self, legacy: &LegacyRaffleHeader) {
+        self.version = RAFFLE_VERSION;
+        self.creator = legacy.creator;
+        self.nft_mint = legacy.nft_mint;
+        self.count = legacy.count;
+        self.no_repeat = legacy.no_repeat;
+        self.max_entrants = legacy.max_entrants;
+        self.start_timestamp = legacy.start_timestamp;
+        self.end_timestamp = legacy.end_timestamp;
+        self.ticket_price_sol = legacy.ticket_price_sol;
+        self.claimed = legacy.claimed;
+        self.winner_index = legacy.winner_index;
+        self.winner = legacy.winner;
+        // the first release kept no ticket receipts, a force cancel has nothing to refund
+        self.total_paid = 0;
+        self.payment_mode = PAYMENT_SOL;
+        self.nonce = 0;
+    }
+
     pub fn append(&mut self, buyer: Pubkey) {
         self.entrants[self.count as usize] = buyer;
         self.count += 1;
     }
+
+    pub fn set_discounts(&mut self, discounts: &[DiscountTier]) -> Result<()> {
+        if discounts.len() > MAX_DISCOUNT_TIERS {
+            return Err(error!(RaffleError::InvalidDiscountTiers));
+        }
+        let mut last_min_tickets: u64 = 0;
+        for tier in discounts {
+            // tiers must be sorted by ticket amount and keep the ticket paid
+            if tier.min_tickets <= last_min_tickets || tier.discount_percent >= 100 {
+                return Err(error!(RaffleError::InvalidDiscountTiers));
+            }
+            last_min_tickets = tier.min_tickets;
+        }
+        self.discounts[..discounts.len()].copy_from_slice(discounts);
+        self.discount_count = discounts.len() as u64;
+        Ok(())
+    }
+
+    pub fn set_royalties(&mut self, royalties: &[RoyaltyShare]) -> Result<()> {
+        if royalties.len() > MAX_ROYALTY_RECIPIENTS {
+            return Err(error!(RaffleError::InvalidRoyaltySplit));
+        }
+        let mut total_basis_points: u64 = 0;
+        for royalty in royalties {
+            if royalty.basis_points == 0 {
+                return Err(error!(RaffleError::InvalidRoyaltySplit));
+            }
+            total_basis_points = checked_add(total_basis_points, royalty.basis_points)?;
+        }
+        if total_basis_points > BASIS_POINTS {
+            return Err(error!(RaffleError::InvalidRoyaltySplit));
+        }
+        self.royalties[..royalties.len()].copy_from_slice(royalties);
+        self.royalty_count = royalties.len() as u64;
+        Ok(())
+    }
+
+    // add `amount` tickets of the buyer and count the no repeat buyers
+    pub fn add_entrant(&mut self, buyer: Pubkey, amount: u64) -> Result<()> {
+        if amount == 0 {
+            return Err(error!(RaffleError::InvalidTicketAmount));
+        }
+        if self.claimed != 0 {
+            return Err(error!(RaffleError::WinnersAlreadyDrawn));
+        }
+        if checked_add(self.count, amount)? > self.max_entrants {
+            return Err(error!(RaffleError::NotEnoughTicketsLeft));
+        }
+        let mut repeat = false;
+        for i in 0..self.count {
+            if self.entrants[i as usize] == buyer {
+                repeat = true;
+                break;
+            }
+        }
+        if !repeat {
+            self.no_repeat += 1;
+        }
+        for _ in 0..amount {
+            self.append(buyer);
+        }
+        Ok(())
+    }
+
+    // the settle bounty is paid once, to whoever moves the prize out of the escrow
+    pub fn take_settle_bounty(&mut self) -> u64 {
+        let bounty = self.settle_bounty;
+        self.settle_bounty = 0;
+        bounty
+    }
+
+    // amount of the tickets which are not removed
+    pub fn active_tickets(&self) -> u64 {
+        let mut tickets: u64 = 0;
+        for i in 0..self.count {
+            if self.entrants[i as usize] != Pubkey::default() {
+                tickets += 1;
+            }
+        }
+        tickets
+    }
+
+    // draw the winner among the tickets which are not removed
+    pub fn draw_winner(&mut self, timestamp: i64) -> Result<()> {
+        let tickets = self.active_tickets();
+        if tickets == 0 {
+            return Err(error!(RaffleError::InvalidRevealedData));
+        }
+        let mut ticket_index = draw_random(timestamp, tickets);
+        for i in 0..self.count {
+            if self.entrants[i as usize] == Pubkey::default() {
+                continue;
+            }
+            if ticket_index == 0 {
+                self.winner_index = i;
+                self.winner = self.entrants[i as usize];
+                break;
+            }
+            ticket_index -= 1;
+        }
+        self.claimed = 2;
+        Ok(())
+    }
+
+    // instant win raffles draw the winner as soon as the tickets are sold out
+    pub fn draw_if_sold_out(&mut self, timestamp: i64, claim_window: i64) -> Result<()> {
+        if self.instant_win == 0 || self.claimed != 0 || self.count < self.max_entrants {
+            return Ok(());
+        }
+        self.draw_winner(timestamp)?;
+        self.claim_deadline = claim_deadline(timestamp, claim_window)?;
+        Ok(())
+    }
+
+    // remove the buyer's tickets and return how many were removed
+    pub fn remove_entrant(&mut self, buyer: Pubkey) -> u64 {
+        let mut tickets: u64 = 0;
+        for i in 0..self.count {
+            if self.entrants[i as usize] == buyer {
+                self.entrants[i as usize] = Pubkey::default();
+                tickets += 1;
+            }
+        }
+        tickets
+    }
+
+    // total price of `amount` tickets after the best matching discount tier
+    pub fn ticket_cost(&self, amount: u64) -> Result<u64> {
+        let total_amount = checked_mul(amount, self.ticket_price_sol)?;
+        let mut discount_percent: u64 = 0;
+        for i in 0..self.discount_count {
+            let tier = self.discounts[i as usize];
+            if amount >= tier.min_tickets {
+                discount_percent = tier.discount_percent;
+            }
+        }
+        checked_div(
+            checked_mul(total_amount, checked_sub(100, discount_percent)?)?,
+            100,
+        )
+    }
 }
 impl CollectionPool {
+    pub const LEN: usize = DISCRIMINATOR_LENGTH + std::mem::size_of::<CollectionPool>();
+    // the layout before the version header
+    pub const LEGACY_LEN: usize = CollectionPool::LEN - U64_LENGTH;
+
+    pub fn is_current(account: &AccountInfo) -> bool {
+        has_layout(account, CollectionPool::LEN, COLLECTION_VERSION)
+    }
+
     pub fn append(&mut self, collection: Pubkey) {
         let mut valid: u8 = 0;
         for i in 0..self.count {
@@ -92,3 +484,16 @@ impl CollectionPool {
         }
     }
 }
+
+// checks the length and the version header of a zero copy account without loading it,
+// `AccountLoader::load` panics on the shorter accounts of older layouts
+fn has_layout(account: &AccountInfo, len: usize, version: u64) -> bool {
+    match account.try_borrow_data() {
+        Ok(data) => {
+            data.len() == len
+                && data[DISCRIMINATOR_LENGTH..DISCRIMINATOR_LENGTH + U64_LENGTH]
+                    == version.to_le_bytes()
+        }
+        Err(_) => false,
+    }
+}
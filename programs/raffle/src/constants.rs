@@ -1,9 +1,33 @@
 pub const GLOBAL_AUTHORITY_SEED: &str = "global-authority";
+pub const ESCROW_AUTHORITY_SEED: &str = "escrow-authority";
+pub const RAFFLE_SEED: &str = "raffle";
+pub const REFERRER_SEED: &str = "referrer";
+pub const VOUCHER_SEED: &str = "voucher";
+pub const RECEIPT_SEED: &str = "receipt";
+pub const DURATION_SEED: &str = "duration";
 pub const RANDOM_SEED: &str = "random-seed";
 pub const TREASURY_WALLET: &str = "Am9xhPPVCfDZFDabcGgmQ8GTMdsbqEt1qVXbyhTxybAp";
+// raffle PDAs are too large to be created by one CPI, allocate_raffle grows them in steps
 pub const MAX_ENTRANTS: usize = 2000;
 pub const MAX_COLLECTION: usize = 400;
+pub const MAX_DISCOUNT_TIERS: usize = 4;
+// same as the max creators of the metaplex metadata
+pub const MAX_ROYALTY_RECIPIENTS: usize = 5;
 
 pub const COMMISSION_FEE: u64 = 5;
+pub const BASIS_POINTS: u64 = 10000;
+// deposited by the creator to pay whoever moves the prize out of the escrow
+// it covers the rent of the winner's NFT ATA created by settle_raffle
+pub const SETTLE_BOUNTY: u64 = 3_000_000;
+
+// how the tickets of a raffle are paid
+pub const PAYMENT_SOL: u64 = 0;
+pub const PAYMENT_BURN_TOKEN: u64 = 1;
+pub const PAYMENT_BURN_NFT: u64 = 2;
+
+// layout versions of the zero copy accounts
+// the accounts created before the version header are migrated to version 1
+pub const RAFFLE_VERSION: u64 = 1;
+pub const COLLECTION_VERSION: u64 = 1;
 
 pub const DAY: i64 = 60 * 60 * 24;
@@ -55,4 +55,71 @@ pub enum RaffleError {
     MetadataCreatorParseError,
     #[msg("Unknown Collection Or The Collection Is Not Allowed")]
     UnkownOrNotAllowedNFTCollection,
+
+    #[msg("Invalid account layout")]
+    InvalidAccountLayout,
+    #[msg("Raffle account is not allocated")]
+    RaffleNotAllocated,
+
+    #[msg("Invalid Discount Tiers")]
+    InvalidDiscountTiers,
+
+    #[msg("You are not the Admin")]
+    NotAdmin,
+    #[msg("Invalid Referral Share")]
+    InvalidReferralShare,
+    #[msg("Invalid Referrer")]
+    InvalidReferrer,
+    #[msg("Account is already migrated")]
+    AlreadyMigrated,
+
+    #[msg("Invalid Royalty Split")]
+    InvalidRoyaltySplit,
+    #[msg("Invalid Royalty Recipient")]
+    InvalidRoyaltyRecipient,
+
+    #[msg("You are not the Admin or Pauser")]
+    NotPauser,
+    #[msg("Program is paused")]
+    ProgramPaused,
+
+    #[msg("Raffle is frozen")]
+    RaffleFrozen,
+    #[msg("Raffle is not frozen")]
+    RaffleNotFrozen,
+    #[msg("Raffle is cancelled")]
+    RaffleCancelled,
+    #[msg("Raffle is not cancelled")]
+    RaffleNotCancelled,
+    #[msg("No tickets to refund")]
+    NoRefund,
+
+    #[msg("Invalid Voucher")]
+    InvalidVoucher,
+
+    #[msg("Invalid Payment Mode")]
+    InvalidPaymentMode,
+    #[msg("Invalid Burn Token")]
+    InvalidBurnToken,
+
+    #[msg("Claim deadline has not passed")]
+    ClaimDeadlineNotPassed,
+    #[msg("Invalid claim window")]
+    InvalidClaimWindow,
+
+    #[msg("Invalid ticket amount")]
+    InvalidTicketAmount,
+
+    #[msg("Raffle has not started")]
+    RaffleNotStarted,
+
+    #[msg("Invalid raffle duration bounds")]
+    InvalidDuration,
+    #[msg("Invalid collection duration account")]
+    InvalidCollectionDuration,
+
+    #[msg("Account must be migrated first")]
+    AccountNotMigrated,
+    #[msg("Invalid NFT token account")]
+    InvalidNftTokenAccount,
 }
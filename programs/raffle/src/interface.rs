@@ -0,0 +1,175 @@
+//! Entry points for programs which create raffles and buy tickets by CPI.
+//!
+//! The creator and the buyer can be PDAs of the calling program, which signs for
+//! them with `signer_seeds`. They pay in SOL, so they must be system accounts.
+//! These functions keep their signatures when the instruction arguments change,
+//! and derive the accounts the client would otherwise prepare.
+//!
+//! The raffle PDA must be allocated before `create_raffle`. One instruction can
+//! grow it by 10KB at most, so `allocate_raffle` has to be called from
+//! `RafflePool::ALLOCATE_STEPS` separate top level instructions of the transaction.
+
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token;
+
+pub use crate::account::CreateRaffleParams;
+
+pub struct AllocateRaffleAccounts<'info> {
+    pub raffle_program: AccountInfo<'info>,
+    // signer and payer of the rent
+    pub payer: AccountInfo<'info>,
+    pub creator: AccountInfo<'info>,
+    pub nft_mint: AccountInfo<'info>,
+    pub raffle: AccountInfo<'info>,
+    pub system_program: AccountInfo<'info>,
+}
+
+pub struct CreateRaffleAccounts<'info> {
+    pub raffle_program: AccountInfo<'info>,
+    // signer and payer, the owner of `creator_nft_token_account`
+    pub creator: AccountInfo<'info>,
+    pub global_authority: AccountInfo<'info>,
+    pub raffle: AccountInfo<'info>,
+    pub escrow_authority: AccountInfo<'info>,
+    // the escrow's ATA of the NFT, the associated token program creates it when missing
+    pub escrow_nft_token_account: AccountInfo<'info>,
+    pub collection: AccountInfo<'info>,
+    pub collection_duration: AccountInfo<'info>,
+    pub creator_nft_token_account: AccountInfo<'info>,
+    pub nft_mint: AccountInfo<'info>,
+    pub mint_metadata: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+    pub associated_token_program: AccountInfo<'info>,
+    pub system_program: AccountInfo<'info>,
+    pub rent: AccountInfo<'info>,
+    pub token_metadata_program: AccountInfo<'info>,
+}
+
+pub struct BuyTicketsAccounts<'info> {
+    pub raffle_program: AccountInfo<'info>,
+    // signer and payer, the entrant of the tickets
+    pub buyer: AccountInfo<'info>,
+    pub raffle: AccountInfo<'info>,
+    pub global_authority: AccountInfo<'info>,
+    pub creator: AccountInfo<'info>,
+    pub treasury_wallet: AccountInfo<'info>,
+    // the buyer's receipt PDA of the raffle, created at the first purchase
+    pub receipt: AccountInfo<'info>,
+    pub system_program: AccountInfo<'info>,
+}
+
+/**
+ * @dev Allocate the next part of the raffle PDA, once per top level instruction
+ * @param accounts: the accounts of allocate_raffle and the raffle program
+ * @param nonce: the creator's nonce of the raffle
+ * @param signer_seeds: the seeds of the payer if it is a PDA of the calling program
+ */
+pub fn allocate_raffle<'info>(
+    accounts: AllocateRaffleAccounts<'info>,
+    nonce: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let cpi_accounts = crate::cpi::accounts::AllocateRaffle {
+        payer: accounts.payer,
+        creator: accounts.creator,
+        nft_mint_address: accounts.nft_mint,
+        raffle: accounts.raffle,
+        system_program: accounts.system_program,
+    };
+    crate::cpi::allocate_raffle(
+        CpiContext::new_with_signer(accounts.raffle_program, cpi_accounts, signer_seeds),
+        nonce,
+    )
+}
+
+/**
+ * @dev Create a raffle of the creator's NFT, the NFT is moved to the raffle's escrow
+ * The raffle must be allocated by allocate_raffle beforehand
+ * @param accounts: the accounts of create_raffle and the raffle program
+ * @param params: the raffle settings like create_raffle takes them
+ * @param signer_seeds: the seeds of the creator if it is a PDA of the calling program
+ */
+pub fn create_raffle<'info>(
+    accounts: CreateRaffleAccounts<'info>,
+    params: CreateRaffleParams,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    if accounts.escrow_nft_token_account.data_is_empty() {
+        let cpi_accounts = associated_token::Create {
+            payer: accounts.creator.clone(),
+            associated_token: accounts.escrow_nft_token_account.clone(),
+            authority: accounts.escrow_authority.clone(),
+            mint: accounts.nft_mint.clone(),
+            system_program: accounts.system_program.clone(),
+            token_program: accounts.token_program.clone(),
+            rent: accounts.rent.clone(),
+        };
+        associated_token::create(CpiContext::new_with_signer(
+            accounts.associated_token_program.clone(),
+            cpi_accounts,
+            signer_seeds,
+        ))?;
+    }
+
+    let cpi_accounts = crate::cpi::accounts::CreateRaffle {
+        admin: accounts.creator,
+        global_authority: accounts.global_authority,
+        raffle: accounts.raffle,
+        escrow_authority: accounts.escrow_authority,
+        collection: accounts.collection,
+        collection_duration: accounts.collection_duration,
+        owner_temp_nft_account: accounts.creator_nft_token_account,
+        dest_nft_token_account: accounts.escrow_nft_token_account,
+        nft_mint_address: accounts.nft_mint,
+        mint_metadata: accounts.mint_metadata,
+        token_program: accounts.token_program,
+        system_program: accounts.system_program,
+        rent: accounts.rent,
+        token_metadata_program: accounts.token_metadata_program,
+    };
+    crate::cpi::create_raffle(
+        CpiContext::new_with_signer(accounts.raffle_program, cpi_accounts, signer_seeds),
+        params.nonce,
+        params.ticket_price_sol,
+        params.start_timestamp,
+        params.end_timestamp,
+        params.max_entrants,
+        params.discounts,
+        params.use_nft_creators,
+        params.royalties,
+        params.payment_mode,
+        params.burn_target,
+        params.return_unclaimed,
+        params.instant_win,
+    )
+}
+
+/**
+ * @dev Buy tickets of a SOL raffle for the buyer
+ * @param accounts: the accounts of buy_tickets and the raffle program
+ * @param remaining_accounts: the raffle's royalty recipients in order
+ * followed by the optional referrer and its referrer_pool
+ * @param amount: the amount of the tickets
+ * @param signer_seeds: the seeds of the buyer if it is a PDA of the calling program
+ */
+pub fn buy_tickets<'info>(
+    accounts: BuyTicketsAccounts<'info>,
+    remaining_accounts: Vec<AccountInfo<'info>>,
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let cpi_accounts = crate::cpi::accounts::BuyTickets {
+        buyer: accounts.buyer,
+        raffle: accounts.raffle,
+        global_authority: accounts.global_authority,
+        creator: accounts.creator,
+        treasury_wallet: accounts.treasury_wallet,
+        receipt: accounts.receipt,
+        system_program: accounts.system_program,
+    };
+    crate::cpi::buy_tickets(
+        CpiContext::new_with_signer(accounts.raffle_program, cpi_accounts, signer_seeds)
+            .with_remaining_accounts(remaining_accounts),
+        amount,
+    )
+}
@@ -1,15 +1,23 @@
-use anchor_lang::{accounts::cpi_account::CpiAccount, prelude::*, AccountSerialize};
+// Anchor 0.24 errors are larger than clippy's limit, every instruction returns them
+#![allow(clippy::result_large_err)]
+// create_raffle and the functions anchor generates for it take every setting apart
+#![allow(clippy::too_many_arguments)]
+
+use anchor_lang::{prelude::*, AccountSerialize, Discriminator};
 use anchor_spl::{
-    token::{self, Token, TokenAccount, Transfer},
+    associated_token::{self, get_associated_token_address, AssociatedToken},
+    token::{self, Burn, Mint, Token, TokenAccount, Transfer},
 };
-use solana_program::program::{invoke, invoke_signed};
 use solana_program::pubkey::Pubkey;
+use solana_program::sysvar::instructions::{load_current_index_checked, load_instruction_at_checked};
 use metaplex_token_metadata::state::Metadata;
 
 
 pub mod account;
 pub mod constants;
 pub mod error;
+#[cfg(feature = "cpi")]
+pub mod interface;
 pub mod utils;
 
 use account::*;
@@ -25,16 +33,19 @@ pub mod raffle {
     /**
      * @dev Initialize the project
      */
-    pub fn initialize(ctx: Context<Initialize>, _bump: u8) -> Result<()> {
+    pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
         let global_authority = &mut ctx.accounts.global_authority;
-        let _collection = ctx.accounts.collection.load_init()?;
+        let mut collection = ctx.accounts.collection.load_init()?;
+        collection.version = COLLECTION_VERSION;
         global_authority.super_admin = ctx.accounts.admin.key();
+        global_authority.bump = *ctx.bumps.get("global_authority").unwrap();
+        global_authority.min_duration = DAY;
         Ok(())
     }
 
     /**
      * @dev Add collections for using this platform
-     * 
+     * @Context has admin and global_authority accounts and the collection pool
      */
     pub fn add_collection(ctx: Context<AddCollection>) -> Result<()> {
         let mut collection = ctx.accounts.collection.load_mut()?;
@@ -42,75 +53,261 @@ pub mod raffle {
         Ok(())
     }
 
+    /**
+     * @dev Set the share of the commission paid to referrers
+     * @Context has admin and global_authority accounts
+     * @param referral_share: percent of the commission fee for the referrer
+     */
+    pub fn set_referral_share(ctx: Context<SetGlobalConfig>, referral_share: u64) -> Result<()> {
+        if referral_share > 100 {
+            return Err(error!(RaffleError::InvalidReferralShare));
+        }
+        ctx.accounts.global_authority.referral_share = referral_share;
+        Ok(())
+    }
+
+    /**
+     * @dev Set the pauser who can pause the program besides the super admin
+     * @Context has admin and global_authority accounts
+     * @param pauser: the pauser's address
+     */
+    pub fn set_pauser(ctx: Context<SetGlobalConfig>, pauser: Pubkey) -> Result<()> {
+        ctx.accounts.global_authority.pauser = pauser;
+        Ok(())
+    }
+
+    /**
+     * @dev Pause or unpause creating raffles and buying tickets
+     * Winners can still claim and creators can still withdraw while paused
+     * @Context has authority(super admin or pauser) and global_authority accounts
+     * @param paused: pause the program or not
+     */
+    pub fn set_paused(ctx: Context<SetPaused>, paused: bool) -> Result<()> {
+        ctx.accounts.global_authority.paused = paused;
+        Ok(())
+    }
+
+    /**
+     * @dev Set the authority who signs the free ticket vouchers
+     * @Context has admin and global_authority accounts
+     * @param voucher_authority: the voucher signer's address
+     */
+    pub fn set_voucher_authority(
+        ctx: Context<SetGlobalConfig>,
+        voucher_authority: Pubkey,
+    ) -> Result<()> {
+        ctx.accounts.global_authority.voucher_authority = voucher_authority;
+        Ok(())
+    }
+
+    /**
+     * @dev Set the claim window after the draw, then the winner can be rerolled
+     * @Context has admin and global_authority accounts
+     * @param claim_window: seconds for the winner to claim, 0 means no deadline
+     */
+    pub fn set_claim_window(ctx: Context<SetGlobalConfig>, claim_window: i64) -> Result<()> {
+        if claim_window < 0 {
+            return Err(error!(RaffleError::InvalidClaimWindow));
+        }
+        ctx.accounts.global_authority.claim_window = claim_window;
+        Ok(())
+    }
+
+    /**
+     * @dev Set the bounds of the raffle duration from its start to its end
+     * @Context has admin and global_authority accounts
+     * @param min_duration: the minimum duration in seconds
+     * @param max_duration: the maximum duration in seconds, 0 means no limit
+     */
+    pub fn set_raffle_duration(
+        ctx: Context<SetGlobalConfig>,
+        min_duration: i64,
+        max_duration: i64,
+    ) -> Result<()> {
+        verify_duration_bounds(min_duration, max_duration)?;
+        let global_authority = &mut ctx.accounts.global_authority;
+        global_authority.min_duration = min_duration;
+        global_authority.max_duration = max_duration;
+        Ok(())
+    }
+
+    /**
+     * @dev Create the duration bounds overriding the global ones for a collection
+     * @Context has admin and global_authority accounts
+     * the collection_id and its collection_duration PDA
+     * @param min_duration: the minimum duration in seconds
+     * @param max_duration: the maximum duration in seconds, 0 means no limit
+     */
+    pub fn init_collection_duration(
+        ctx: Context<InitCollectionDuration>,
+        min_duration: i64,
+        max_duration: i64,
+    ) -> Result<()> {
+        verify_duration_bounds(min_duration, max_duration)?;
+        let collection_duration = &mut ctx.accounts.collection_duration;
+        collection_duration.collection = ctx.accounts.collection_id.key();
+        collection_duration.min_duration = min_duration;
+        collection_duration.max_duration = max_duration;
+        Ok(())
+    }
+
+    /**
+     * @dev Update the duration bounds of a collection
+     * @Context has admin and global_authority accounts and collection_duration PDA
+     * @param min_duration: the minimum duration in seconds
+     * @param max_duration: the maximum duration in seconds, 0 means no limit
+     */
+    pub fn set_collection_duration(
+        ctx: Context<SetCollectionDuration>,
+        min_duration: i64,
+        max_duration: i64,
+    ) -> Result<()> {
+        verify_duration_bounds(min_duration, max_duration)?;
+        let collection_duration = &mut ctx.accounts.collection_duration;
+        collection_duration.min_duration = min_duration;
+        collection_duration.max_duration = max_duration;
+        Ok(())
+    }
+
+    /**
+     * @dev Register the referrer PDA which tracks the referral earnings
+     * @Context has referrer and referrer_pool accounts
+     */
+    pub fn init_referrer(ctx: Context<InitReferrer>) -> Result<()> {
+        let referrer_pool = &mut ctx.accounts.referrer_pool;
+        referrer_pool.referrer = ctx.accounts.referrer.key();
+        Ok(())
+    }
+
+    /**
+     * @dev Allocate the raffle PDA before create_raffle, anyone can pay for it
+     * One instruction can't allocate the whole raffle, so the raffle needs
+     * RafflePool::ALLOCATE_STEPS instructions, later ones do nothing
+     * @Context has payer, the raffle's creator and nft mint, and the raffle PDA
+     * @param nonce: creator's nonce to derive the raffle PDA with the nft mint
+     */
+    pub fn allocate_raffle(ctx: Context<AllocateRaffle>, nonce: u64) -> Result<()> {
+        let creator = ctx.accounts.creator.key();
+        let nft_mint = ctx.accounts.nft_mint_address.key();
+        let nonce_bytes = nonce.to_le_bytes();
+        let raffle_bump = *ctx.bumps.get("raffle").unwrap();
+        let seeds = &[
+            RAFFLE_SEED.as_bytes(),
+            creator.as_ref(),
+            nft_mint.as_ref(),
+            nonce_bytes.as_ref(),
+            &[raffle_bump],
+        ];
+        allocate_pda_step(
+            &ctx.accounts.raffle,
+            ctx.accounts.payer.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+            RafflePool::LEN,
+            seeds,
+        )
+    }
+
     /**
      * @dev Create new raffle with new arguements
      * @Context has admin, global_authority accounts.
-     * and zero-account Raffle, owner's nft ATA and global_authority's nft ATA
-     * and nft mint address
-     * @param global_bump: global authority's bump
+     * and Raffle PDA allocated by allocate_raffle, the raffle's escrow_authority,
+     * owner's nft ATA and escrow_authority's nft ATA and nft mint address
+     * the collection_duration PDA of the NFT's collection, it is not initialized
+     * when the collection uses the global duration bounds
+     * @param nonce: creator's nonce to derive the raffle PDA with the nft mint
      * @param ticket_price_sol: ticket price by sol
+     * @param start_timestamp: the scheduled start time of ticket sales, now if it already passed
      * @param end_timestamp: the end time of raffle
      * @param max_entrants: entrants amount to take part in this raffle
+     * @param discounts: quantity discount tiers sorted by min_tickets
+     * @param use_nft_creators: split the creator share among the NFT's metaplex creators
+     * @param royalties: custom royalty split of the creator share by basis points
+     * @param payment_mode: pay tickets by SOL, by burning tokens or by burning NFTs
+     * @param burn_target: the token mint to burn, or the collection of the NFTs to burn
+     * in burning tokens mode, the ticket price is the token amount
+     * @param return_unclaimed: return the NFT to the creator instead of rerolling
+     * when the winner doesn't claim before the claim deadline
+     * @param instant_win: draw the winner as soon as max_entrants tickets are sold
      */
     pub fn create_raffle(
         ctx: Context<CreateRaffle>,
-        _global_bump: u8,
+        nonce: u64,
         ticket_price_sol: u64,
+        start_timestamp: i64,
         end_timestamp: i64,
         max_entrants: u64,
+        discounts: Vec<DiscountTier>,
+        use_nft_creators: bool,
+        royalties: Vec<RoyaltyShare>,
+        payment_mode: u64,
+        burn_target: Pubkey,
+        return_unclaimed: bool,
+        instant_win: bool,
     ) -> Result<()> {
+        if ctx.accounts.global_authority.paused {
+            return Err(error!(RaffleError::ProgramPaused));
+        }
+
         let mint_metadata = &mut &ctx.accounts.mint_metadata;
 
         msg!("Metadata Account: {:?}", ctx.accounts.mint_metadata.key());
-        let (metadata, _) = Pubkey::find_program_address(
-            &[
-                metaplex_token_metadata::state::PREFIX.as_bytes(),
-                metaplex_token_metadata::id().as_ref(),
-                ctx.accounts.nft_mint_address.key().as_ref(),
-            ],
-            &metaplex_token_metadata::id(),
-        );
-        require!(
-            metadata == mint_metadata.key(),
-            RaffleError::InvaliedMetadata
-        );
+        verify_metadata_address(&ctx.accounts.nft_mint_address.key(), &mint_metadata.key())?;
 
         let collection = ctx.accounts.collection.load_mut()?;
 
         // verify metadata is legit
         let nft_metadata = Metadata::from_account_info(mint_metadata)?;
-        if let Some(creators) = nft_metadata.data.creators {
-            let mut valid: u8 = 0;
-            for creator in creators {
-                for j in 0..collection.count  {
-                    if creator.address == collection.collections[j as usize] && creator.verified == true
-                    {
-                        valid = 1;
-                        break;
-                    }
-                }
-                if valid == 1 {
-                    break;
-                }
-            }
-            if valid != 1 {
-                return Err(error!(RaffleError::InvalidCollection));
-            }
-        } else {
-            return Err(error!(RaffleError::MetadataCreatorParseError));
-        };   
+        let collection_id = verify_nft_collection(
+            &nft_metadata,
+            &collection.collections[..collection.count as usize],
+        )?;
+        if payment_mode > PAYMENT_BURN_NFT {
+            return Err(error!(RaffleError::InvalidPaymentMode));
+        }
+
+        // Deposit the settle bounty to the raffle account
+        sol_transfer_user(
+            ctx.accounts.admin.to_account_info(),
+            ctx.accounts.raffle.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+            SETTLE_BOUNTY,
+        )?;
 
         let mut raffle = ctx.accounts.raffle.load_init()?;
         let timestamp = Clock::get()?.unix_timestamp;
+        raffle.version = RAFFLE_VERSION;
 
-        if max_entrants > 2000 {
+        if max_entrants > MAX_ENTRANTS as u64 {
             return Err(error!(RaffleError::MaxEntrantsTooLarge));
         }
-        if timestamp + DAY > end_timestamp {
-            return Err(error!(RaffleError::EndTimeError));
+        let start_timestamp = start_timestamp.max(timestamp);
+
+        // Use the collection's duration bounds if the admin set them
+        let (duration_key, _) = Pubkey::find_program_address(
+            &[DURATION_SEED.as_bytes(), collection_id.as_ref()],
+            &raffle::ID,
+        );
+        let collection_duration = &ctx.accounts.collection_duration;
+        if collection_duration.key() != duration_key {
+            return Err(error!(RaffleError::InvalidCollectionDuration));
         }
+        let global_authority = &ctx.accounts.global_authority;
+        let (min_duration, max_duration) = if collection_duration.owner == &raffle::ID {
+            let duration =
+                CollectionDuration::try_deserialize(&mut &collection_duration.data.borrow()[..])?;
+            (duration.min_duration, duration.max_duration)
+        } else {
+            (global_authority.min_duration, global_authority.max_duration)
+        };
+        verify_raffle_duration(
+            timestamp,
+            start_timestamp,
+            end_timestamp,
+            min_duration,
+            max_duration,
+        )?;
 
-        // Transfer NFT to the PDA
+        // Transfer NFT to the raffle's escrow PDA
         let src_token_account_info = &mut &ctx.accounts.owner_temp_nft_account;
         let dest_token_account_info = &mut &ctx.accounts.dest_nft_token_account;
         let token_program = &mut &ctx.accounts.token_program;
@@ -127,10 +324,43 @@ pub mod raffle {
 
         raffle.creator = ctx.accounts.admin.key();
         raffle.nft_mint = ctx.accounts.nft_mint_address.key();
+        raffle.nonce = nonce;
         raffle.ticket_price_sol = ticket_price_sol;
-        raffle.start_timestamp = timestamp;
+        raffle.start_timestamp = start_timestamp;
         raffle.end_timestamp = end_timestamp;
         raffle.max_entrants = max_entrants;
+        raffle.set_discounts(&discounts)?;
+        raffle.payment_mode = payment_mode;
+        raffle.burn_target = burn_target;
+        raffle.return_unclaimed = u64::from(return_unclaimed);
+        raffle.instant_win = u64::from(instant_win);
+        raffle.settle_bounty = SETTLE_BOUNTY;
+
+        // Royalties like on secondary sales: seller fee split by the creators' shares
+        if use_nft_creators {
+            if !royalties.is_empty() {
+                return Err(error!(RaffleError::InvalidRoyaltySplit));
+            }
+            let seller_fee = u64::from(nft_metadata.data.seller_fee_basis_points);
+            let mut nft_royalties: Vec<RoyaltyShare> = vec![];
+            if let Some(creators) = &nft_metadata.data.creators {
+                for creator in creators {
+                    let basis_points = checked_div(
+                        checked_mul(seller_fee, u64::from(creator.share))?,
+                        100,
+                    )?;
+                    if basis_points > 0 {
+                        nft_royalties.push(RoyaltyShare {
+                            recipient: creator.address,
+                            basis_points,
+                        });
+                    }
+                }
+            }
+            raffle.set_royalties(&nft_royalties)?;
+        } else {
+            raffle.set_royalties(&royalties)?;
+        }
 
         Ok(())
     }
@@ -139,70 +369,293 @@ pub mod raffle {
      * @dev Buy tickets functions
      * @Context has buyer and raffle's account.
      * global_authority and creator address and their reap token ATAs
-     * @param global_bump: global_authority's bump
+     * the buyer's receipt of the raffle, created at the first purchase
+     * remaining accounts are the raffle's royalty recipients in order
+     * followed by the optional referrer and its referrer_pool
      * @param amount: the amount of the tickets
      */
-    pub fn buy_tickets(ctx: Context<BuyTickets>, _global_bump: u8, amount: u64) -> Result<()> {
+    pub fn buy_tickets<'info>(
+        ctx: Context<'_, '_, '_, 'info, BuyTickets<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        if ctx.accounts.global_authority.paused {
+            return Err(error!(RaffleError::ProgramPaused));
+        }
+
         let timestamp = Clock::get()?.unix_timestamp;
         let mut raffle = ctx.accounts.raffle.load_mut()?;
 
+        if timestamp < raffle.start_timestamp {
+            return Err(error!(RaffleError::RaffleNotStarted));
+        }
         if timestamp > raffle.end_timestamp {
             return Err(error!(RaffleError::RaffleEnded));
         }
-        if raffle.count + amount > raffle.max_entrants {
+        if checked_add(raffle.count, amount)? > raffle.max_entrants {
             return Err(error!(RaffleError::NotEnoughTicketsLeft));
         }
 
-        let total_amount_sol = amount * raffle.ticket_price_sol;
+        if raffle.frozen == 1 {
+            return Err(error!(RaffleError::RaffleFrozen));
+        }
+        if raffle.payment_mode != PAYMENT_SOL {
+            return Err(error!(RaffleError::InvalidPaymentMode));
+        }
+
+        let total_amount_sol = raffle.ticket_cost(amount)?;
 
         if ctx.accounts.buyer.to_account_info().lamports() < total_amount_sol {
             return Err(error!(RaffleError::NotEnoughSOL));
         }
 
-        // Check how many no repeat accounts bought the tickets
-        if raffle.count == 0 {
-            raffle.no_repeat = 1;
-        } else {
-            let mut index: u64 = 0;
-            for i in 0..raffle.count {
-                if raffle.entrants[i as usize] == ctx.accounts.buyer.key() {
-                    index = i + 1 as u64;
-                    break;
-                }
-            }
-            if index == 0 {
-                raffle.no_repeat += 1;
+        raffle.add_entrant(ctx.accounts.buyer.key(), amount)?;
+        raffle.draw_if_sold_out(timestamp, ctx.accounts.global_authority.claim_window)?;
+        raffle.total_paid = checked_add(raffle.total_paid, total_amount_sol)?;
+
+        let receipt = &mut ctx.accounts.receipt;
+        receipt.raffle = ctx.accounts.raffle.key();
+        receipt.buyer = ctx.accounts.buyer.key();
+        receipt.paid = checked_add(receipt.paid, total_amount_sol)?;
+
+        let (creator_amount, fee_amount) = split_commission(total_amount_sol)?;
+
+        // Transfer the royalties of the creator share to the royalty recipients
+        let royalty_count = raffle.royalty_count as usize;
+        if ctx.remaining_accounts.len() < royalty_count {
+            return Err(error!(RaffleError::InvalidRoyaltyRecipient));
+        }
+        let mut rest_creator_amount = creator_amount;
+        for i in 0..royalty_count {
+            let royalty = raffle.royalties[i];
+            let recipient = &ctx.remaining_accounts[i];
+            if recipient.key() != royalty.recipient {
+                return Err(error!(RaffleError::InvalidRoyaltyRecipient));
             }
+            let amount = royalty_amount(creator_amount, royalty.basis_points)?;
+            rest_creator_amount = checked_sub(rest_creator_amount, amount)?;
+            sol_transfer_user(
+                ctx.accounts.buyer.to_account_info(),
+                recipient.clone(),
+                ctx.accounts.system_program.to_account_info(),
+                amount,
+            )?;
         }
 
-        for _ in 0..amount {
-            raffle.append(ctx.accounts.buyer.key());
-        }
-        
         // Transfer SOL from the buyer to the Raffle Creator's wallet
-        let creator_amount = total_amount_sol * (100 - COMMISSION_FEE) / 100;
         sol_transfer_user(
             ctx.accounts.buyer.to_account_info(),
             ctx.accounts.creator.to_account_info(),
             ctx.accounts.system_program.to_account_info(),
-            creator_amount,
+            rest_creator_amount,
         )?;
 
+        // Transfer the referral share of COMMISSION_FEE to the referrer
+        let mut treasury_amount = fee_amount;
+        if ctx.remaining_accounts.len() >= royalty_count + 2 {
+            let referrer = &ctx.remaining_accounts[royalty_count];
+            let referrer_pool_info = &ctx.remaining_accounts[royalty_count + 1];
+            if referrer_pool_info.owner != &raffle::ID {
+                return Err(error!(RaffleError::InvalidReferrer));
+            }
+            let mut referrer_pool =
+                ReferrerPool::try_deserialize(&mut &referrer_pool_info.data.borrow()[..])?;
+            if referrer_pool.referrer != referrer.key()
+                || referrer.key() == ctx.accounts.buyer.key()
+            {
+                return Err(error!(RaffleError::InvalidReferrer));
+            }
+
+            let (referral_amount, rest_amount) =
+                split_referral(fee_amount, ctx.accounts.global_authority.referral_share)?;
+            treasury_amount = rest_amount;
+            sol_transfer_user(
+                ctx.accounts.buyer.to_account_info(),
+                referrer.clone(),
+                ctx.accounts.system_program.to_account_info(),
+                referral_amount,
+            )?;
+
+            referrer_pool.total_earned = checked_add(referrer_pool.total_earned, referral_amount)?;
+            referrer_pool.referral_count = checked_add(referrer_pool.referral_count, 1)?;
+            referrer_pool.try_serialize(&mut &mut referrer_pool_info.data.borrow_mut()[..])?;
+        }
+
         // Transfer COMMISSION_FEE SOL from the buyer to the treasury wallet 
-        let fee_amount = total_amount_sol * COMMISSION_FEE / 100;
         sol_transfer_user(
             ctx.accounts.buyer.to_account_info(),
             ctx.accounts.treasury_wallet.to_account_info(),
             ctx.accounts.system_program.to_account_info(),
-            fee_amount,
+            treasury_amount,
+        )?;
+
+        Ok(())
+    }
+
+    /**
+     * @dev Buy tickets by burning the raffle's SPL token instead of paying SOL
+     * @Context has buyer and raffle's account, global_authority
+     * the token mint to burn and buyer's token account
+     * @param amount: the amount of the tickets
+     */
+    pub fn burn_tokens_for_tickets(ctx: Context<BurnTokensForTickets>, amount: u64) -> Result<()> {
+        if ctx.accounts.global_authority.paused {
+            return Err(error!(RaffleError::ProgramPaused));
+        }
+
+        let timestamp = Clock::get()?.unix_timestamp;
+        let mut raffle = ctx.accounts.raffle.load_mut()?;
+
+        if timestamp < raffle.start_timestamp {
+            return Err(error!(RaffleError::RaffleNotStarted));
+        }
+        if timestamp > raffle.end_timestamp {
+            return Err(error!(RaffleError::RaffleEnded));
+        }
+        if raffle.frozen == 1 {
+            return Err(error!(RaffleError::RaffleFrozen));
+        }
+        if raffle.payment_mode != PAYMENT_BURN_TOKEN {
+            return Err(error!(RaffleError::InvalidPaymentMode));
+        }
+        if raffle.burn_target != ctx.accounts.burn_mint.key() {
+            return Err(error!(RaffleError::InvalidBurnToken));
+        }
+
+        let total_amount_token = raffle.ticket_cost(amount)?;
+        raffle.add_entrant(ctx.accounts.buyer.key(), amount)?;
+        raffle.draw_if_sold_out(timestamp, ctx.accounts.global_authority.claim_window)?;
+
+        // Burn the tokens from the buyer's token account
+        let cpi_accounts = Burn {
+            mint: ctx.accounts.burn_mint.to_account_info(),
+            from: ctx.accounts.buyer_token_account.to_account_info(),
+            authority: ctx.accounts.buyer.to_account_info(),
+        };
+        token::burn(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            total_amount_token,
+        )?;
+        Ok(())
+    }
+
+    /**
+     * @dev Buy one ticket by burning an NFT of the raffle's burn collection
+     * the NFT is checked like the raffle NFT in create_raffle
+     * @Context has buyer and raffle's account, global_authority
+     * the NFT mint to burn, buyer's NFT account and the NFT metadata
+     */
+    pub fn burn_nft_for_ticket(ctx: Context<BurnNftForTicket>) -> Result<()> {
+        if ctx.accounts.global_authority.paused {
+            return Err(error!(RaffleError::ProgramPaused));
+        }
+
+        let timestamp = Clock::get()?.unix_timestamp;
+        let mut raffle = ctx.accounts.raffle.load_mut()?;
+
+        if timestamp < raffle.start_timestamp {
+            return Err(error!(RaffleError::RaffleNotStarted));
+        }
+        if timestamp > raffle.end_timestamp {
+            return Err(error!(RaffleError::RaffleEnded));
+        }
+        if raffle.frozen == 1 {
+            return Err(error!(RaffleError::RaffleFrozen));
+        }
+        if raffle.payment_mode != PAYMENT_BURN_NFT {
+            return Err(error!(RaffleError::InvalidPaymentMode));
+        }
+
+        // verify the NFT belongs to the burn collection
+        let mint_metadata = &ctx.accounts.mint_metadata;
+        verify_metadata_address(&ctx.accounts.burn_mint.key(), &mint_metadata.key())?;
+        let nft_metadata = Metadata::from_account_info(mint_metadata)?;
+        verify_nft_collection(&nft_metadata, &[raffle.burn_target])?;
+
+        raffle.add_entrant(ctx.accounts.buyer.key(), 1)?;
+        raffle.draw_if_sold_out(timestamp, ctx.accounts.global_authority.claim_window)?;
+
+        // Burn the NFT from the buyer's token account
+        let cpi_accounts = Burn {
+            mint: ctx.accounts.burn_mint.to_account_info(),
+            from: ctx.accounts.buyer_token_account.to_account_info(),
+            authority: ctx.accounts.buyer.to_account_info(),
+        };
+        token::burn(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            1,
+        )?;
+        Ok(())
+    }
+
+    /**
+     * @dev Claim free tickets with the voucher signed by the voucher authority
+     * The previous instruction must be the ed25519 program instruction
+     * verifying the voucher signature of (raffle, claimer, amount, voucher_id)
+     * @Context has claimer and global_authority account and raffle account
+     * voucher PDA for replay protection and instructions sysvar
+     * @param voucher_id: the unique id of the voucher
+     * @param amount: the amount of the free tickets
+     */
+    pub fn claim_free_ticket(
+        ctx: Context<ClaimFreeTicket>,
+        voucher_id: u64,
+        amount: u64,
+    ) -> Result<()> {
+        if ctx.accounts.global_authority.paused {
+            return Err(error!(RaffleError::ProgramPaused));
+        }
+
+        // Verify the voucher signature checked by the ed25519 program
+        // nobody can sign for the default key, so vouchers are off until the admin sets one
+        require!(
+            ctx.accounts.global_authority.voucher_authority != Pubkey::default(),
+            RaffleError::InvalidVoucher
+        );
+        let instructions_sysvar = &ctx.accounts.instructions_sysvar;
+        let current_index = load_current_index_checked(instructions_sysvar)?;
+        if current_index == 0 {
+            return Err(error!(RaffleError::InvalidVoucher));
+        }
+        let ed25519_ix = load_instruction_at_checked(current_index as usize - 1, instructions_sysvar)?;
+        let message = voucher_message(
+            &ctx.accounts.raffle.key(),
+            &ctx.accounts.claimer.key(),
+            amount,
+            voucher_id,
+        );
+        verify_ed25519_ix(
+            &ed25519_ix,
+            &ctx.accounts.global_authority.voucher_authority,
+            &message,
         )?;
 
+        let timestamp = Clock::get()?.unix_timestamp;
+        let mut raffle = ctx.accounts.raffle.load_mut()?;
+
+        if timestamp < raffle.start_timestamp {
+            return Err(error!(RaffleError::RaffleNotStarted));
+        }
+        if timestamp > raffle.end_timestamp {
+            return Err(error!(RaffleError::RaffleEnded));
+        }
+        if raffle.frozen == 1 {
+            return Err(error!(RaffleError::RaffleFrozen));
+        }
+
+        let claimer = ctx.accounts.claimer.key();
+        raffle.add_entrant(claimer, amount)?;
+        raffle.draw_if_sold_out(timestamp, ctx.accounts.global_authority.claim_window)?;
+
+        let voucher = &mut ctx.accounts.voucher;
+        voucher.raffle = ctx.accounts.raffle.key();
+        voucher.claimer = claimer;
+        voucher.amount = amount;
         Ok(())
     }
 
     /**
      * @dev Reaveal winner function
-     * @Context has buyer and raffle account address
+     * @Context has buyer and raffle account address and global_authority
      */
     pub fn reveal_winner(ctx: Context<RevealWinner>) -> Result<()> {
         let mut raffle = ctx.accounts.raffle.load_mut()?;
@@ -213,41 +666,106 @@ pub mod raffle {
         if raffle.count == 0 {
             return Err(error!(RaffleError::InvalidRevealedData));
         }
+        if raffle.frozen == 1 {
+            return Err(error!(RaffleError::RaffleFrozen));
+        }
+        if raffle.claimed != 0 {
+            return Err(error!(RaffleError::WinnersAlreadyDrawn));
+        }
 
         // Get the random number of the entrant amount
-        let (player_address, _bump) = Pubkey::find_program_address(
-            &[
-                RANDOM_SEED.as_bytes(),
-                timestamp.to_string().as_bytes(),
-            ],
-            &raffle::ID,
-        );
-        let char_vec: Vec<char> = player_address.to_string().chars().collect();
-        let mut mul = 1;
-        for i in 0..7 {
-            mul *= u64::from(char_vec[i as usize]);
-        }
-        mul += u64::from(char_vec[7]);
-        let winner_index = mul % raffle.count;
-        raffle.winner_index = winner_index;
-        raffle.winner = raffle.entrants[winner_index as usize];
-        raffle.claimed = 2;
+        raffle.draw_winner(timestamp)?;
+        raffle.claim_deadline = claim_deadline(timestamp, ctx.accounts.global_authority.claim_window)?;
+        Ok(())
+    }
+
+    /**
+     * @dev Reroll the winner who didn't claim before the claim deadline
+     * The previous winner's tickets are removed and a new winner is drawn.
+     * The NFT goes back to the creator if the raffle returns unclaimed prizes
+     * or there are no other tickets
+     * @Context has caller and global_authority account and raffle account
+     * the raffle's escrow_authority, its nft ATA and the creator's nft ATA
+     */
+    pub fn reroll_winner(ctx: Context<RerollWinner>) -> Result<()> {
+        let timestamp = Clock::get()?.unix_timestamp;
+        let mut raffle = ctx.accounts.raffle.load_mut()?;
+
+        if raffle.claimed != 2 {
+            return Err(error!(RaffleError::WinnerNotDrawn));
+        }
+        if raffle.frozen == 1 {
+            return Err(error!(RaffleError::RaffleFrozen));
+        }
+        if raffle.claim_deadline == 0 || timestamp <= raffle.claim_deadline {
+            return Err(error!(RaffleError::ClaimDeadlineNotPassed));
+        }
+
+        let previous_winner = raffle.winner;
+        raffle.remove_entrant(previous_winner);
+        if raffle.return_unclaimed == 0 && raffle.active_tickets() > 0 {
+            raffle.draw_winner(timestamp)?;
+            raffle.claim_deadline =
+                claim_deadline(timestamp, ctx.accounts.global_authority.claim_window)?;
+            return Ok(());
+        }
+
+        // Return NFT to the creator's wallet
+        let src_token_account = &mut &ctx.accounts.src_nft_token_account;
+        let dest_token_account = &mut &ctx.accounts.creator_nft_token_account;
+        let token_program = &mut &ctx.accounts.token_program;
+        let raffle_key = ctx.accounts.raffle.key();
+        let escrow_bump = *ctx.bumps.get("escrow_authority").unwrap();
+        let seeds = &[
+            ESCROW_AUTHORITY_SEED.as_bytes(),
+            raffle_key.as_ref(),
+            &[escrow_bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        let cpi_accounts = Transfer {
+            from: src_token_account.to_account_info().clone(),
+            to: dest_token_account.to_account_info().clone(),
+            authority: ctx.accounts.escrow_authority.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new_with_signer(
+                token_program.clone().to_account_info(),
+                cpi_accounts,
+                signer,
+            ),
+            1,
+        )?;
+        raffle.winner = Pubkey::default();
+        raffle.claimed = 3;
+        let bounty = raffle.take_settle_bounty();
+        lamports_transfer_from_pda(
+            &ctx.accounts.raffle.to_account_info(),
+            &ctx.accounts.caller.to_account_info(),
+            bounty,
+        )?;
         Ok(())
     }
 
     /**
      * @dev Claim reward function
-     * @Context has claimer and global_authority account
-     * raffle account and the nft ATA of claimer and global_authority.
-     * @param global_bump: the global_authority's bump
+     * The winner of an instant win raffle can claim before the end of the raffle
+     * @Context has claimer and the raffle's escrow_authority account
+     * raffle account and the nft ATA of claimer and escrow_authority.
      */
-    pub fn claim_reward(ctx: Context<ClaimReward>, global_bump: u8) -> Result<()> {
+    pub fn claim_reward(ctx: Context<ClaimReward>) -> Result<()> {
         let timestamp = Clock::get()?.unix_timestamp;
         let mut raffle = ctx.accounts.raffle.load_mut()?;
 
-        if timestamp < raffle.end_timestamp {
+        if raffle.claimed != 2 && timestamp < raffle.end_timestamp {
             return Err(error!(RaffleError::RaffleNotEnded));
         }
+        if raffle.claimed == 4 {
+            return Err(error!(RaffleError::RaffleCancelled));
+        }
+        if raffle.frozen == 1 {
+            return Err(error!(RaffleError::RaffleFrozen));
+        }
         if raffle.winner != ctx.accounts.claimer.key() {
             return Err(error!(RaffleError::NotWinner));
         }
@@ -256,12 +774,18 @@ pub mod raffle {
         let src_token_account = &mut &ctx.accounts.src_nft_token_account;
         let dest_token_account = &mut &ctx.accounts.claimer_nft_token_account;
         let token_program = &mut &ctx.accounts.token_program;
-        let seeds = &[GLOBAL_AUTHORITY_SEED.as_bytes(), &[global_bump]];
+        let raffle_key = ctx.accounts.raffle.key();
+        let escrow_bump = *ctx.bumps.get("escrow_authority").unwrap();
+        let seeds = &[
+            ESCROW_AUTHORITY_SEED.as_bytes(),
+            raffle_key.as_ref(),
+            &[escrow_bump],
+        ];
         let signer = &[&seeds[..]];
         let cpi_accounts = Transfer {
             from: src_token_account.to_account_info().clone(),
             to: dest_token_account.to_account_info().clone(),
-            authority: ctx.accounts.global_authority.to_account_info(),
+            authority: ctx.accounts.escrow_authority.to_account_info(),
         };
         token::transfer(
             CpiContext::new_with_signer(
@@ -272,16 +796,88 @@ pub mod raffle {
             1,
         )?;
         raffle.claimed = 1;
+        let bounty = raffle.take_settle_bounty();
+        lamports_transfer_from_pda(
+            &ctx.accounts.raffle.to_account_info(),
+            &ctx.accounts.claimer.to_account_info(),
+            bounty,
+        )?;
     
         Ok(())
     }
+
+    /**
+     * @dev Settle raffle function, anyone can deliver the prize to the winner
+     * The NFT is sent to the winner's ATA, which is created if needed and paid by
+     * the cranker, and the cranker receives the settle bounty of the raffle
+     * @Context has cranker and the raffle's escrow_authority account
+     * raffle account, winner and winner's nft ATA, escrow_authority's nft ATA
+     */
+    pub fn settle_raffle(ctx: Context<SettleRaffle>) -> Result<()> {
+        let mut raffle = ctx.accounts.raffle.load_mut()?;
+
+        if raffle.claimed != 2 {
+            return Err(error!(RaffleError::WinnerNotDrawn));
+        }
+        if raffle.frozen == 1 {
+            return Err(error!(RaffleError::RaffleFrozen));
+        }
+
+        // Create the winner's NFT ATA if needed
+        if ctx.accounts.winner_nft_token_account.data_is_empty() {
+            let cpi_accounts = associated_token::Create {
+                payer: ctx.accounts.cranker.to_account_info(),
+                associated_token: ctx.accounts.winner_nft_token_account.to_account_info(),
+                authority: ctx.accounts.winner.to_account_info(),
+                mint: ctx.accounts.nft_mint_address.to_account_info(),
+                system_program: ctx.accounts.system_program.to_account_info(),
+                token_program: ctx.accounts.token_program.to_account_info(),
+                rent: ctx.accounts.rent.to_account_info(),
+            };
+            associated_token::create(CpiContext::new(
+                ctx.accounts.associated_token_program.to_account_info(),
+                cpi_accounts,
+            ))?;
+        }
+
+        // Transfer NFT to the winner's wallet
+        let raffle_key = ctx.accounts.raffle.key();
+        let escrow_bump = *ctx.bumps.get("escrow_authority").unwrap();
+        let seeds = &[
+            ESCROW_AUTHORITY_SEED.as_bytes(),
+            raffle_key.as_ref(),
+            &[escrow_bump],
+        ];
+        let signer = &[&seeds[..]];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.src_nft_token_account.to_account_info(),
+            to: ctx.accounts.winner_nft_token_account.to_account_info(),
+            authority: ctx.accounts.escrow_authority.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                cpi_accounts,
+                signer,
+            ),
+            1,
+        )?;
+        raffle.claimed = 1;
+        let bounty = raffle.take_settle_bounty();
+        lamports_transfer_from_pda(
+            &ctx.accounts.raffle.to_account_info(),
+            &ctx.accounts.cranker.to_account_info(),
+            bounty,
+        )?;
+        Ok(())
+    }
     /**
-     * @dev Withdraw NFT function
-     * @Context has claimer and global_authority account
-     * raffle account and creator's nft ATA and global_authority's nft ATA
-     * @param global_bump: global_authority's bump
+     * @dev Withdraw NFT function, the creator takes back the NFT of a raffle without tickets
+     * one day after its scheduled start
+     * @Context has claimer and the raffle's escrow_authority account
+     * raffle account and creator's nft ATA and escrow_authority's nft ATA
      */
-    pub fn withdraw_nft(ctx: Context<WithdrawNft>, global_bump: u8) -> Result<()> {
+    pub fn withdraw_nft(ctx: Context<WithdrawNft>) -> Result<()> {
         let timestamp = Clock::get()?.unix_timestamp;
         let mut raffle = ctx.accounts.raffle.load_mut()?;
 
@@ -291,22 +887,37 @@ pub mod raffle {
         if raffle.creator != ctx.accounts.claimer.key() {
             return Err(error!(RaffleError::NotCreator));
         }
+        if raffle.claimed == 4 {
+            return Err(error!(RaffleError::RaffleCancelled));
+        }
+        if raffle.claimed != 0 {
+            return Err(error!(RaffleError::NoPrize));
+        }
+        if raffle.frozen == 1 {
+            return Err(error!(RaffleError::RaffleFrozen));
+        }
         if raffle.count != 0 {
             return Err(error!(RaffleError::OtherEntrants));
         }
 
-        // Transfer NFT to the creator's wallet after the raffle ends or 
-        // creator wants to cancel raffle because no tickets are sold
+        // Transfer NFT to the creator's wallet because no tickets were sold
+        // during the first day of the raffle
         let src_token_account = &mut &ctx.accounts.src_nft_token_account;
         let dest_token_account = &mut &ctx.accounts.claimer_nft_token_account;
         let token_program = &mut &ctx.accounts.token_program;
-        let seeds = &[GLOBAL_AUTHORITY_SEED.as_bytes(), &[global_bump]];
+        let raffle_key = ctx.accounts.raffle.key();
+        let escrow_bump = *ctx.bumps.get("escrow_authority").unwrap();
+        let seeds = &[
+            ESCROW_AUTHORITY_SEED.as_bytes(),
+            raffle_key.as_ref(),
+            &[escrow_bump],
+        ];
         let signer = &[&seeds[..]];
 
         let cpi_accounts = Transfer {
             from: src_token_account.to_account_info().clone(),
             to: dest_token_account.to_account_info().clone(),
-            authority: ctx.accounts.global_authority.to_account_info(),
+            authority: ctx.accounts.escrow_authority.to_account_info(),
         };
         token::transfer(
             CpiContext::new_with_signer(
@@ -317,17 +928,278 @@ pub mod raffle {
             1,
         )?;
         raffle.claimed = 3;
+        let bounty = raffle.take_settle_bounty();
+        lamports_transfer_from_pda(
+            &ctx.accounts.raffle.to_account_info(),
+            &ctx.accounts.claimer.to_account_info(),
+            bounty,
+        )?;
         Ok(())
     }
-}
-
-#[derive(Accounts)]
-#[instruction(bump: u8)]
-pub struct Initialize<'info> {
-    #[account(mut)]
-    pub admin: Signer<'info>,
 
-    #[account(
+    /**
+     * @dev Freeze or unfreeze a raffle, frozen raffles can't sell tickets or reveal winner
+     * @Context has admin and global_authority account and raffle account
+     * @param frozen: freeze the raffle or not
+     */
+    pub fn set_raffle_frozen(ctx: Context<SetRaffleFrozen>, frozen: bool) -> Result<()> {
+        let mut raffle = ctx.accounts.raffle.load_mut()?;
+        if raffle.claimed == 4 {
+            return Err(error!(RaffleError::RaffleCancelled));
+        }
+        raffle.frozen = u64::from(frozen);
+        Ok(())
+    }
+
+    /**
+     * @dev Cancel the frozen raffle, return the NFT to the creator and open refunds
+     * The ticket payments went to the creator, royalty recipients and treasury at purchase,
+     * so the admin deposits the raffle's total_paid from their wallet for the refunds
+     * @Context has admin and global_authority account
+     * raffle account and the raffle's escrow_authority
+     * creator's nft ATA and escrow_authority's nft ATA
+     */
+    pub fn force_cancel_raffle(ctx: Context<ForceCancelRaffle>) -> Result<()> {
+        let mut raffle = ctx.accounts.raffle.load_mut()?;
+
+        if raffle.frozen != 1 {
+            return Err(error!(RaffleError::RaffleNotFrozen));
+        }
+        if raffle.claimed == 1 || raffle.claimed == 3 {
+            return Err(error!(RaffleError::NoPrize));
+        }
+        if raffle.claimed == 4 {
+            return Err(error!(RaffleError::RaffleCancelled));
+        }
+        raffle.claimed = 4;
+        let total_paid = raffle.total_paid;
+        let bounty = raffle.take_settle_bounty();
+        // release the raffle account before depositing the refunds to it
+        drop(raffle);
+
+        // Return NFT to the creator's wallet
+        let src_token_account = &mut &ctx.accounts.src_nft_token_account;
+        let dest_token_account = &mut &ctx.accounts.creator_nft_token_account;
+        let token_program = &mut &ctx.accounts.token_program;
+        let raffle_key = ctx.accounts.raffle.key();
+        let escrow_bump = *ctx.bumps.get("escrow_authority").unwrap();
+        let seeds = &[
+            ESCROW_AUTHORITY_SEED.as_bytes(),
+            raffle_key.as_ref(),
+            &[escrow_bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        let cpi_accounts = Transfer {
+            from: src_token_account.to_account_info().clone(),
+            to: dest_token_account.to_account_info().clone(),
+            authority: ctx.accounts.escrow_authority.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new_with_signer(
+                token_program.clone().to_account_info(),
+                cpi_accounts,
+                signer,
+            ),
+            1,
+        )?;
+
+        // Deposit the ticket payments to the raffle account for the refunds
+        sol_transfer_user(
+            ctx.accounts.admin.to_account_info(),
+            ctx.accounts.raffle.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+            total_paid,
+        )?;
+        lamports_transfer_from_pda(
+            &ctx.accounts.raffle.to_account_info(),
+            &ctx.accounts.admin.to_account_info(),
+            bounty,
+        )?;
+        Ok(())
+    }
+
+    /**
+     * @dev Claim refund of the tickets from the cancelled raffle
+     * The refund is the SOL paid by the buyer, free and burn tickets are not refunded
+     * @Context has buyer and raffle account, the buyer's receipt is closed to the buyer
+     */
+    pub fn claim_refund(ctx: Context<ClaimRefund>) -> Result<()> {
+        let mut raffle = ctx.accounts.raffle.load_mut()?;
+
+        if raffle.claimed != 4 {
+            return Err(error!(RaffleError::RaffleNotCancelled));
+        }
+        let refund_amount = ctx.accounts.receipt.paid;
+        if refund_amount == 0 {
+            return Err(error!(RaffleError::NoRefund));
+        }
+        raffle.remove_entrant(ctx.accounts.buyer.key());
+
+        // Transfer the refund from the raffle account to the buyer
+        lamports_transfer_from_pda(
+            &ctx.accounts.raffle.to_account_info(),
+            &ctx.accounts.buyer.to_account_info(),
+            refund_amount,
+        )?;
+        Ok(())
+    }
+
+    /**
+     * @dev Grow the global authority of the first release to the current layout
+     * The settings added since then start from the defaults of initialize,
+     * and the canonical bump is stored for the constraints checking it
+     * @Context has admin and global_authority accounts
+     */
+    pub fn migrate_global(ctx: Context<MigrateGlobal>) -> Result<()> {
+        let global_authority = &ctx.accounts.global_authority;
+        let super_admin = {
+            let data = global_authority.try_borrow_data()?;
+            if data.len() < DISCRIMINATOR_LENGTH
+                || data[..DISCRIMINATOR_LENGTH] != GlobalPool::discriminator()
+            {
+                return Err(error!(RaffleError::InvalidAccountLayout));
+            }
+            if data.len() == GlobalPool::LEN {
+                return Err(error!(RaffleError::AlreadyMigrated));
+            }
+            if data.len() != GlobalPool::LEGACY_LEN {
+                return Err(error!(RaffleError::InvalidAccountLayout));
+            }
+            Pubkey::new(&data[DISCRIMINATOR_LENGTH..GlobalPool::LEGACY_LEN])
+        };
+        if super_admin != ctx.accounts.admin.key() {
+            return Err(error!(RaffleError::NotAdmin));
+        }
+
+        grow_account(
+            global_authority,
+            ctx.accounts.admin.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+            GlobalPool::LEN,
+        )?;
+        let global_pool = GlobalPool {
+            super_admin,
+            min_duration: DAY,
+            bump: *ctx.bumps.get("global_authority").unwrap(),
+            ..GlobalPool::default()
+        };
+        global_pool.try_serialize(&mut &mut global_authority.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    /**
+     * @dev Convert a raffle of the first release to the current layout
+     * The fields in front of the entrants are remapped and the entrants move behind the new settings,
+     * the NFT of a running or drawn raffle moves from the global authority's token account to the escrow
+     * unless its winner already claimed it.
+     * Anyone can migrate a raffle, the payer funds the rent of the larger account.
+     * migrate_global must run first, the global authority signs with its stored bump
+     * @Context has payer, global_authority, raffle and escrow_authority accounts
+     * the global authority's and the escrow's NFT token accounts
+     */
+    pub fn migrate_raffle(ctx: Context<MigrateRaffle>) -> Result<()> {
+        let raffle_info = &ctx.accounts.raffle;
+        let legacy = {
+            let data = raffle_info.try_borrow_data()?;
+            if data.len() < DISCRIMINATOR_LENGTH
+                || data[..DISCRIMINATOR_LENGTH] != RafflePool::discriminator()
+            {
+                return Err(error!(RaffleError::InvalidAccountLayout));
+            }
+            if data.len() == RafflePool::LEN {
+                return Err(error!(RaffleError::AlreadyMigrated));
+            }
+            if data.len() != RafflePool::LEGACY_LEN {
+                return Err(error!(RaffleError::InvalidAccountLayout));
+            }
+            LegacyRaffleHeader::read(&data)
+        };
+
+        grow_account(
+            raffle_info,
+            ctx.accounts.payer.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+            RafflePool::LEN,
+        )?;
+        {
+            // the entrants move first, their old place is taken by the new settings
+            let mut data = raffle_info.try_borrow_mut_data()?;
+            data.copy_within(
+                RafflePool::LEGACY_ENTRANTS_OFFSET..RafflePool::LEGACY_LEN,
+                RafflePool::ENTRANTS_OFFSET,
+            );
+            data[DISCRIMINATOR_LENGTH..RafflePool::ENTRANTS_OFFSET].fill(0);
+        }
+        let raffle_loader = AccountLoader::<RafflePool>::try_from(raffle_info)?;
+        raffle_loader.load_mut()?.set_legacy_fields(&legacy);
+
+        // the NFT of a claimed or withdrawn raffle already left the program
+        if legacy.claimed != 0 && legacy.claimed != 2 {
+            return Ok(());
+        }
+        let src_nft_token_account =
+            Account::<TokenAccount>::try_from(&ctx.accounts.src_nft_token_account)?;
+        if src_nft_token_account.mint != legacy.nft_mint
+            || src_nft_token_account.owner != ctx.accounts.global_authority.key()
+        {
+            return Err(error!(RaffleError::InvalidNftTokenAccount));
+        }
+        // a drawn raffle of the first release stayed drawn after its winner claimed the NFT
+        if src_nft_token_account.amount == 0 {
+            return Ok(());
+        }
+        let dest_nft_token_account =
+            Account::<TokenAccount>::try_from(&ctx.accounts.dest_nft_token_account)?;
+        if dest_nft_token_account.mint != legacy.nft_mint
+            || dest_nft_token_account.owner != ctx.accounts.escrow_authority.key()
+        {
+            return Err(error!(RaffleError::InvalidNftTokenAccount));
+        }
+
+        let seeds = &[
+            GLOBAL_AUTHORITY_SEED.as_bytes(),
+            &[ctx.accounts.global_authority.bump],
+        ];
+        let signer = &[&seeds[..]];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.src_nft_token_account.to_account_info(),
+            to: ctx.accounts.dest_nft_token_account.to_account_info(),
+            authority: ctx.accounts.global_authority.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                cpi_accounts,
+                signer,
+            ),
+            1,
+        )?;
+        Ok(())
+    }
+
+    /**
+     * @dev Convert the collection pool created before the version header to the current layout
+     * @Context has admin, global_authority and the collection pool
+     */
+    pub fn migrate_collection(ctx: Context<MigrateCollection>) -> Result<()> {
+        migrate_legacy_layout(
+            &ctx.accounts.collection,
+            ctx.accounts.admin.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+            CollectionPool::discriminator(),
+            CollectionPool::LEN,
+            COLLECTION_VERSION,
+        )
+    }
+}
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
         init,
         seeds = [GLOBAL_AUTHORITY_SEED.as_ref()],
         bump,
@@ -348,7 +1220,17 @@ pub struct AddCollection<'info> {
     #[account(mut)]
     pub admin: Signer<'info>,
 
-    #[account(mut)]
+    #[account(
+        seeds = [GLOBAL_AUTHORITY_SEED.as_ref()],
+        bump = global_authority.bump,
+        constraint = global_authority.super_admin == admin.key() @ RaffleError::NotAdmin,
+    )]
+    pub global_authority: Account<'info, GlobalPool>,
+
+    #[account(
+        mut,
+        constraint = CollectionPool::is_current(&collection.to_account_info()) @ RaffleError::AccountNotMigrated,
+    )]
     pub collection: AccountLoader<'info, CollectionPool>,
 
     /// CHECK: This is not dangerous because we don't read or write from this account
@@ -356,36 +1238,182 @@ pub struct AddCollection<'info> {
 }
 
 #[derive(Accounts)]
-#[instruction(_global_bump: u8)]
-pub struct CreateRaffle<'info> {
+pub struct SetGlobalConfig<'info> {
     #[account(mut)]
     pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [GLOBAL_AUTHORITY_SEED.as_ref()],
+        bump = global_authority.bump,
+        constraint = global_authority.super_admin == admin.key() @ RaffleError::NotAdmin,
+    )]
+    pub global_authority: Account<'info, GlobalPool>,
+}
+
+#[derive(Accounts)]
+pub struct SetPaused<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
     #[account(
         mut,
         seeds = [GLOBAL_AUTHORITY_SEED.as_ref()],
+        bump = global_authority.bump,
+        constraint = global_authority.super_admin == authority.key()
+            || global_authority.pauser == authority.key() @ RaffleError::NotPauser,
+    )]
+    pub global_authority: Account<'info, GlobalPool>,
+}
+
+#[derive(Accounts)]
+pub struct InitCollectionDuration<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [GLOBAL_AUTHORITY_SEED.as_ref()],
+        bump = global_authority.bump,
+        constraint = global_authority.super_admin == admin.key() @ RaffleError::NotAdmin,
+    )]
+    pub global_authority: Account<'info, GlobalPool>,
+
+    /// CHECK: This is not dangerous because we don't read or write from this account
+    pub collection_id: AccountInfo<'info>,
+
+    #[account(
+        init,
+        seeds = [DURATION_SEED.as_ref(), collection_id.key().as_ref()],
         bump,
+        payer = admin,
+        space = CollectionDuration::LEN
+    )]
+    pub collection_duration: Account<'info, CollectionDuration>,
+
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct SetCollectionDuration<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [GLOBAL_AUTHORITY_SEED.as_ref()],
+        bump = global_authority.bump,
+        constraint = global_authority.super_admin == admin.key() @ RaffleError::NotAdmin,
     )]
     pub global_authority: Account<'info, GlobalPool>,
 
-    #[account(zero)]
-    pub raffle: AccountLoader<'info, RafflePool>,
+    #[account(
+        mut,
+        seeds = [DURATION_SEED.as_ref(), collection_duration.collection.as_ref()],
+        bump,
+    )]
+    pub collection_duration: Account<'info, CollectionDuration>,
+}
 
+#[derive(Accounts)]
+pub struct InitReferrer<'info> {
     #[account(mut)]
+    pub referrer: Signer<'info>,
+
+    #[account(
+        init,
+        seeds = [REFERRER_SEED.as_ref(), referrer.key().as_ref()],
+        bump,
+        payer = referrer,
+        space = ReferrerPool::LEN
+    )]
+    pub referrer_pool: Account<'info, ReferrerPool>,
+
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct AllocateRaffle<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: This is the creator of the raffle, it signs create_raffle
+    pub creator: AccountInfo<'info>,
+
+    /// CHECK: This is not dangerous because we don't read or write from this account
+    pub nft_mint_address: AccountInfo<'info>,
+
+    /// CHECK: This is the raffle PDA, created or grown by the instruction
+    #[account(
+        mut,
+        seeds = [
+            RAFFLE_SEED.as_ref(),
+            creator.key().as_ref(),
+            nft_mint_address.key().as_ref(),
+            nonce.to_le_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    pub raffle: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct CreateRaffle<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [GLOBAL_AUTHORITY_SEED.as_ref()],
+        bump = global_authority.bump,
+    )]
+    pub global_authority: Account<'info, GlobalPool>,
+
+    #[account(
+        zero,
+        seeds = [
+            RAFFLE_SEED.as_ref(),
+            admin.key().as_ref(),
+            nft_mint_address.key().as_ref(),
+            nonce.to_le_bytes().as_ref(),
+        ],
+        bump,
+        constraint = raffle.to_account_info().data_len() == RafflePool::LEN @ RaffleError::RaffleNotAllocated,
+    )]
+    pub raffle: AccountLoader<'info, RafflePool>,
+
+    /// CHECK: This is the raffle's escrow PDA, it only signs for the prize NFT
+    #[account(
+        seeds = [ESCROW_AUTHORITY_SEED.as_ref(), raffle.key().as_ref()],
+        bump,
+    )]
+    pub escrow_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        constraint = CollectionPool::is_current(&collection.to_account_info()) @ RaffleError::AccountNotMigrated,
+    )]
     pub collection: AccountLoader<'info, CollectionPool>,
 
+    /// CHECK: This is the collection_duration PDA of the NFT's collection, checked in the instruction
+    pub collection_duration: AccountInfo<'info>,
+
     #[account(
         mut,
         constraint = owner_temp_nft_account.mint == *nft_mint_address.to_account_info().key,
         constraint = owner_temp_nft_account.owner == *admin.key,
     )]
-    pub owner_temp_nft_account: CpiAccount<'info, TokenAccount>,
+    pub owner_temp_nft_account: Account<'info, TokenAccount>,
 
     #[account(
         mut,
         constraint = dest_nft_token_account.mint == *nft_mint_address.to_account_info().key,
-        constraint = dest_nft_token_account.owner == *global_authority.to_account_info().key,
+        constraint = dest_nft_token_account.owner == *escrow_authority.key,
     )]
-    pub dest_nft_token_account: CpiAccount<'info, TokenAccount>,
+    pub dest_nft_token_account: Account<'info, TokenAccount>,
     /// CHECK: This is not dangerous because we don't read or write from this account
     pub nft_mint_address: AccountInfo<'info>,
     /// CHECK: This is not dangerous because we don't read or write from this account
@@ -396,6 +1424,8 @@ pub struct CreateRaffle<'info> {
     pub mint_metadata: AccountInfo<'info>,
 
     pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
 
     // the token metadata program
     /// CHECK: This is not dangerous because we don't read or write from this account
@@ -404,22 +1434,27 @@ pub struct CreateRaffle<'info> {
 }
 
 #[derive(Accounts)]
-#[instruction(_global_bump: u8)]
 pub struct BuyTickets<'info> {
     #[account(mut)]
     pub buyer: Signer<'info>,
 
-    #[account(mut)]
+    #[account(
+        mut,
+        constraint = RafflePool::is_current(&raffle.to_account_info()) @ RaffleError::AccountNotMigrated,
+    )]
     pub raffle: AccountLoader<'info, RafflePool>,
 
     #[account(
         mut,
         seeds = [GLOBAL_AUTHORITY_SEED.as_ref()],
-        bump,
+        bump = global_authority.bump,
     )]
     pub global_authority: Account<'info, GlobalPool>,
-    /// CHECK: This is not dangerous because we don't read or write from this account
-    #[account(mut)]
+    /// CHECK: This is not dangerous because it must be the raffle's creator
+    #[account(
+        mut,
+        constraint = creator.key() == raffle.load()?.creator @ RaffleError::NotCreator,
+    )]
     pub creator: AccountInfo<'info>,
 
     /// CHECK: This is not dangerous because we don't read or write from this account
@@ -429,47 +1464,208 @@ pub struct BuyTickets<'info> {
     )]
     pub treasury_wallet: AccountInfo<'info>,
 
+    #[account(
+        init_if_needed,
+        seeds = [RECEIPT_SEED.as_ref(), raffle.key().as_ref(), buyer.key().as_ref()],
+        bump,
+        payer = buyer,
+        space = TicketReceipt::LEN
+    )]
+    pub receipt: Account<'info, TicketReceipt>,
+
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct RevealWinner<'info> {
+pub struct BurnTokensForTickets<'info> {
     #[account(mut)]
     pub buyer: Signer<'info>,
 
+    #[account(
+        mut,
+        constraint = RafflePool::is_current(&raffle.to_account_info()) @ RaffleError::AccountNotMigrated,
+    )]
+    pub raffle: AccountLoader<'info, RafflePool>,
+
+    #[account(
+        seeds = [GLOBAL_AUTHORITY_SEED.as_ref()],
+        bump = global_authority.bump,
+    )]
+    pub global_authority: Account<'info, GlobalPool>,
+
     #[account(mut)]
+    pub burn_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = buyer_token_account.mint == burn_mint.key(),
+        constraint = buyer_token_account.owner == buyer.key(),
+    )]
+    pub buyer_token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct BurnNftForTicket<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = RafflePool::is_current(&raffle.to_account_info()) @ RaffleError::AccountNotMigrated,
+    )]
     pub raffle: AccountLoader<'info, RafflePool>,
+
+    #[account(
+        seeds = [GLOBAL_AUTHORITY_SEED.as_ref()],
+        bump = global_authority.bump,
+    )]
+    pub global_authority: Account<'info, GlobalPool>,
+
+    #[account(mut)]
+    pub burn_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = buyer_token_account.mint == burn_mint.key(),
+        constraint = buyer_token_account.owner == buyer.key(),
+    )]
+    pub buyer_token_account: Account<'info, TokenAccount>,
+
+    /// CHECK: This is not dangerous because we check the metadata address and owner
+    #[account(constraint = mint_metadata.owner == &metaplex_token_metadata::ID)]
+    pub mint_metadata: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
 }
 
 #[derive(Accounts)]
-#[instruction(global_bump: u8)]
-pub struct ClaimReward<'info> {
+#[instruction(voucher_id: u64)]
+pub struct ClaimFreeTicket<'info> {
     #[account(mut)]
     pub claimer: Signer<'info>,
 
     #[account(
-        mut,
         seeds = [GLOBAL_AUTHORITY_SEED.as_ref()],
+        bump = global_authority.bump,
+    )]
+    pub global_authority: Account<'info, GlobalPool>,
+
+    #[account(
+        mut,
+        constraint = RafflePool::is_current(&raffle.to_account_info()) @ RaffleError::AccountNotMigrated,
+    )]
+    pub raffle: AccountLoader<'info, RafflePool>,
+
+    // creating the voucher PDA fails if the voucher was already claimed
+    #[account(
+        init,
+        seeds = [VOUCHER_SEED.as_ref(), voucher_id.to_le_bytes().as_ref()],
         bump,
+        payer = claimer,
+        space = VoucherPool::LEN
+    )]
+    pub voucher: Account<'info, VoucherPool>,
+
+    /// CHECK: This is the instructions sysvar to load the ed25519 instruction
+    #[account(address = solana_program::sysvar::instructions::ID)]
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct RevealWinner<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = RafflePool::is_current(&raffle.to_account_info()) @ RaffleError::AccountNotMigrated,
+    )]
+    pub raffle: AccountLoader<'info, RafflePool>,
+
+    #[account(
+        seeds = [GLOBAL_AUTHORITY_SEED.as_ref()],
+        bump = global_authority.bump,
     )]
     pub global_authority: Account<'info, GlobalPool>,
+}
 
+#[derive(Accounts)]
+pub struct RerollWinner<'info> {
     #[account(mut)]
+    pub caller: Signer<'info>,
+
+    #[account(
+        seeds = [GLOBAL_AUTHORITY_SEED.as_ref()],
+        bump = global_authority.bump,
+    )]
+    pub global_authority: Account<'info, GlobalPool>,
+
+    #[account(
+        mut,
+        constraint = RafflePool::is_current(&raffle.to_account_info()) @ RaffleError::AccountNotMigrated,
+    )]
     pub raffle: AccountLoader<'info, RafflePool>,
 
+    /// CHECK: This is the raffle's escrow PDA, it only signs for the prize NFT
+    #[account(
+        seeds = [ESCROW_AUTHORITY_SEED.as_ref(), raffle.key().as_ref()],
+        bump,
+    )]
+    pub escrow_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        constraint = creator_nft_token_account.mint == raffle.load()?.nft_mint,
+        constraint = creator_nft_token_account.owner == raffle.load()?.creator,
+    )]
+    pub creator_nft_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = src_nft_token_account.mint == raffle.load()?.nft_mint,
+        constraint = src_nft_token_account.owner == *escrow_authority.key,
+    )]
+    pub src_nft_token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimReward<'info> {
+    #[account(mut)]
+    pub claimer: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = RafflePool::is_current(&raffle.to_account_info()) @ RaffleError::AccountNotMigrated,
+    )]
+    pub raffle: AccountLoader<'info, RafflePool>,
+
+    /// CHECK: This is the raffle's escrow PDA, it only signs for the prize NFT
+    #[account(
+        seeds = [ESCROW_AUTHORITY_SEED.as_ref(), raffle.key().as_ref()],
+        bump,
+    )]
+    pub escrow_authority: AccountInfo<'info>,
+
     #[account(
         mut,
         constraint = claimer_nft_token_account.mint == *nft_mint_address.to_account_info().key,
         constraint = claimer_nft_token_account.owner == *claimer.key,
     )]
-    pub claimer_nft_token_account: CpiAccount<'info, TokenAccount>,
+    pub claimer_nft_token_account: Account<'info, TokenAccount>,
 
     #[account(
         mut,
         constraint = src_nft_token_account.mint == *nft_mint_address.to_account_info().key,
-        constraint = src_nft_token_account.owner == *global_authority.to_account_info().key,
+        constraint = src_nft_token_account.owner == *escrow_authority.key,
     )]
-    pub src_nft_token_account: CpiAccount<'info, TokenAccount>,
+    pub src_nft_token_account: Account<'info, TokenAccount>,
 
     /// CHECK: This is not dangerous because we don't read or write from this account
     pub nft_mint_address: AccountInfo<'info>,
@@ -477,36 +1673,243 @@ pub struct ClaimReward<'info> {
 }
 
 #[derive(Accounts)]
-#[instruction(global_bump: u8)]
-pub struct WithdrawNft<'info> {
+pub struct SettleRaffle<'info> {
     #[account(mut)]
-    pub claimer: Signer<'info>,
+    pub cranker: Signer<'info>,
 
     #[account(
         mut,
-        seeds = [GLOBAL_AUTHORITY_SEED.as_ref()],
+        constraint = RafflePool::is_current(&raffle.to_account_info()) @ RaffleError::AccountNotMigrated,
+    )]
+    pub raffle: AccountLoader<'info, RafflePool>,
+
+    /// CHECK: This is the raffle's escrow PDA, it only signs for the prize NFT
+    #[account(
+        seeds = [ESCROW_AUTHORITY_SEED.as_ref(), raffle.key().as_ref()],
         bump,
     )]
-    pub global_authority: Account<'info, GlobalPool>,
+    pub escrow_authority: AccountInfo<'info>,
+
+    /// CHECK: This is not dangerous because it must be the raffle's winner
+    #[account(constraint = winner.key() == raffle.load()?.winner)]
+    pub winner: AccountInfo<'info>,
+
+    /// CHECK: This is the winner's NFT ATA, created by this instruction if needed
+    #[account(
+        mut,
+        address = get_associated_token_address(&winner.key(), &nft_mint_address.key()),
+    )]
+    pub winner_nft_token_account: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        constraint = src_nft_token_account.mint == nft_mint_address.key(),
+        constraint = src_nft_token_account.owner == *escrow_authority.key,
+    )]
+    pub src_nft_token_account: Account<'info, TokenAccount>,
+
+    /// CHECK: This is not dangerous because it must be the raffle's NFT mint
+    #[account(constraint = nft_mint_address.key() == raffle.load()?.nft_mint)]
+    pub nft_mint_address: AccountInfo<'info>,
 
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawNft<'info> {
     #[account(mut)]
+    pub claimer: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = RafflePool::is_current(&raffle.to_account_info()) @ RaffleError::AccountNotMigrated,
+    )]
     pub raffle: AccountLoader<'info, RafflePool>,
 
+    /// CHECK: This is the raffle's escrow PDA, it only signs for the prize NFT
+    #[account(
+        seeds = [ESCROW_AUTHORITY_SEED.as_ref(), raffle.key().as_ref()],
+        bump,
+    )]
+    pub escrow_authority: AccountInfo<'info>,
+
     #[account(
         mut,
         constraint = claimer_nft_token_account.mint == *nft_mint_address.to_account_info().key,
         constraint = claimer_nft_token_account.owner == *claimer.key,
     )]
-    pub claimer_nft_token_account: CpiAccount<'info, TokenAccount>,
+    pub claimer_nft_token_account: Account<'info, TokenAccount>,
 
     #[account(
         mut,
         constraint = src_nft_token_account.mint == *nft_mint_address.to_account_info().key,
-        constraint = src_nft_token_account.owner == *global_authority.to_account_info().key,
+        constraint = src_nft_token_account.owner == *escrow_authority.key,
     )]
-    pub src_nft_token_account: CpiAccount<'info, TokenAccount>,
+    pub src_nft_token_account: Account<'info, TokenAccount>,
 
     /// CHECK: This is not dangerous because we don't read or write from this account
     pub nft_mint_address: AccountInfo<'info>,
     pub token_program: Program<'info, Token>,
 }
+
+#[derive(Accounts)]
+pub struct SetRaffleFrozen<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [GLOBAL_AUTHORITY_SEED.as_ref()],
+        bump = global_authority.bump,
+        constraint = global_authority.super_admin == admin.key() @ RaffleError::NotAdmin,
+    )]
+    pub global_authority: Account<'info, GlobalPool>,
+
+    #[account(
+        mut,
+        constraint = RafflePool::is_current(&raffle.to_account_info()) @ RaffleError::AccountNotMigrated,
+    )]
+    pub raffle: AccountLoader<'info, RafflePool>,
+}
+
+#[derive(Accounts)]
+pub struct ForceCancelRaffle<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [GLOBAL_AUTHORITY_SEED.as_ref()],
+        bump = global_authority.bump,
+        constraint = global_authority.super_admin == admin.key() @ RaffleError::NotAdmin,
+    )]
+    pub global_authority: Account<'info, GlobalPool>,
+
+    #[account(
+        mut,
+        constraint = RafflePool::is_current(&raffle.to_account_info()) @ RaffleError::AccountNotMigrated,
+    )]
+    pub raffle: AccountLoader<'info, RafflePool>,
+
+    /// CHECK: This is the raffle's escrow PDA, it only signs for the prize NFT
+    #[account(
+        seeds = [ESCROW_AUTHORITY_SEED.as_ref(), raffle.key().as_ref()],
+        bump,
+    )]
+    pub escrow_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        constraint = creator_nft_token_account.mint == raffle.load()?.nft_mint,
+        constraint = creator_nft_token_account.owner == raffle.load()?.creator,
+    )]
+    pub creator_nft_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = src_nft_token_account.mint == raffle.load()?.nft_mint,
+        constraint = src_nft_token_account.owner == *escrow_authority.key,
+    )]
+    pub src_nft_token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimRefund<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = RafflePool::is_current(&raffle.to_account_info()) @ RaffleError::AccountNotMigrated,
+    )]
+    pub raffle: AccountLoader<'info, RafflePool>,
+
+    #[account(
+        mut,
+        seeds = [RECEIPT_SEED.as_ref(), raffle.key().as_ref(), buyer.key().as_ref()],
+        bump,
+        close = buyer,
+    )]
+    pub receipt: Account<'info, TicketReceipt>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateGlobal<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// CHECK: This is the global authority of any layout, the discriminator, length and admin are checked by the migration
+    #[account(
+        mut,
+        seeds = [GLOBAL_AUTHORITY_SEED.as_ref()],
+        bump,
+        constraint = global_authority.owner == &crate::ID,
+    )]
+    pub global_authority: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateRaffle<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [GLOBAL_AUTHORITY_SEED.as_ref()],
+        bump = global_authority.bump,
+    )]
+    pub global_authority: Account<'info, GlobalPool>,
+
+    /// CHECK: This is a raffle of any layout, the discriminator and length are checked by the migration
+    #[account(
+        mut,
+        constraint = raffle.owner == &crate::ID,
+    )]
+    pub raffle: AccountInfo<'info>,
+
+    /// CHECK: This is the raffle's escrow PDA, it only signs for the prize NFT
+    #[account(
+        seeds = [ESCROW_AUTHORITY_SEED.as_ref(), raffle.key().as_ref()],
+        bump,
+    )]
+    pub escrow_authority: AccountInfo<'info>,
+
+    /// CHECK: This is the global authority's NFT token account of the first release,
+    /// checked by the migration if it still holds the NFT
+    #[account(mut)]
+    pub src_nft_token_account: AccountInfo<'info>,
+
+    /// CHECK: This is the escrow's NFT token account, checked by the migration if the NFT moves
+    #[account(mut)]
+    pub dest_nft_token_account: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateCollection<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [GLOBAL_AUTHORITY_SEED.as_ref()],
+        bump = global_authority.bump,
+        constraint = global_authority.super_admin == admin.key() @ RaffleError::NotAdmin,
+    )]
+    pub global_authority: Account<'info, GlobalPool>,
+
+    /// CHECK: This is the collection pool of any layout, the discriminator and length are checked by the migration
+    #[account(
+        mut,
+        constraint = collection.owner == &crate::ID,
+    )]
+    pub collection: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
@@ -1,6 +1,14 @@
 use anchor_lang::prelude::*;
-use solana_program::entrypoint::ProgramResult;
+use metaplex_token_metadata::state::Metadata;
+use solana_program::entrypoint::{ProgramResult, MAX_PERMITTED_DATA_INCREASE};
+use solana_program::instruction::Instruction;
 use solana_program::program::{invoke, invoke_signed};
+use solana_program::pubkey::PUBKEY_BYTES;
+use solana_program::system_instruction;
+
+use crate::account::{DISCRIMINATOR_LENGTH, U64_LENGTH};
+use crate::constants::*;
+use crate::error::*;
 
 // transfer sol
 pub fn sol_transfer_with_signer<'a>(
@@ -23,3 +31,281 @@ pub fn sol_transfer_user<'a>(
     let ix = solana_program::system_instruction::transfer(source.key, destination.key, amount);
     invoke(&ix, &[source, destination, system_program])
 }
+
+// move lamports out of an account owned by this program
+pub fn lamports_transfer_from_pda<'a>(
+    source: &AccountInfo<'a>,
+    destination: &AccountInfo<'a>,
+    amount: u64,
+) -> Result<()> {
+    **source.try_borrow_mut_lamports()? = checked_sub(source.lamports(), amount)?;
+    **destination.try_borrow_mut_lamports()? = checked_add(destination.lamports(), amount)?;
+    Ok(())
+}
+
+// max_duration 0 means the raffle duration has no upper bound
+pub fn verify_duration_bounds(min_duration: i64, max_duration: i64) -> Result<()> {
+    if min_duration < 0 || max_duration < 0 || (max_duration != 0 && max_duration < min_duration) {
+        return Err(error!(RaffleError::InvalidDuration));
+    }
+    Ok(())
+}
+
+// the NFT stays locked from now until the end, so a late start counts for the maximum
+pub fn verify_raffle_duration(
+    timestamp: i64,
+    start_timestamp: i64,
+    end_timestamp: i64,
+    min_duration: i64,
+    max_duration: i64,
+) -> Result<()> {
+    let duration = end_timestamp
+        .checked_sub(start_timestamp)
+        .ok_or(RaffleError::InvalidCalculation)?;
+    let locked = end_timestamp
+        .checked_sub(timestamp)
+        .ok_or(RaffleError::InvalidCalculation)?;
+    if duration < min_duration || (max_duration != 0 && locked > max_duration) {
+        return Err(error!(RaffleError::EndTimeError));
+    }
+    Ok(())
+}
+
+// checked math for ticket pricing and fees
+pub fn checked_add(a: u64, b: u64) -> Result<u64> {
+    a.checked_add(b)
+        .ok_or_else(|| error!(RaffleError::InvalidCalculation))
+}
+
+pub fn checked_sub(a: u64, b: u64) -> Result<u64> {
+    a.checked_sub(b)
+        .ok_or_else(|| error!(RaffleError::InvalidCalculation))
+}
+
+pub fn checked_mul(a: u64, b: u64) -> Result<u64> {
+    a.checked_mul(b)
+        .ok_or_else(|| error!(RaffleError::InvalidCalculation))
+}
+
+pub fn checked_div(a: u64, b: u64) -> Result<u64> {
+    a.checked_div(b)
+        .ok_or_else(|| error!(RaffleError::InvalidCalculation))
+}
+
+// split the ticket payment into (creator_amount, fee_amount)
+// the fee is the remainder so both parts always add up to the total
+pub fn split_commission(total_amount: u64) -> Result<(u64, u64)> {
+    let creator_amount = checked_div(
+        checked_mul(total_amount, checked_sub(100, COMMISSION_FEE)?)?,
+        100,
+    )?;
+    let fee_amount = checked_sub(total_amount, creator_amount)?;
+    Ok((creator_amount, fee_amount))
+}
+
+// royalty of the creator amount by basis points
+pub fn royalty_amount(creator_amount: u64, basis_points: u64) -> Result<u64> {
+    checked_div(checked_mul(creator_amount, basis_points)?, BASIS_POINTS)
+}
+
+// split the commission into (referral_amount, treasury_amount)
+pub fn split_referral(fee_amount: u64, referral_share: u64) -> Result<(u64, u64)> {
+    let referral_amount = checked_div(checked_mul(fee_amount, referral_share)?, 100)?;
+    let treasury_amount = checked_sub(fee_amount, referral_amount)?;
+    Ok((referral_amount, treasury_amount))
+}
+
+// random number below `modulus` derived from the timestamp
+pub fn draw_random(timestamp: i64, modulus: u64) -> u64 {
+    let (player_address, _bump) = Pubkey::find_program_address(
+        &[RANDOM_SEED.as_bytes(), timestamp.to_string().as_bytes()],
+        &crate::ID,
+    );
+    let char_vec: Vec<char> = player_address.to_string().chars().collect();
+    let mut mul = 1;
+    for i in 0..7 {
+        mul *= u64::from(char_vec[i as usize]);
+    }
+    mul += u64::from(char_vec[7]);
+    mul % modulus
+}
+
+// claim deadline of the winner drawn at `timestamp`, 0 means no deadline
+pub fn claim_deadline(timestamp: i64, claim_window: i64) -> Result<i64> {
+    if claim_window == 0 {
+        return Ok(0);
+    }
+    timestamp
+        .checked_add(claim_window)
+        .ok_or_else(|| error!(RaffleError::InvalidCalculation))
+}
+
+// message signed by the voucher authority for the free tickets
+pub fn voucher_message(raffle: &Pubkey, claimer: &Pubkey, amount: u64, voucher_id: u64) -> Vec<u8> {
+    let mut message = Vec::with_capacity(80);
+    message.extend_from_slice(raffle.as_ref());
+    message.extend_from_slice(claimer.as_ref());
+    message.extend_from_slice(&amount.to_le_bytes());
+    message.extend_from_slice(&voucher_id.to_le_bytes());
+    message
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<usize> {
+    let bytes = data
+        .get(offset..offset + 2)
+        .ok_or_else(|| error!(RaffleError::InvalidVoucher))?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+}
+
+// verify the ed25519 program instruction checked `signer` signing `message`
+pub fn verify_ed25519_ix(ix: &Instruction, signer: &Pubkey, message: &[u8]) -> Result<()> {
+    let data = &ix.data;
+    // one signature, and the signature, public key and message live in this instruction
+    if ix.program_id != solana_program::ed25519_program::id() || data.len() < 16 || data[0] != 1 {
+        return Err(error!(RaffleError::InvalidVoucher));
+    }
+    for index_offset in [4, 8, 14] {
+        if read_u16(data, index_offset)? != u16::MAX as usize {
+            return Err(error!(RaffleError::InvalidVoucher));
+        }
+    }
+    let public_key_offset = read_u16(data, 6)?;
+    let message_offset = read_u16(data, 10)?;
+    let message_size = read_u16(data, 12)?;
+
+    let public_key = data.get(public_key_offset..public_key_offset + PUBKEY_BYTES);
+    let signed_message = data.get(message_offset..message_offset + message_size);
+    if public_key != Some(signer.as_ref()) || signed_message != Some(message) {
+        return Err(error!(RaffleError::InvalidVoucher));
+    }
+    Ok(())
+}
+
+// verify the metadata account is the metaplex metadata PDA of the mint
+pub fn verify_metadata_address(mint: &Pubkey, metadata: &Pubkey) -> Result<()> {
+    let (metadata_address, _) = Pubkey::find_program_address(
+        &[
+            metaplex_token_metadata::state::PREFIX.as_bytes(),
+            metaplex_token_metadata::id().as_ref(),
+            mint.as_ref(),
+        ],
+        &metaplex_token_metadata::id(),
+    );
+    require!(metadata_address == *metadata, RaffleError::InvaliedMetadata);
+    Ok(())
+}
+
+// verify one of the collections is a verified creator of the NFT
+// returns the first verified creator of the NFT which is in the collections
+pub fn verify_nft_collection(nft_metadata: &Metadata, collections: &[Pubkey]) -> Result<Pubkey> {
+    if let Some(creators) = &nft_metadata.data.creators {
+        for creator in creators {
+            if creator.verified && collections.contains(&creator.address) {
+                return Ok(creator.address);
+            }
+        }
+        Err(error!(RaffleError::InvalidCollection))
+    } else {
+        Err(error!(RaffleError::MetadataCreatorParseError))
+    }
+}
+
+// create the PDA or grow it by MAX_PERMITTED_DATA_INCREASE bytes at most until it has `len` bytes
+// one instruction can't allocate more, so large accounts take several instructions
+// the payer funds the rent of the new bytes
+pub fn allocate_pda_step<'a>(
+    account: &AccountInfo<'a>,
+    payer: AccountInfo<'a>,
+    system_program: AccountInfo<'a>,
+    len: usize,
+    signer_seeds: &[&[u8]],
+) -> Result<()> {
+    let current_len = account.data_len();
+    if current_len >= len {
+        return Ok(());
+    }
+    let new_len = len.min(current_len + MAX_PERMITTED_DATA_INCREASE);
+    let rent_amount = Rent::get()?
+        .minimum_balance(new_len)
+        .saturating_sub(account.lamports());
+    if rent_amount > 0 {
+        sol_transfer_user(payer, account.clone(), system_program.clone(), rent_amount)?;
+    }
+
+    if current_len == 0 {
+        invoke_signed(
+            &system_instruction::allocate(account.key, new_len as u64),
+            &[account.clone(), system_program.clone()],
+            &[signer_seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(account.key, &crate::ID),
+            &[account.clone(), system_program],
+            &[signer_seeds],
+        )?;
+    } else {
+        if account.owner != &crate::ID {
+            return Err(error!(RaffleError::InvalidAccountLayout));
+        }
+        account.realloc(new_len, true)?;
+    }
+    Ok(())
+}
+
+// grow a program owned account to `len` bytes, the payer funds the rent of the new bytes
+pub fn grow_account<'a>(
+    account: &AccountInfo<'a>,
+    payer: AccountInfo<'a>,
+    system_program: AccountInfo<'a>,
+    len: usize,
+) -> Result<()> {
+    let rent_amount = Rent::get()?
+        .minimum_balance(len)
+        .saturating_sub(account.lamports());
+    if rent_amount > 0 {
+        sol_transfer_user(payer, account.clone(), system_program, rent_amount)?;
+    }
+    account.realloc(len, true)?;
+    Ok(())
+}
+
+// move the fields of a zero copy account of the legacy layout behind the version header
+// the payer funds the rent of the header, so the lamports held by the account stay the same
+pub fn migrate_legacy_layout<'a>(
+    account: &AccountInfo<'a>,
+    payer: AccountInfo<'a>,
+    system_program: AccountInfo<'a>,
+    discriminator: [u8; 8],
+    len: usize,
+    version: u64,
+) -> Result<()> {
+    {
+        let data = account.try_borrow_data()?;
+        if data.len() < DISCRIMINATOR_LENGTH || data[..DISCRIMINATOR_LENGTH] != discriminator {
+            return Err(error!(RaffleError::InvalidAccountLayout));
+        }
+        if data.len() == len {
+            return Err(error!(RaffleError::AlreadyMigrated));
+        }
+        if data.len() != len - U64_LENGTH {
+            return Err(error!(RaffleError::InvalidAccountLayout));
+        }
+    }
+
+    let rent = Rent::get()?;
+    let rent_amount = checked_sub(
+        rent.minimum_balance(len),
+        rent.minimum_balance(len - U64_LENGTH),
+    )?;
+    sol_transfer_user(payer, account.clone(), system_program, rent_amount)?;
+
+    account.realloc(len, false)?;
+    let mut data = account.try_borrow_mut_data()?;
+    data.copy_within(
+        DISCRIMINATOR_LENGTH..len - U64_LENGTH,
+        DISCRIMINATOR_LENGTH + U64_LENGTH,
+    );
+    data[DISCRIMINATOR_LENGTH..DISCRIMINATOR_LENGTH + U64_LENGTH]
+        .copy_from_slice(&version.to_le_bytes());
+    Ok(())
+}
@@ -0,0 +1,1308 @@
+#![allow(dead_code)]
+
+mod native;
+
+use std::mem::size_of;
+
+use anchor_lang::{
+    error::ErrorCode, system_program, AccountDeserialize, AnchorSerialize, Discriminator,
+    InstructionData, ToAccountMetas,
+};
+use anchor_spl::associated_token::get_associated_token_address;
+use metaplex_token_metadata::state::{Creator, Data, Key, Metadata, MAX_METADATA_LEN};
+pub use raffle::account::CreateRaffleParams;
+use raffle::{
+    account::{CollectionPool, GlobalPool, RafflePool, TicketReceipt},
+    constants::*,
+    error::RaffleError,
+    utils::voucher_message,
+};
+use solana_program::{program_pack::Pack, pubkey::Pubkey, sysvar};
+use solana_program_runtime::invoke_context::ProcessInstructionWithContext;
+use solana_program_test::{
+    InvokeContext, ProgramTest, ProgramTestBanksClientExt, ProgramTestContext,
+};
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    clock::Clock,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    rent::Rent,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+    transport::TransportError,
+};
+
+// the clock of every test starts here so the draws are deterministic
+pub const NOW: i64 = 1_700_000_000;
+pub const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+pub const TICKET_PRICE: u64 = LAMPORTS_PER_SOL / 10;
+
+pub use native::process_native;
+
+fn process_raffle(
+    _first_instruction_account: usize,
+    input: &[u8],
+    invoke_context: &mut InvokeContext,
+) -> Result<(), InstructionError> {
+    process_native(raffle::entry, input, invoke_context)
+}
+
+fn process_token(
+    _first_instruction_account: usize,
+    input: &[u8],
+    invoke_context: &mut InvokeContext,
+) -> Result<(), InstructionError> {
+    process_native(
+        spl_token::processor::Processor::process,
+        input,
+        invoke_context,
+    )
+}
+
+fn process_associated_token(
+    _first_instruction_account: usize,
+    input: &[u8],
+    invoke_context: &mut InvokeContext,
+) -> Result<(), InstructionError> {
+    process_native(
+        spl_associated_token_account::processor::process_instruction,
+        input,
+        invoke_context,
+    )
+}
+
+pub struct Nft {
+    pub mint: Pubkey,
+    pub token_account: Pubkey,
+    pub metadata: Pubkey,
+}
+
+// the settings of create_raffle, a paid raffle which ends in two days
+pub fn raffle_params() -> CreateRaffleParams {
+    CreateRaffleParams {
+        ticket_price_sol: TICKET_PRICE,
+        end_timestamp: NOW + 2 * DAY,
+        max_entrants: 100,
+        ..CreateRaffleParams::default()
+    }
+}
+
+pub struct RaffleTest {
+    pub context: ProgramTestContext,
+    pub admin: Keypair,
+    pub collection: Pubkey,
+    pub collection_id: Pubkey,
+}
+
+impl RaffleTest {
+    // starts the program with the admin funded, the collection pool allocated
+    // and the clock at NOW, without initializing the program
+    pub async fn start() -> RaffleTest {
+        RaffleTest::start_with(|_| {}).await
+    }
+
+    // `start` with the programs or accounts of `setup` added, like a CPI caller
+    pub async fn start_with(setup: impl FnOnce(&mut ProgramTest)) -> RaffleTest {
+        let mut program_test = ProgramTest::new(
+            "raffle",
+            raffle::id(),
+            Some(process_raffle as ProcessInstructionWithContext),
+        );
+        // the token programs run natively too, the BPF builds of solana-program-test crash
+        program_test.add_program(
+            "spl_token",
+            spl_token::id(),
+            Some(process_token as ProcessInstructionWithContext),
+        );
+        program_test.add_program(
+            "spl_associated_token_account",
+            spl_associated_token_account::id(),
+            Some(process_associated_token as ProcessInstructionWithContext),
+        );
+        setup(&mut program_test);
+        let admin = Keypair::new();
+        program_test.add_account(admin.pubkey(), system_account(100 * LAMPORTS_PER_SOL));
+
+        // the collection pool is allocated by the client, `initialize` only zero-inits it
+        let collection = Pubkey::new_unique();
+        let collection_len = 8 + size_of::<CollectionPool>();
+        program_test.add_account(
+            collection,
+            Account {
+                lamports: Rent::default().minimum_balance(collection_len),
+                data: vec![0; collection_len],
+                owner: raffle::id(),
+                ..Account::default()
+            },
+        );
+
+        let context = program_test.start_with_context().await;
+        let mut test = RaffleTest {
+            context,
+            admin,
+            collection,
+            collection_id: Pubkey::new_unique(),
+        };
+        test.set_timestamp(NOW).await;
+        test
+    }
+
+    // `start` then initialize the program and allow the test collection
+    pub async fn start_initialized() -> RaffleTest {
+        let mut test = RaffleTest::start().await;
+        test.initialize().await.unwrap();
+        let collection_id = test.collection_id;
+        test.add_collection(&collection_id).await.unwrap();
+        test
+    }
+
+    pub async fn process(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<(), TransportError> {
+        // a new blockhash so the same instruction can be sent again
+        let blockhash = self
+            .context
+            .banks_client
+            .get_new_latest_blockhash(&self.context.last_blockhash)
+            .await?;
+        self.context.last_blockhash = blockhash;
+
+        let mut all_signers = vec![&self.context.payer];
+        all_signers.extend_from_slice(signers);
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.context.payer.pubkey()),
+            &all_signers,
+            blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+    }
+
+    pub async fn set_timestamp(&mut self, timestamp: i64) {
+        let mut clock: Clock = self.context.banks_client.get_sysvar().await.unwrap();
+        clock.unix_timestamp = timestamp;
+        self.context.set_sysvar(&clock);
+    }
+
+    pub fn set_account(&mut self, address: &Pubkey, account: Account) {
+        self.context
+            .set_account(address, &AccountSharedData::from(account));
+    }
+
+    pub async fn get_account(&mut self, address: &Pubkey) -> Option<Account> {
+        self.context
+            .banks_client
+            .get_account(*address)
+            .await
+            .unwrap()
+    }
+
+    pub async fn balance(&mut self, address: &Pubkey) -> u64 {
+        self.get_account(address)
+            .await
+            .map(|account| account.lamports)
+            .unwrap_or(0)
+    }
+
+    pub async fn token_amount(&mut self, address: &Pubkey) -> u64 {
+        let account = self.get_account(address).await.unwrap();
+        spl_token::state::Account::unpack(&account.data)
+            .unwrap()
+            .amount
+    }
+
+    pub async fn mint_supply(&mut self, mint: &Pubkey) -> u64 {
+        let account = self.get_account(mint).await.unwrap();
+        spl_token::state::Mint::unpack(&account.data)
+            .unwrap()
+            .supply
+    }
+
+    pub async fn global_state(&mut self) -> GlobalPool {
+        let account = self.get_account(&global_authority().0).await.unwrap();
+        GlobalPool::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    pub async fn collection_state(&mut self) -> CollectionPool {
+        let collection = self.collection;
+        let account = self.get_account(&collection).await.unwrap();
+        let mut pool = CollectionPool::default();
+        bytemuck::bytes_of_mut(&mut pool)
+            .copy_from_slice(&account.data[8..8 + size_of::<CollectionPool>()]);
+        pool
+    }
+
+    pub async fn raffle_state(&mut self, raffle: &Pubkey) -> RafflePool {
+        let account = self.get_account(raffle).await.unwrap();
+        let mut pool = RafflePool::default();
+        bytemuck::bytes_of_mut(&mut pool)
+            .copy_from_slice(&account.data[8..8 + size_of::<RafflePool>()]);
+        pool
+    }
+
+    pub async fn receipt_state(&mut self, raffle: &Pubkey, buyer: &Pubkey) -> TicketReceipt {
+        let account = self
+            .get_account(&receipt_address(raffle, buyer))
+            .await
+            .unwrap();
+        TicketReceipt::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    pub fn new_wallet(&mut self, lamports: u64) -> Keypair {
+        let wallet = Keypair::new();
+        self.set_account(&wallet.pubkey(), system_account(lamports));
+        wallet
+    }
+
+    pub fn set_token_account(
+        &mut self,
+        address: &Pubkey,
+        mint: &Pubkey,
+        owner: &Pubkey,
+        amount: u64,
+    ) {
+        let mut data = vec![0; spl_token::state::Account::LEN];
+        spl_token::state::Account {
+            mint: *mint,
+            owner: *owner,
+            amount,
+            state: spl_token::state::AccountState::Initialized,
+            ..spl_token::state::Account::default()
+        }
+        .pack_into_slice(&mut data);
+        self.set_account(
+            address,
+            Account {
+                lamports: Rent::default().minimum_balance(data.len()),
+                data,
+                owner: spl_token::id(),
+                ..Account::default()
+            },
+        );
+    }
+
+    // mints an NFT to the owner with a mocked metaplex metadata account
+    pub fn set_mint(&mut self, mint: &Pubkey, supply: u64, decimals: u8) {
+        let mut data = vec![0; spl_token::state::Mint::LEN];
+        spl_token::state::Mint {
+            supply,
+            decimals,
+            is_initialized: true,
+            ..spl_token::state::Mint::default()
+        }
+        .pack_into_slice(&mut data);
+        self.set_account(
+            mint,
+            Account {
+                lamports: Rent::default().minimum_balance(data.len()),
+                data,
+                owner: spl_token::id(),
+                ..Account::default()
+            },
+        );
+    }
+
+    // a fungible token with the whole supply in the owner's token account
+    pub fn create_token(&mut self, owner: &Pubkey, supply: u64) -> (Pubkey, Pubkey) {
+        let mint = Pubkey::new_unique();
+        self.set_mint(&mint, supply, 6);
+        let token_account = Pubkey::new_unique();
+        self.set_token_account(&token_account, &mint, owner, supply);
+        (mint, token_account)
+    }
+
+    pub fn create_nft(&mut self, owner: &Pubkey, creators: Option<Vec<Creator>>) -> Nft {
+        let mint = Pubkey::new_unique();
+        self.set_mint(&mint, 1, 0);
+
+        let token_account = Pubkey::new_unique();
+        self.set_token_account(&token_account, &mint, owner, 1);
+
+        let metadata = metadata_address(&mint);
+        self.set_metadata(&metadata, &mint, creators);
+        Nft {
+            mint,
+            token_account,
+            metadata,
+        }
+    }
+
+    // the NFT verified by the test collection
+    pub fn create_collection_nft(&mut self, owner: &Pubkey) -> Nft {
+        let creators = vec![Creator {
+            address: self.collection_id,
+            verified: true,
+            share: 100,
+        }];
+        self.create_nft(owner, Some(creators))
+    }
+
+    pub fn set_metadata(
+        &mut self,
+        address: &Pubkey,
+        mint: &Pubkey,
+        creators: Option<Vec<Creator>>,
+    ) {
+        let metadata = Metadata {
+            key: Key::MetadataV1,
+            update_authority: Pubkey::new_unique(),
+            mint: *mint,
+            data: Data {
+                name: "Raffle NFT".to_string(),
+                symbol: "RAFFLE".to_string(),
+                uri: "https://example.com/nft.json".to_string(),
+                seller_fee_basis_points: 500,
+                creators,
+            },
+            primary_sale_happened: false,
+            is_mutable: true,
+            edition_nonce: None,
+        };
+        let mut data = metadata.try_to_vec().unwrap();
+        data.resize(MAX_METADATA_LEN, 0);
+        self.set_account(
+            address,
+            Account {
+                lamports: Rent::default().minimum_balance(data.len()),
+                data,
+                owner: metaplex_token_metadata::id(),
+                ..Account::default()
+            },
+        );
+    }
+
+    pub async fn initialize(&mut self) -> Result<(), TransportError> {
+        let global_authority = global_authority().0;
+        let instruction = Instruction {
+            program_id: raffle::id(),
+            accounts: raffle::accounts::Initialize {
+                admin: self.admin.pubkey(),
+                global_authority,
+                collection: self.collection,
+                system_program: system_program::ID,
+                rent: sysvar::rent::id(),
+            }
+            .to_account_metas(None),
+            data: raffle::instruction::Initialize {}.data(),
+        };
+        let admin = clone_keypair(&self.admin);
+        self.process(&[instruction], &[&admin]).await
+    }
+
+    pub async fn add_collection(&mut self, collection_id: &Pubkey) -> Result<(), TransportError> {
+        let instruction = Instruction {
+            program_id: raffle::id(),
+            accounts: raffle::accounts::AddCollection {
+                admin: self.admin.pubkey(),
+                global_authority: global_authority().0,
+                collection: self.collection,
+                collection_id: *collection_id,
+            }
+            .to_account_metas(None),
+            data: raffle::instruction::AddCollection {}.data(),
+        };
+        let admin = clone_keypair(&self.admin);
+        self.process(&[instruction], &[&admin]).await
+    }
+
+    pub async fn set_paused(&mut self, paused: bool) -> Result<(), TransportError> {
+        let admin = clone_keypair(&self.admin);
+        self.set_paused_by(&admin, paused).await
+    }
+
+    pub async fn set_paused_by(
+        &mut self,
+        authority: &Keypair,
+        paused: bool,
+    ) -> Result<(), TransportError> {
+        let instruction = Instruction {
+            program_id: raffle::id(),
+            accounts: raffle::accounts::SetPaused {
+                authority: authority.pubkey(),
+                global_authority: global_authority().0,
+            }
+            .to_account_metas(None),
+            data: raffle::instruction::SetPaused { paused }.data(),
+        };
+        self.process(&[instruction], &[authority]).await
+    }
+
+    pub async fn set_pauser(&mut self, pauser: &Pubkey) -> Result<(), TransportError> {
+        let instruction = Instruction {
+            program_id: raffle::id(),
+            accounts: raffle::accounts::SetGlobalConfig {
+                admin: self.admin.pubkey(),
+                global_authority: global_authority().0,
+            }
+            .to_account_metas(None),
+            data: raffle::instruction::SetPauser { pauser: *pauser }.data(),
+        };
+        let admin = clone_keypair(&self.admin);
+        self.process(&[instruction], &[&admin]).await
+    }
+
+    pub async fn set_referral_share(&mut self, referral_share: u64) -> Result<(), TransportError> {
+        let instruction = Instruction {
+            program_id: raffle::id(),
+            accounts: raffle::accounts::SetGlobalConfig {
+                admin: self.admin.pubkey(),
+                global_authority: global_authority().0,
+            }
+            .to_account_metas(None),
+            data: raffle::instruction::SetReferralShare { referral_share }.data(),
+        };
+        let admin = clone_keypair(&self.admin);
+        self.process(&[instruction], &[&admin]).await
+    }
+
+    // returns the referrer's pool which goes after the royalty recipients of a purchase
+    pub async fn init_referrer(&mut self, referrer: &Keypair) -> Result<Pubkey, TransportError> {
+        let referrer_pool = referrer_pool_address(&referrer.pubkey());
+        let instruction = Instruction {
+            program_id: raffle::id(),
+            accounts: raffle::accounts::InitReferrer {
+                referrer: referrer.pubkey(),
+                referrer_pool,
+                system_program: system_program::ID,
+                rent: sysvar::rent::id(),
+            }
+            .to_account_metas(None),
+            data: raffle::instruction::InitReferrer {}.data(),
+        };
+        self.process(&[instruction], &[referrer]).await?;
+        Ok(referrer_pool)
+    }
+
+    pub async fn set_raffle_duration(
+        &mut self,
+        min_duration: i64,
+        max_duration: i64,
+    ) -> Result<(), TransportError> {
+        let instruction = Instruction {
+            program_id: raffle::id(),
+            accounts: raffle::accounts::SetGlobalConfig {
+                admin: self.admin.pubkey(),
+                global_authority: global_authority().0,
+            }
+            .to_account_metas(None),
+            data: raffle::instruction::SetRaffleDuration {
+                min_duration,
+                max_duration,
+            }
+            .data(),
+        };
+        let admin = clone_keypair(&self.admin);
+        self.process(&[instruction], &[&admin]).await
+    }
+
+    pub async fn init_collection_duration(
+        &mut self,
+        collection_id: &Pubkey,
+        min_duration: i64,
+        max_duration: i64,
+    ) -> Result<(), TransportError> {
+        let instruction = Instruction {
+            program_id: raffle::id(),
+            accounts: raffle::accounts::InitCollectionDuration {
+                admin: self.admin.pubkey(),
+                global_authority: global_authority().0,
+                collection_id: *collection_id,
+                collection_duration: collection_duration(collection_id),
+                system_program: system_program::ID,
+                rent: sysvar::rent::ID,
+            }
+            .to_account_metas(None),
+            data: raffle::instruction::InitCollectionDuration {
+                min_duration,
+                max_duration,
+            }
+            .data(),
+        };
+        let admin = clone_keypair(&self.admin);
+        self.process(&[instruction], &[&admin]).await
+    }
+
+    pub async fn set_collection_duration(
+        &mut self,
+        admin: &Keypair,
+        collection_id: &Pubkey,
+        min_duration: i64,
+        max_duration: i64,
+    ) -> Result<(), TransportError> {
+        let instruction = Instruction {
+            program_id: raffle::id(),
+            accounts: raffle::accounts::SetCollectionDuration {
+                admin: admin.pubkey(),
+                global_authority: global_authority().0,
+                collection_duration: collection_duration(collection_id),
+            }
+            .to_account_metas(None),
+            data: raffle::instruction::SetCollectionDuration {
+                min_duration,
+                max_duration,
+            }
+            .data(),
+        };
+        self.process(&[instruction], &[admin]).await
+    }
+
+    pub async fn set_voucher_authority(
+        &mut self,
+        voucher_authority: &Pubkey,
+    ) -> Result<(), TransportError> {
+        let instruction = Instruction {
+            program_id: raffle::id(),
+            accounts: raffle::accounts::SetGlobalConfig {
+                admin: self.admin.pubkey(),
+                global_authority: global_authority().0,
+            }
+            .to_account_metas(None),
+            data: raffle::instruction::SetVoucherAuthority {
+                voucher_authority: *voucher_authority,
+            }
+            .data(),
+        };
+        let admin = clone_keypair(&self.admin);
+        self.process(&[instruction], &[&admin]).await
+    }
+
+    pub async fn set_claim_window(&mut self, claim_window: i64) -> Result<(), TransportError> {
+        let instruction = Instruction {
+            program_id: raffle::id(),
+            accounts: raffle::accounts::SetGlobalConfig {
+                admin: self.admin.pubkey(),
+                global_authority: global_authority().0,
+            }
+            .to_account_metas(None),
+            data: raffle::instruction::SetClaimWindow { claim_window }.data(),
+        };
+        let admin = clone_keypair(&self.admin);
+        self.process(&[instruction], &[&admin]).await
+    }
+
+    pub async fn set_raffle_frozen(
+        &mut self,
+        raffle: &Pubkey,
+        frozen: bool,
+    ) -> Result<(), TransportError> {
+        let instruction = Instruction {
+            program_id: raffle::id(),
+            accounts: raffle::accounts::SetRaffleFrozen {
+                admin: self.admin.pubkey(),
+                global_authority: global_authority().0,
+                raffle: *raffle,
+            }
+            .to_account_metas(None),
+            data: raffle::instruction::SetRaffleFrozen { frozen }.data(),
+        };
+        let admin = clone_keypair(&self.admin);
+        self.process(&[instruction], &[&admin]).await
+    }
+
+    // the global authority as the first release initialized it, with only the super admin
+    pub fn set_baseline_global(&mut self) {
+        let data = [
+            &GlobalPool::discriminator()[..],
+            self.admin.pubkey().as_ref(),
+        ]
+        .concat();
+        let account = Account {
+            lamports: Rent::default().minimum_balance(data.len()),
+            data,
+            owner: raffle::id(),
+            ..Account::default()
+        };
+        self.set_account(&global_authority().0, account);
+    }
+
+    pub async fn migrate_global(&mut self, admin: &Keypair) -> Result<(), TransportError> {
+        let instruction = Instruction {
+            program_id: raffle::id(),
+            accounts: raffle::accounts::MigrateGlobal {
+                admin: admin.pubkey(),
+                global_authority: global_authority().0,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: raffle::instruction::MigrateGlobal {}.data(),
+        };
+        self.process(&[instruction], &[admin]).await
+    }
+
+    // rewrites the collection pool to the layout before the version header
+    pub async fn set_legacy_layout(&mut self, address: &Pubkey) {
+        let mut account = self.get_account(address).await.unwrap();
+        let len = account.data.len();
+        account.data = [&account.data[..8], &account.data[16..]].concat();
+        account.lamports -=
+            Rent::default().minimum_balance(len) - Rent::default().minimum_balance(len - 8);
+        self.set_account(address, account);
+    }
+
+    // a raffle as the first release created it: a keypair account of 64168 bytes with its fields
+    // in front of the 2000 entrants, its NFT held by the global authority's ATA
+    // a drawn raffle has won the first entrant
+    pub fn set_baseline_raffle(
+        &mut self,
+        creator: &Pubkey,
+        nft_mint: &Pubkey,
+        entrants: &[Pubkey],
+        claimed: u64,
+    ) -> Pubkey {
+        let no_repeat = entrants
+            .iter()
+            .enumerate()
+            .filter(|(i, entrant)| !entrants[..*i].contains(entrant))
+            .count() as u64;
+        let winner = if claimed == 0 {
+            Pubkey::default()
+        } else {
+            entrants[0]
+        };
+        let mut data = RafflePool::discriminator().to_vec();
+        data.extend_from_slice(creator.as_ref());
+        data.extend_from_slice(nft_mint.as_ref());
+        for field in [
+            entrants.len() as u64,
+            no_repeat,
+            100,
+            NOW as u64,
+            (NOW + DAY) as u64,
+            TICKET_PRICE,
+            claimed,
+            0,
+        ] {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        data.extend_from_slice(winner.as_ref());
+        for entrant in entrants {
+            data.extend_from_slice(entrant.as_ref());
+        }
+        data.resize(64168, 0);
+
+        let raffle = Pubkey::new_unique();
+        self.set_account(
+            &raffle,
+            Account {
+                lamports: Rent::default().minimum_balance(data.len()),
+                data,
+                owner: raffle::id(),
+                ..Account::default()
+            },
+        );
+        let held = if claimed == 0 || claimed == 2 { 1 } else { 0 };
+        self.set_token_account(
+            &legacy_nft_token_account(nft_mint),
+            nft_mint,
+            &global_authority().0,
+            held,
+        );
+        self.set_token_account(
+            &escrow_token_account(&raffle, nft_mint),
+            nft_mint,
+            &escrow_authority(&raffle),
+            0,
+        );
+        raffle
+    }
+
+    pub async fn migrate_raffle(
+        &mut self,
+        payer: &Keypair,
+        raffle: &Pubkey,
+        nft_mint: &Pubkey,
+    ) -> Result<(), TransportError> {
+        let instruction = Instruction {
+            program_id: raffle::id(),
+            accounts: raffle::accounts::MigrateRaffle {
+                payer: payer.pubkey(),
+                global_authority: global_authority().0,
+                raffle: *raffle,
+                escrow_authority: escrow_authority(raffle),
+                src_nft_token_account: legacy_nft_token_account(nft_mint),
+                dest_nft_token_account: escrow_token_account(raffle, nft_mint),
+                token_program: spl_token::id(),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: raffle::instruction::MigrateRaffle {}.data(),
+        };
+        self.process(&[instruction], &[payer]).await
+    }
+
+    pub async fn migrate_collection(&mut self, admin: &Keypair) -> Result<(), TransportError> {
+        let instruction = Instruction {
+            program_id: raffle::id(),
+            accounts: raffle::accounts::MigrateCollection {
+                admin: admin.pubkey(),
+                global_authority: global_authority().0,
+                collection: self.collection,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: raffle::instruction::MigrateCollection {}.data(),
+        };
+        self.process(&[instruction], &[admin]).await
+    }
+
+    // returns the creator's token account which receives the NFT back
+    pub async fn force_cancel_raffle(&mut self, raffle: &Pubkey) -> Result<Pubkey, TransportError> {
+        let state = self.raffle_state(raffle).await;
+        let creator_nft_token_account = Pubkey::new_unique();
+        self.set_token_account(
+            &creator_nft_token_account,
+            &state.nft_mint,
+            &state.creator,
+            0,
+        );
+        let escrow_authority = escrow_authority(raffle);
+        let instruction = Instruction {
+            program_id: raffle::id(),
+            accounts: raffle::accounts::ForceCancelRaffle {
+                admin: self.admin.pubkey(),
+                global_authority: global_authority().0,
+                raffle: *raffle,
+                escrow_authority,
+                creator_nft_token_account,
+                src_nft_token_account: escrow_token_account(raffle, &state.nft_mint),
+                token_program: spl_token::id(),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: raffle::instruction::ForceCancelRaffle {}.data(),
+        };
+        let admin = clone_keypair(&self.admin);
+        self.process(&[instruction], &[&admin]).await?;
+        Ok(creator_nft_token_account)
+    }
+
+    pub async fn claim_refund(
+        &mut self,
+        buyer: &Keypair,
+        raffle: &Pubkey,
+    ) -> Result<(), TransportError> {
+        let instruction = Instruction {
+            program_id: raffle::id(),
+            accounts: raffle::accounts::ClaimRefund {
+                buyer: buyer.pubkey(),
+                raffle: *raffle,
+                receipt: receipt_address(raffle, &buyer.pubkey()),
+            }
+            .to_account_metas(None),
+            data: raffle::instruction::ClaimRefund {}.data(),
+        };
+        self.process(&[instruction], &[buyer]).await
+    }
+
+    pub fn allocate_raffle_ix(
+        &self,
+        creator: &Pubkey,
+        nft_mint: &Pubkey,
+        nonce: u64,
+    ) -> Instruction {
+        Instruction {
+            program_id: raffle::id(),
+            accounts: raffle::accounts::AllocateRaffle {
+                payer: self.context.payer.pubkey(),
+                creator: *creator,
+                nft_mint_address: *nft_mint,
+                raffle: raffle_address(creator, nft_mint, nonce),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: raffle::instruction::AllocateRaffle { nonce }.data(),
+        }
+    }
+
+    // allocates the whole raffle PDA, one step per instruction
+    pub async fn allocate_raffle(
+        &mut self,
+        creator: &Pubkey,
+        nft_mint: &Pubkey,
+        nonce: u64,
+    ) -> Result<(), TransportError> {
+        let instruction = self.allocate_raffle_ix(creator, nft_mint, nonce);
+        let instructions = vec![instruction; RafflePool::ALLOCATE_STEPS];
+        self.process(&instructions, &[]).await
+    }
+
+    pub fn create_raffle_ix(
+        &mut self,
+        creator: &Pubkey,
+        nft: &Nft,
+        params: CreateRaffleParams,
+    ) -> Instruction {
+        let global_authority = global_authority().0;
+        let raffle = raffle_address(creator, &nft.mint, params.nonce);
+        let escrow_authority = escrow_authority(&raffle);
+
+        // the escrow's NFT account is created ahead like the client does
+        let dest_nft_token_account = escrow_token_account(&raffle, &nft.mint);
+        self.set_token_account(&dest_nft_token_account, &nft.mint, &escrow_authority, 0);
+
+        Instruction {
+            program_id: raffle::id(),
+            accounts: raffle::accounts::CreateRaffle {
+                admin: *creator,
+                global_authority,
+                raffle,
+                escrow_authority,
+                collection: self.collection,
+                collection_duration: collection_duration(&self.collection_id),
+                owner_temp_nft_account: nft.token_account,
+                dest_nft_token_account,
+                nft_mint_address: nft.mint,
+                mint_metadata: nft.metadata,
+                token_program: spl_token::id(),
+                system_program: system_program::ID,
+                rent: sysvar::rent::id(),
+                token_metadata_program: metaplex_token_metadata::id(),
+            }
+            .to_account_metas(None),
+            data: raffle::instruction::CreateRaffle::from(params).data(),
+        }
+    }
+
+    pub async fn create_raffle(
+        &mut self,
+        creator: &Keypair,
+        nft: &Nft,
+        params: CreateRaffleParams,
+    ) -> Result<Pubkey, TransportError> {
+        let raffle = raffle_address(&creator.pubkey(), &nft.mint, params.nonce);
+        self.allocate_raffle(&creator.pubkey(), &nft.mint, params.nonce)
+            .await?;
+        let instruction = self.create_raffle_ix(&creator.pubkey(), nft, params);
+        self.process(&[instruction], &[creator]).await?;
+        Ok(raffle)
+    }
+
+    // a creator with a collection NFT and a running raffle of it
+    pub async fn create_default_raffle(
+        &mut self,
+        params: CreateRaffleParams,
+    ) -> (Keypair, Nft, Pubkey) {
+        let creator = self.new_wallet(10 * LAMPORTS_PER_SOL);
+        let nft = self.create_collection_nft(&creator.pubkey());
+        let raffle = self.create_raffle(&creator, &nft, params).await.unwrap();
+        (creator, nft, raffle)
+    }
+
+    // remaining accounts are the royalty recipients then the optional referrer and its pool
+    pub fn buy_tickets_ix(
+        &self,
+        buyer: &Pubkey,
+        raffle: &Pubkey,
+        creator: &Pubkey,
+        amount: u64,
+        remaining_accounts: &[Pubkey],
+    ) -> Instruction {
+        let global_authority = global_authority().0;
+        let mut accounts = raffle::accounts::BuyTickets {
+            buyer: *buyer,
+            raffle: *raffle,
+            global_authority,
+            creator: *creator,
+            treasury_wallet: treasury_wallet(),
+            receipt: receipt_address(raffle, buyer),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None);
+        accounts.extend(
+            remaining_accounts
+                .iter()
+                .map(|account| AccountMeta::new(*account, false)),
+        );
+        Instruction {
+            program_id: raffle::id(),
+            accounts,
+            data: raffle::instruction::BuyTickets { amount }.data(),
+        }
+    }
+
+    pub async fn buy_tickets(
+        &mut self,
+        buyer: &Keypair,
+        raffle: &Pubkey,
+        amount: u64,
+    ) -> Result<(), TransportError> {
+        let creator = self.raffle_state(raffle).await.creator;
+        let instruction = self.buy_tickets_ix(&buyer.pubkey(), raffle, &creator, amount, &[]);
+        self.process(&[instruction], &[buyer]).await
+    }
+
+    pub fn claim_free_ticket_ix(
+        &self,
+        claimer: &Pubkey,
+        raffle: &Pubkey,
+        voucher_id: u64,
+        amount: u64,
+    ) -> Instruction {
+        Instruction {
+            program_id: raffle::id(),
+            accounts: raffle::accounts::ClaimFreeTicket {
+                claimer: *claimer,
+                global_authority: global_authority().0,
+                raffle: *raffle,
+                voucher: voucher_address(voucher_id),
+                instructions_sysvar: sysvar::instructions::id(),
+                system_program: system_program::ID,
+                rent: sysvar::rent::id(),
+            }
+            .to_account_metas(None),
+            data: raffle::instruction::ClaimFreeTicket { voucher_id, amount }.data(),
+        }
+    }
+
+    // claim the tickets of a voucher signed by `signer` in the ed25519 instruction before it
+    pub async fn claim_free_ticket(
+        &mut self,
+        claimer: &Keypair,
+        raffle: &Pubkey,
+        voucher_id: u64,
+        amount: u64,
+        signer: &Keypair,
+    ) -> Result<(), TransportError> {
+        let message = voucher_message(raffle, &claimer.pubkey(), amount, voucher_id);
+        let instruction = self.claim_free_ticket_ix(&claimer.pubkey(), raffle, voucher_id, amount);
+        self.process(
+            &[ed25519_instruction(signer, &message), instruction],
+            &[claimer],
+        )
+        .await
+    }
+
+    pub async fn burn_tokens_for_tickets(
+        &mut self,
+        buyer: &Keypair,
+        raffle: &Pubkey,
+        burn_mint: &Pubkey,
+        buyer_token_account: &Pubkey,
+        amount: u64,
+    ) -> Result<(), TransportError> {
+        let instruction = Instruction {
+            program_id: raffle::id(),
+            accounts: raffle::accounts::BurnTokensForTickets {
+                buyer: buyer.pubkey(),
+                raffle: *raffle,
+                global_authority: global_authority().0,
+                burn_mint: *burn_mint,
+                buyer_token_account: *buyer_token_account,
+                token_program: spl_token::id(),
+            }
+            .to_account_metas(None),
+            data: raffle::instruction::BurnTokensForTickets { amount }.data(),
+        };
+        self.process(&[instruction], &[buyer]).await
+    }
+
+    pub async fn burn_nft_for_ticket(
+        &mut self,
+        buyer: &Keypair,
+        raffle: &Pubkey,
+        nft: &Nft,
+    ) -> Result<(), TransportError> {
+        let instruction = Instruction {
+            program_id: raffle::id(),
+            accounts: raffle::accounts::BurnNftForTicket {
+                buyer: buyer.pubkey(),
+                raffle: *raffle,
+                global_authority: global_authority().0,
+                burn_mint: nft.mint,
+                buyer_token_account: nft.token_account,
+                mint_metadata: nft.metadata,
+                token_program: spl_token::id(),
+            }
+            .to_account_metas(None),
+            data: raffle::instruction::BurnNftForTicket {}.data(),
+        };
+        self.process(&[instruction], &[buyer]).await
+    }
+
+    pub async fn reveal_winner(&mut self, raffle: &Pubkey) -> Result<(), TransportError> {
+        let instruction = Instruction {
+            program_id: raffle::id(),
+            accounts: raffle::accounts::RevealWinner {
+                buyer: self.context.payer.pubkey(),
+                raffle: *raffle,
+                global_authority: global_authority().0,
+            }
+            .to_account_metas(None),
+            data: raffle::instruction::RevealWinner {}.data(),
+        };
+        self.process(&[instruction], &[]).await
+    }
+
+    // returns the winner's NFT ATA which receives the prize
+    pub async fn settle_raffle(
+        &mut self,
+        cranker: &Keypair,
+        raffle: &Pubkey,
+        winner: &Pubkey,
+    ) -> Result<Pubkey, TransportError> {
+        let state = self.raffle_state(raffle).await;
+        let winner_nft_token_account = get_associated_token_address(winner, &state.nft_mint);
+        let instruction = Instruction {
+            program_id: raffle::id(),
+            accounts: raffle::accounts::SettleRaffle {
+                cranker: cranker.pubkey(),
+                raffle: *raffle,
+                escrow_authority: escrow_authority(raffle),
+                winner: *winner,
+                winner_nft_token_account,
+                src_nft_token_account: escrow_token_account(raffle, &state.nft_mint),
+                nft_mint_address: state.nft_mint,
+                token_program: spl_token::id(),
+                associated_token_program: anchor_spl::associated_token::ID,
+                system_program: system_program::ID,
+                rent: sysvar::rent::id(),
+            }
+            .to_account_metas(None),
+            data: raffle::instruction::SettleRaffle {}.data(),
+        };
+        self.process(&[instruction], &[cranker]).await?;
+        Ok(winner_nft_token_account)
+    }
+
+    // returns the creator's token account which receives an unclaimed NFT back
+    pub async fn reroll_winner(
+        &mut self,
+        caller: &Keypair,
+        raffle: &Pubkey,
+    ) -> Result<Pubkey, TransportError> {
+        let state = self.raffle_state(raffle).await;
+        let creator_nft_token_account = Pubkey::new_unique();
+        self.set_token_account(
+            &creator_nft_token_account,
+            &state.nft_mint,
+            &state.creator,
+            0,
+        );
+        let instruction = Instruction {
+            program_id: raffle::id(),
+            accounts: raffle::accounts::RerollWinner {
+                caller: caller.pubkey(),
+                global_authority: global_authority().0,
+                raffle: *raffle,
+                escrow_authority: escrow_authority(raffle),
+                creator_nft_token_account,
+                src_nft_token_account: escrow_token_account(raffle, &state.nft_mint),
+                token_program: spl_token::id(),
+            }
+            .to_account_metas(None),
+            data: raffle::instruction::RerollWinner {}.data(),
+        };
+        self.process(&[instruction], &[caller]).await?;
+        Ok(creator_nft_token_account)
+    }
+
+    // returns the claimer's NFT account which receives the prize
+    pub async fn claim_reward(
+        &mut self,
+        claimer: &Keypair,
+        raffle: &Pubkey,
+    ) -> Result<Pubkey, TransportError> {
+        let state = self.raffle_state(raffle).await;
+        let claimer_nft_token_account = Pubkey::new_unique();
+        self.set_token_account(
+            &claimer_nft_token_account,
+            &state.nft_mint,
+            &claimer.pubkey(),
+            0,
+        );
+        let instruction = Instruction {
+            program_id: raffle::id(),
+            accounts: raffle::accounts::ClaimReward {
+                claimer: claimer.pubkey(),
+                raffle: *raffle,
+                escrow_authority: escrow_authority(raffle),
+                claimer_nft_token_account,
+                src_nft_token_account: escrow_token_account(raffle, &state.nft_mint),
+                nft_mint_address: state.nft_mint,
+                token_program: spl_token::id(),
+            }
+            .to_account_metas(None),
+            data: raffle::instruction::ClaimReward {}.data(),
+        };
+        self.process(&[instruction], &[claimer]).await?;
+        Ok(claimer_nft_token_account)
+    }
+
+    // returns the claimer's NFT account which receives the prize
+    pub async fn withdraw_nft(
+        &mut self,
+        claimer: &Keypair,
+        raffle: &Pubkey,
+    ) -> Result<Pubkey, TransportError> {
+        let state = self.raffle_state(raffle).await;
+        let claimer_nft_token_account = Pubkey::new_unique();
+        self.set_token_account(
+            &claimer_nft_token_account,
+            &state.nft_mint,
+            &claimer.pubkey(),
+            0,
+        );
+        let instruction = Instruction {
+            program_id: raffle::id(),
+            accounts: raffle::accounts::WithdrawNft {
+                claimer: claimer.pubkey(),
+                raffle: *raffle,
+                escrow_authority: escrow_authority(raffle),
+                claimer_nft_token_account,
+                src_nft_token_account: escrow_token_account(raffle, &state.nft_mint),
+                nft_mint_address: state.nft_mint,
+                token_program: spl_token::id(),
+            }
+            .to_account_metas(None),
+            data: raffle::instruction::WithdrawNft {}.data(),
+        };
+        self.process(&[instruction], &[claimer]).await?;
+        Ok(claimer_nft_token_account)
+    }
+}
+
+pub fn system_account(lamports: u64) -> Account {
+    Account::new(lamports, 0, &system_program::ID)
+}
+
+pub fn clone_keypair(keypair: &Keypair) -> Keypair {
+    Keypair::from_bytes(&keypair.to_bytes()).unwrap()
+}
+
+pub fn global_authority() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[GLOBAL_AUTHORITY_SEED.as_bytes()], &raffle::id())
+}
+
+pub fn raffle_address(creator: &Pubkey, nft_mint: &Pubkey, nonce: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            RAFFLE_SEED.as_bytes(),
+            creator.as_ref(),
+            nft_mint.as_ref(),
+            &nonce.to_le_bytes(),
+        ],
+        &raffle::id(),
+    )
+    .0
+}
+
+pub fn receipt_address(raffle: &Pubkey, buyer: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[RECEIPT_SEED.as_bytes(), raffle.as_ref(), buyer.as_ref()],
+        &raffle::id(),
+    )
+    .0
+}
+
+pub fn referrer_pool_address(referrer: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[REFERRER_SEED.as_bytes(), referrer.as_ref()],
+        &raffle::id(),
+    )
+    .0
+}
+
+pub fn voucher_address(voucher_id: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[VOUCHER_SEED.as_bytes(), &voucher_id.to_le_bytes()],
+        &raffle::id(),
+    )
+    .0
+}
+
+// the ed25519 program instruction verifying one signature of `message`, with the
+// public key, signature and message in its own data like the voucher service sends it
+pub fn ed25519_instruction(signer: &Keypair, message: &[u8]) -> Instruction {
+    const PUBLIC_KEY_OFFSET: u16 = 16;
+    const SIGNATURE_OFFSET: u16 = PUBLIC_KEY_OFFSET + 32;
+    const MESSAGE_OFFSET: u16 = SIGNATURE_OFFSET + 64;
+    let signature = signer.sign_message(message);
+
+    let mut data = vec![1, 0];
+    for offset in [
+        SIGNATURE_OFFSET,
+        u16::MAX,
+        PUBLIC_KEY_OFFSET,
+        u16::MAX,
+        MESSAGE_OFFSET,
+        message.len() as u16,
+        u16::MAX,
+    ] {
+        data.extend_from_slice(&offset.to_le_bytes());
+    }
+    data.extend_from_slice(signer.pubkey().as_ref());
+    data.extend_from_slice(signature.as_ref());
+    data.extend_from_slice(message);
+    Instruction {
+        program_id: solana_sdk::ed25519_program::id(),
+        accounts: vec![],
+        data,
+    }
+}
+
+pub fn escrow_authority(raffle: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[ESCROW_AUTHORITY_SEED.as_bytes(), raffle.as_ref()],
+        &raffle::id(),
+    )
+    .0
+}
+
+// the escrow's NFT ATA like the client creates it
+pub fn escrow_token_account(raffle: &Pubkey, nft_mint: &Pubkey) -> Pubkey {
+    get_associated_token_address(&escrow_authority(raffle), nft_mint)
+}
+
+// the first release kept the NFTs in ATAs of the global authority
+pub fn legacy_nft_token_account(nft_mint: &Pubkey) -> Pubkey {
+    get_associated_token_address(&global_authority().0, nft_mint)
+}
+
+pub fn collection_duration(collection_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[DURATION_SEED.as_bytes(), collection_id.as_ref()],
+        &raffle::id(),
+    )
+    .0
+}
+
+pub fn metadata_address(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            metaplex_token_metadata::state::PREFIX.as_bytes(),
+            metaplex_token_metadata::id().as_ref(),
+            mint.as_ref(),
+        ],
+        &metaplex_token_metadata::id(),
+    )
+    .0
+}
+
+pub fn treasury_wallet() -> Pubkey {
+    TREASURY_WALLET.parse().unwrap()
+}
+
+pub fn assert_raffle_error(result: Result<(), TransportError>, error: RaffleError) {
+    match result {
+        Err(TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        ))) => assert_eq!(code, u32::from(error), "expected {:?}", error),
+        other => panic!("expected {:?}, got {:?}", error, other),
+    }
+}
+
+pub fn assert_anchor_error(result: Result<(), TransportError>, error: ErrorCode) {
+    match result {
+        Err(TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        ))) => assert_eq!(code, u32::from(error), "expected {:?}", error),
+        other => panic!("expected {:?}, got {:?}", error, other),
+    }
+}
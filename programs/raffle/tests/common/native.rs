@@ -0,0 +1,268 @@
+//! Runs the programs of the tests natively like the BPF loader would.
+//!
+//! solana-program-test 1.9 hands builtin programs account data which can't grow, and
+//! wants the invoked program among the account infos of a CPI. Anchor 0.24 creates and
+//! reallocs accounts and leaves the invoked program out, so the programs of the tests
+//! run through `process_native` and `NativeStubs` instead.
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc, sync::Once};
+
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::{ProcessInstruction, MAX_PERMITTED_DATA_INCREASE, SUCCESS},
+    program_error::{ProgramError, UNSUPPORTED_SYSVAR},
+    program_stubs::{set_syscall_stubs, SyscallStubs},
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+use solana_program_runtime::{ic_msg, stable_log, timings::ExecuteTimings};
+use solana_program_test::InvokeContext;
+use solana_sdk::{
+    account::{ReadableAccount, WritableAccount},
+    instruction::{Instruction, InstructionError},
+    message::{Message, SanitizedMessage},
+};
+
+thread_local! {
+    static INVOKE_CONTEXT: RefCell<Option<usize>> = const { RefCell::new(None) };
+}
+
+fn set_invoke_context(invoke_context: &mut InvokeContext) {
+    INVOKE_CONTEXT.with(|cell| cell.replace(Some(invoke_context as *mut InvokeContext as usize)));
+}
+
+fn invoke_context<'a, 'b>() -> &'a mut InvokeContext<'b> {
+    let address = INVOKE_CONTEXT.with(|cell| cell.borrow().expect("no program is running"));
+    unsafe { &mut *(address as *mut InvokeContext) }
+}
+
+// the data of an account behind its length and followed by the room to grow, as the
+// BPF loader serializes it for `AccountInfo::realloc`. The buffers of a test are leaked.
+fn account_data(data: &[u8]) -> &'static mut [u8] {
+    let mut buffer = vec![0; 8 + data.len() + MAX_PERMITTED_DATA_INCREASE];
+    buffer[..8].copy_from_slice(&(data.len() as u64).to_le_bytes());
+    buffer[8..8 + data.len()].copy_from_slice(data);
+    &mut Box::leak(buffer.into_boxed_slice())[8..8 + data.len()]
+}
+
+/// Runs `process_instruction` with the accounts of the running instruction, for the
+/// programs added with `ProgramTest::add_program`.
+pub fn process_native(
+    process_instruction: ProcessInstruction,
+    input: &[u8],
+    invoke_context: &mut InvokeContext,
+) -> Result<(), InstructionError> {
+    set_invoke_context(invoke_context);
+    install_native_stubs();
+
+    let log_collector = invoke_context.get_log_collector();
+    let program_id = *invoke_context.get_caller()?;
+    stable_log::program_invoke(
+        &log_collector,
+        &program_id,
+        invoke_context.get_stack_height(),
+    );
+
+    // skip the program account, an account listed twice shares its lamports and data
+    let keyed_accounts = &invoke_context.get_keyed_accounts()?[1..];
+    let mut shared = HashMap::new();
+    let account_infos: Vec<AccountInfo<'static>> = keyed_accounts
+        .iter()
+        .map(|keyed_account| {
+            let key = *keyed_account.unsigned_key();
+            let account = keyed_account.account.borrow();
+            let (lamports, data, owner) = shared
+                .entry(key)
+                .or_insert_with(|| {
+                    (
+                        Rc::new(RefCell::new(Box::leak(Box::new(account.lamports())))),
+                        Rc::new(RefCell::new(account_data(account.data()))),
+                        &*Box::leak(Box::new(*account.owner())),
+                    )
+                })
+                .clone();
+            AccountInfo {
+                key: Box::leak(Box::new(key)),
+                is_signer: keyed_account.signer_key().is_some(),
+                is_writable: keyed_account.is_writable(),
+                lamports,
+                data,
+                owner,
+                executable: account.executable(),
+                rent_epoch: account.rent_epoch(),
+            }
+        })
+        .collect();
+
+    process_instruction(&program_id, &account_infos, input).map_err(|err| {
+        let err = u64::from(err);
+        stable_log::program_failure(&log_collector, &program_id, &err.into());
+        err
+    })?;
+    stable_log::program_success(&log_collector, &program_id);
+
+    for keyed_account in keyed_accounts {
+        let (lamports, data, _) = &shared[keyed_account.unsigned_key()];
+        let mut account = keyed_account.account.borrow_mut();
+        account.set_lamports(**lamports.borrow());
+        account.set_data(data.borrow().to_vec());
+    }
+    Ok(())
+}
+
+fn get_sysvar<T: Sysvar + Clone>(
+    sysvar: Result<std::sync::Arc<T>, InstructionError>,
+    var_addr: *mut u8,
+) -> u64 {
+    match sysvar {
+        Ok(sysvar) => {
+            unsafe { *(var_addr as *mut T) = T::clone(&sysvar) };
+            SUCCESS
+        }
+        Err(_) => UNSUPPORTED_SYSVAR,
+    }
+}
+
+struct NativeStubs;
+
+impl SyscallStubs for NativeStubs {
+    fn sol_log(&self, message: &str) {
+        ic_msg!(invoke_context(), "Program log: {}", message);
+    }
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> Result<(), ProgramError> {
+        let invoke_context = invoke_context();
+        let log_collector = invoke_context.get_log_collector();
+        let caller = *invoke_context.get_caller().expect("get_caller");
+        let message = Message::new(std::slice::from_ref(instruction), None);
+        let program_id = instruction.program_id;
+        let caller_privileges: Vec<bool> = (0..message.account_keys.len())
+            .map(|index| message.is_writable(index))
+            .collect();
+
+        stable_log::program_invoke(
+            &log_collector,
+            &program_id,
+            invoke_context.get_stack_height(),
+        );
+
+        // the caller's account infos hold the latest state of the accounts
+        let mut account_indices = Vec::with_capacity(message.account_keys.len());
+        let mut accounts = Vec::with_capacity(message.account_keys.len());
+        for (index, key) in message.account_keys.iter().enumerate() {
+            let (account_index, account) = invoke_context
+                .get_account(key)
+                .ok_or(ProgramError::NotEnoughAccountKeys)?;
+            let account_info = account_infos.iter().find(|info| info.key == key);
+            match account_info {
+                Some(account_info) => {
+                    let mut account = account.borrow_mut();
+                    account.copy_into_owner_from_slice(account_info.owner.as_ref());
+                    account.set_data_from_slice(&account_info.try_borrow_data()?);
+                    account.set_lamports(account_info.lamports());
+                    account.set_executable(account_info.executable);
+                    account.set_rent_epoch(account_info.rent_epoch);
+                }
+                // anchor doesn't pass the invoked program
+                None if *key == program_id => {}
+                None => return Err(ProgramError::NotEnoughAccountKeys),
+            }
+            account_indices.push(account_index);
+            accounts.push((account, account_info.filter(|_| message.is_writable(index))));
+        }
+        let program_indices = vec![invoke_context.get_account(&program_id).unwrap().0];
+
+        for instruction_account in &instruction.accounts {
+            let signed = account_infos
+                .iter()
+                .any(|info| info.key == &instruction_account.pubkey && info.is_signer);
+            if instruction_account.is_signer && !signed {
+                let program_signer = signers_seeds.iter().any(|seeds| {
+                    Pubkey::create_program_address(seeds, &caller).ok()
+                        == Some(instruction_account.pubkey)
+                });
+                if !program_signer {
+                    return Err(ProgramError::MissingRequiredSignature);
+                }
+            }
+        }
+
+        invoke_context.record_instruction(invoke_context.get_stack_height(), instruction.clone());
+        let message = SanitizedMessage::Legacy(message);
+        invoke_context
+            .process_instruction(
+                &message,
+                &message.instructions()[0],
+                &program_indices,
+                &account_indices,
+                &caller_privileges,
+                &mut ExecuteTimings::default(),
+            )
+            .result
+            .map_err(|err| ProgramError::try_from(err).unwrap_or_else(|err| panic!("{}", err)))?;
+
+        // the invoked program may have created, resized or assigned the accounts
+        for (account, account_info) in &accounts {
+            if let Some(account_info) = account_info {
+                let account = account.borrow();
+                **account_info.try_borrow_mut_lamports()? = account.lamports();
+                let mut data = account_info.try_borrow_mut_data()?;
+                if data.len() == account.data().len() {
+                    data.copy_from_slice(account.data());
+                } else {
+                    *data = account_data(account.data());
+                }
+                if account_info.owner != account.owner() {
+                    account_info.assign(account.owner());
+                }
+            }
+        }
+
+        stable_log::program_success(&log_collector, &program_id);
+        Ok(())
+    }
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        get_sysvar(invoke_context().get_sysvar_cache().get_clock(), var_addr)
+    }
+
+    fn sol_get_epoch_schedule_sysvar(&self, var_addr: *mut u8) -> u64 {
+        get_sysvar(
+            invoke_context().get_sysvar_cache().get_epoch_schedule(),
+            var_addr,
+        )
+    }
+
+    #[allow(deprecated)]
+    fn sol_get_fees_sysvar(&self, var_addr: *mut u8) -> u64 {
+        get_sysvar(invoke_context().get_sysvar_cache().get_fees(), var_addr)
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        get_sysvar(invoke_context().get_sysvar_cache().get_rent(), var_addr)
+    }
+
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        let (program_id, data) = &invoke_context().return_data;
+        Some((*program_id, data.to_vec()))
+    }
+
+    fn sol_set_return_data(&self, data: &[u8]) {
+        let invoke_context = invoke_context();
+        let caller = *invoke_context.get_caller().unwrap();
+        invoke_context.return_data = (caller, data.to_vec());
+    }
+}
+
+// replaces the stubs solana-program-test installs when the first bank starts
+fn install_native_stubs() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        set_syscall_stubs(Box::new(NativeStubs));
+    });
+}
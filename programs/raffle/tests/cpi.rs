@@ -0,0 +1,221 @@
+mod common;
+
+use anchor_lang::prelude::AccountInfo;
+use common::*;
+use raffle::{account::RafflePool, interface};
+use solana_program::{
+    account_info::next_account_info, entrypoint::ProgramResult, program_error::ProgramError,
+    pubkey::Pubkey, sysvar,
+};
+use solana_program_runtime::invoke_context::ProcessInstructionWithContext;
+use solana_program_test::InvokeContext;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction, InstructionError},
+    system_program,
+};
+
+// the calling program owns two PDAs, one creates the raffle and the other buys tickets
+const CREATOR_SEED: &[u8] = b"creator";
+const BUYER_SEED: &[u8] = b"buyer";
+
+// the caller's instruction data is a tag followed by the nonce or the amount
+const ALLOCATE_RAFFLE: u8 = 0;
+const CREATE_RAFFLE: u8 = 1;
+const BUY_TICKETS: u8 = 2;
+
+fn caller_id() -> Pubkey {
+    Pubkey::new_from_array([7; 32])
+}
+
+fn caller_pda(seed: &[u8]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[seed], &caller_id())
+}
+
+// a program which calls the raffle through `raffle::interface`, signing for its PDAs
+fn process_caller(
+    _first_instruction_account: usize,
+    input: &[u8],
+    invoke_context: &mut InvokeContext,
+) -> Result<(), InstructionError> {
+    process_native(call_raffle, input, invoke_context)
+}
+
+fn call_raffle(_program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let accounts = &mut accounts.iter();
+    let raffle_program = next_account_info(accounts)?.clone();
+    let mut next = || next_account_info(accounts).cloned();
+    let (tag, argument) = data
+        .split_first()
+        .ok_or(ProgramError::InvalidInstructionData)?;
+    let argument = u64::from_le_bytes(
+        argument
+            .try_into()
+            .map_err(|_| ProgramError::InvalidInstructionData)?,
+    );
+    match *tag {
+        ALLOCATE_RAFFLE => {
+            let nonce = argument;
+            let bump = caller_pda(CREATOR_SEED).1;
+            let creator = next()?;
+            let allocate_accounts = interface::AllocateRaffleAccounts {
+                raffle_program,
+                payer: creator.clone(),
+                creator,
+                nft_mint: next()?,
+                raffle: next()?,
+                system_program: next()?,
+            };
+            interface::allocate_raffle(allocate_accounts, nonce, &[&[CREATOR_SEED, &[bump]]])?;
+        }
+        CREATE_RAFFLE => {
+            let nonce = argument;
+            let bump = caller_pda(CREATOR_SEED).1;
+            let create_accounts = interface::CreateRaffleAccounts {
+                raffle_program,
+                creator: next()?,
+                global_authority: next()?,
+                raffle: next()?,
+                escrow_authority: next()?,
+                escrow_nft_token_account: next()?,
+                collection: next()?,
+                collection_duration: next()?,
+                creator_nft_token_account: next()?,
+                nft_mint: next()?,
+                mint_metadata: next()?,
+                token_program: next()?,
+                associated_token_program: next()?,
+                system_program: next()?,
+                rent: next()?,
+                token_metadata_program: next()?,
+            };
+            let params = CreateRaffleParams {
+                nonce,
+                ..raffle_params()
+            };
+            interface::create_raffle(create_accounts, params, &[&[CREATOR_SEED, &[bump]]])?;
+        }
+        BUY_TICKETS => {
+            let amount = argument;
+            let bump = caller_pda(BUYER_SEED).1;
+            let buy_accounts = interface::BuyTicketsAccounts {
+                raffle_program,
+                buyer: next()?,
+                raffle: next()?,
+                global_authority: next()?,
+                creator: next()?,
+                treasury_wallet: next()?,
+                receipt: next()?,
+                system_program: next()?,
+            };
+            interface::buy_tickets(buy_accounts, vec![], amount, &[&[BUYER_SEED, &[bump]]])?;
+        }
+        _ => return Err(ProgramError::InvalidInstructionData),
+    }
+    Ok(())
+}
+
+fn caller_instruction(accounts: Vec<AccountMeta>, tag: u8, argument: u64) -> Instruction {
+    let mut metas = vec![AccountMeta::new_readonly(raffle::id(), false)];
+    metas.extend(accounts);
+    Instruction {
+        program_id: caller_id(),
+        accounts: metas,
+        data: [&[tag][..], &argument.to_le_bytes()].concat(),
+    }
+}
+
+#[tokio::test]
+async fn program_derived_accounts_create_raffles_and_buy_tickets() {
+    let mut test = RaffleTest::start_with(|program_test| {
+        program_test.add_program(
+            "raffle_caller",
+            caller_id(),
+            Some(process_caller as ProcessInstructionWithContext),
+        );
+    })
+    .await;
+    test.initialize().await.unwrap();
+    let collection_id = test.collection_id;
+    test.add_collection(&collection_id).await.unwrap();
+
+    let creator = caller_pda(CREATOR_SEED).0;
+    let buyer = caller_pda(BUYER_SEED).0;
+    test.set_account(&creator, system_account(10 * LAMPORTS_PER_SOL));
+    test.set_account(&buyer, system_account(10 * LAMPORTS_PER_SOL));
+    let nft = test.create_collection_nft(&creator);
+    let nonce = 1;
+    let raffle = raffle_address(&creator, &nft.mint, nonce);
+
+    // the PDAs can't sign the transaction, the caller signs for them
+    let allocate = caller_instruction(
+        vec![
+            AccountMeta::new(creator, false),
+            AccountMeta::new_readonly(nft.mint, false),
+            AccountMeta::new(raffle, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        ALLOCATE_RAFFLE,
+        nonce,
+    );
+    test.process(&vec![allocate; RafflePool::ALLOCATE_STEPS], &[])
+        .await
+        .unwrap();
+
+    // the escrow's ATA doesn't exist yet, the interface creates it
+    let escrow_nft_token_account = escrow_token_account(&raffle, &nft.mint);
+    assert!(test.get_account(&escrow_nft_token_account).await.is_none());
+    let create = caller_instruction(
+        vec![
+            AccountMeta::new(creator, false),
+            AccountMeta::new(global_authority().0, false),
+            AccountMeta::new(raffle, false),
+            AccountMeta::new_readonly(escrow_authority(&raffle), false),
+            AccountMeta::new(escrow_nft_token_account, false),
+            AccountMeta::new(test.collection, false),
+            AccountMeta::new_readonly(collection_duration(&collection_id), false),
+            AccountMeta::new(nft.token_account, false),
+            AccountMeta::new_readonly(nft.mint, false),
+            AccountMeta::new(nft.metadata, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(anchor_spl::associated_token::ID, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(metaplex_token_metadata::id(), false),
+        ],
+        CREATE_RAFFLE,
+        nonce,
+    );
+    test.process(&[create], &[]).await.unwrap();
+
+    let state = test.raffle_state(&raffle).await;
+    assert_eq!(state.creator, creator);
+    assert_eq!(state.nft_mint, nft.mint);
+    assert_eq!(state.ticket_price_sol, TICKET_PRICE);
+    assert_eq!(test.token_amount(&nft.token_account).await, 0);
+    assert_eq!(test.token_amount(&escrow_nft_token_account).await, 1);
+
+    let buy = caller_instruction(
+        vec![
+            AccountMeta::new(buyer, false),
+            AccountMeta::new(raffle, false),
+            AccountMeta::new(global_authority().0, false),
+            AccountMeta::new(creator, false),
+            AccountMeta::new(treasury_wallet(), false),
+            AccountMeta::new(receipt_address(&raffle, &buyer), false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        BUY_TICKETS,
+        3,
+    );
+    let buyer_balance = test.balance(&buyer).await;
+    test.process(&[buy], &[]).await.unwrap();
+
+    let state = test.raffle_state(&raffle).await;
+    assert_eq!(state.count, 3);
+    assert!(state.entrants[..3].iter().all(|entrant| *entrant == buyer));
+    let receipt = test.receipt_state(&raffle, &buyer).await;
+    assert_eq!(receipt.buyer, buyer);
+    assert_eq!(receipt.paid, 3 * TICKET_PRICE);
+    // the buyer also paid the rent of its receipt
+    assert!(buyer_balance - test.balance(&buyer).await >= 3 * TICKET_PRICE);
+}
@@ -0,0 +1,270 @@
+mod common;
+
+use std::collections::{HashMap, HashSet};
+
+use common::*;
+use proptest::prelude::*;
+use raffle::{
+    account::{DiscountTier, RoyaltyShare, TicketReceipt},
+    constants::*,
+    error::RaffleError,
+};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{
+    rent::Rent,
+    signature::{Keypair, Signer},
+};
+
+const BUYERS: usize = 8;
+
+// every case runs its own program test
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+async fn total_balance(test: &mut RaffleTest, accounts: &[Pubkey]) -> u64 {
+    let mut total = 0;
+    for account in accounts {
+        total += test.balance(account).await;
+    }
+    total
+}
+
+// (min_tickets, discount_percent) sorted by min_tickets like `set_discounts` requires
+fn discounts() -> impl Strategy<Value = Vec<(u64, u64)>> {
+    prop::collection::vec((1u64..10, 0u64..100), 0..=MAX_DISCOUNT_TIERS).prop_map(|steps| {
+        let mut min_tickets = 0;
+        steps
+            .into_iter()
+            .map(|(step, discount_percent)| {
+                min_tickets += step;
+                (min_tickets, discount_percent)
+            })
+            .collect()
+    })
+}
+
+// royalty basis points which sum up to BASIS_POINTS at most
+fn royalties() -> impl Strategy<Value = Vec<u64>> {
+    prop::collection::vec(
+        1u64..=BASIS_POINTS / MAX_ROYALTY_RECIPIENTS as u64,
+        0..=MAX_ROYALTY_RECIPIENTS,
+    )
+}
+
+proptest! {
+    // a case sends dozens of transactions, so run fewer of them
+    #![proptest_config(ProptestConfig::with_cases(16))]
+
+    #[test]
+    fn ticket_sales_conserve_funds_and_count_tickets(
+        ticket_price_sol in 1u64..LAMPORTS_PER_SOL,
+        max_entrants in 1u64..=200,
+        discounts in discounts(),
+        royalties in royalties(),
+        referral_share in 0u64..=100,
+        purchases in prop::collection::vec((0..BUYERS, 0u64..40, any::<bool>()), 1..30),
+        reveal_delay in 0i64..DAY,
+    ) {
+        block_on(async move {
+            let mut test = RaffleTest::start_initialized().await;
+            test.set_referral_share(referral_share).await.unwrap();
+            let treasury = treasury_wallet();
+            test.set_account(&treasury, system_account(LAMPORTS_PER_SOL));
+            let referrer = test.new_wallet(LAMPORTS_PER_SOL);
+            let referrer_pool = test.init_referrer(&referrer).await.unwrap();
+            let buyers: Vec<Keypair> = (0..BUYERS)
+                .map(|_| test.new_wallet(1000 * LAMPORTS_PER_SOL))
+                .collect();
+            let recipients: Vec<Pubkey> = royalties
+                .iter()
+                .map(|_| test.new_wallet(LAMPORTS_PER_SOL).pubkey())
+                .collect();
+
+            let (creator, _, raffle) = test
+                .create_default_raffle(CreateRaffleParams {
+                    ticket_price_sol,
+                    max_entrants,
+                    discounts: discounts
+                        .into_iter()
+                        .map(|(min_tickets, discount_percent)| DiscountTier {
+                            min_tickets,
+                            discount_percent,
+                        })
+                        .collect(),
+                    royalties: recipients
+                        .iter()
+                        .zip(royalties)
+                        .map(|(recipient, basis_points)| RoyaltyShare {
+                            recipient: *recipient,
+                            basis_points,
+                        })
+                        .collect(),
+                    ..raffle_params()
+                })
+                .await;
+            let mut payees = vec![creator.pubkey(), referrer.pubkey(), treasury];
+            payees.extend_from_slice(&recipients);
+            let receipt_rent = Rent::default().minimum_balance(TicketReceipt::LEN);
+
+            let mut tickets_sold: u64 = 0;
+            let mut tickets_by_buyer: HashMap<Pubkey, u64> = HashMap::new();
+            let mut paid_by_buyer: HashMap<Pubkey, u64> = HashMap::new();
+            let mut distinct_buyers: HashSet<Pubkey> = HashSet::new();
+            let mut total_paid: u64 = 0;
+            let payees_balance = total_balance(&mut test, &payees).await;
+
+            for (buyer_index, amount, referred) in purchases {
+                let buyer = &buyers[buyer_index];
+                let state = test.raffle_state(&raffle).await;
+                let mut remaining_accounts = recipients.clone();
+                if referred {
+                    remaining_accounts.extend([referrer.pubkey(), referrer_pool]);
+                }
+                let instruction = test.buy_tickets_ix(
+                    &buyer.pubkey(),
+                    &raffle,
+                    &creator.pubkey(),
+                    amount,
+                    &remaining_accounts,
+                );
+                let first_purchase = !paid_by_buyer.contains_key(&buyer.pubkey());
+                let buyer_balance = test.balance(&buyer.pubkey()).await;
+                let balance_before = total_balance(&mut test, &payees).await;
+                let result = test.process(&[instruction], &[buyer]).await;
+
+                // a rejected purchase changes nothing
+                if amount == 0 || state.count + amount > max_entrants {
+                    let error = if amount == 0 {
+                        RaffleError::InvalidTicketAmount
+                    } else {
+                        RaffleError::NotEnoughTicketsLeft
+                    };
+                    assert_raffle_error(result, error);
+                    prop_assert_eq!(test.balance(&buyer.pubkey()).await, buyer_balance);
+                    prop_assert_eq!(test.raffle_state(&raffle).await.count, state.count);
+                    continue;
+                }
+                result.unwrap();
+
+                let total_amount = state.ticket_cost(amount).unwrap();
+                prop_assert!(total_amount <= amount * ticket_price_sol);
+                // the buyer also funds the receipt of the SOL paid at the first purchase
+                let rent = if first_purchase { receipt_rent } else { 0 };
+                prop_assert_eq!(
+                    buyer_balance - test.balance(&buyer.pubkey()).await,
+                    total_amount + rent
+                );
+                prop_assert_eq!(
+                    total_balance(&mut test, &payees).await - balance_before,
+                    total_amount
+                );
+
+                tickets_sold += amount;
+                *tickets_by_buyer.entry(buyer.pubkey()).or_default() += amount;
+                *paid_by_buyer.entry(buyer.pubkey()).or_default() += total_amount;
+                distinct_buyers.insert(buyer.pubkey());
+                total_paid += total_amount;
+            }
+
+            let state = test.raffle_state(&raffle).await;
+            prop_assert_eq!(
+                total_balance(&mut test, &payees).await - payees_balance,
+                total_paid
+            );
+            prop_assert_eq!(state.total_paid, total_paid);
+            prop_assert!(state.count <= max_entrants);
+            prop_assert_eq!(state.count, tickets_sold);
+            prop_assert_eq!(state.active_tickets(), tickets_sold);
+            prop_assert_eq!(state.no_repeat, distinct_buyers.len() as u64);
+            for (buyer, tickets) in &tickets_by_buyer {
+                let entries = state.entrants[..state.count as usize]
+                    .iter()
+                    .filter(|entrant| *entrant == buyer)
+                    .count() as u64;
+                prop_assert_eq!(entries, *tickets);
+                let receipt = test.receipt_state(&raffle, buyer).await;
+                prop_assert_eq!(receipt.paid, paid_by_buyer[buyer]);
+            }
+
+            if state.count > 0 {
+                test.set_timestamp(state.end_timestamp + reveal_delay).await;
+                test.reveal_winner(&raffle).await.unwrap();
+                let state = test.raffle_state(&raffle).await;
+                prop_assert!(state.winner_index < state.count);
+                prop_assert_eq!(state.winner, state.entrants[state.winner_index as usize]);
+                prop_assert!(distinct_buyers.contains(&state.winner));
+                prop_assert_eq!(state.claimed, 2);
+            }
+            Ok(())
+        })?;
+    }
+
+    #[test]
+    fn rerolled_winners_are_active_entrants(
+        purchases in prop::collection::vec((0..BUYERS, 0u64..10), 1..20),
+        reroll_delays in prop::collection::vec(1i64..DAY, 1..10),
+    ) {
+        block_on(async move {
+            let mut test = RaffleTest::start_initialized().await;
+            test.set_claim_window(DAY).await.unwrap();
+            let (_, _, raffle) = test
+                .create_default_raffle(CreateRaffleParams {
+                    max_entrants: MAX_ENTRANTS as u64,
+                    ..raffle_params()
+                })
+                .await;
+            let buyers: Vec<Keypair> = (0..BUYERS)
+                .map(|_| test.new_wallet(100 * LAMPORTS_PER_SOL))
+                .collect();
+            let mut tickets_by_buyer: HashMap<Pubkey, u64> = HashMap::new();
+            for (buyer_index, amount) in purchases {
+                let buyer = &buyers[buyer_index];
+                if test.buy_tickets(buyer, &raffle, amount).await.is_ok() {
+                    *tickets_by_buyer.entry(buyer.pubkey()).or_default() += amount;
+                }
+            }
+            if tickets_by_buyer.is_empty() {
+                return Ok(());
+            }
+
+            let state = test.raffle_state(&raffle).await;
+            test.set_timestamp(state.end_timestamp).await;
+            test.reveal_winner(&raffle).await.unwrap();
+
+            // every reroll removes the previous winner's tickets
+            let caller = test.new_wallet(LAMPORTS_PER_SOL);
+            for reroll_delay in reroll_delays {
+                let state = test.raffle_state(&raffle).await;
+                if state.claimed != 2 {
+                    break;
+                }
+                test.set_timestamp(state.claim_deadline + reroll_delay).await;
+                test.reroll_winner(&caller, &raffle).await.unwrap();
+
+                let removed = tickets_by_buyer.remove(&state.winner).unwrap();
+                let rerolled = test.raffle_state(&raffle).await;
+                prop_assert_eq!(rerolled.active_tickets(), state.active_tickets() - removed);
+                if rerolled.active_tickets() == 0 {
+                    // nobody is left, the NFT went back to the creator
+                    prop_assert_eq!(rerolled.claimed, 3);
+                    prop_assert_eq!(rerolled.winner, Pubkey::default());
+                    break;
+                }
+                prop_assert_eq!(rerolled.claimed, 2);
+                prop_assert!(rerolled.winner_index < rerolled.count);
+                prop_assert_ne!(rerolled.winner, state.winner);
+                prop_assert_eq!(
+                    rerolled.winner,
+                    rerolled.entrants[rerolled.winner_index as usize]
+                );
+                prop_assert!(tickets_by_buyer.contains_key(&rerolled.winner));
+            }
+            Ok(())
+        })?;
+    }
+}
@@ -0,0 +1,1772 @@
+mod common;
+
+use anchor_lang::{error::ErrorCode, Discriminator, InstructionData, ToAccountMetas};
+use anchor_spl::associated_token::get_associated_token_address;
+use common::*;
+use metaplex_token_metadata::state::Creator;
+use raffle::{
+    account::{CollectionPool, DiscountTier, GlobalPool, RafflePool, RoyaltyShare, TicketReceipt},
+    constants::*,
+    error::RaffleError,
+    utils::{draw_random, split_commission, voucher_message},
+};
+use solana_program::{entrypoint::MAX_PERMITTED_DATA_INCREASE, program_pack::Pack, pubkey::Pubkey};
+use solana_sdk::{
+    account::Account,
+    instruction::Instruction,
+    rent::Rent,
+    signature::{Keypair, Signer},
+};
+
+#[tokio::test]
+async fn initialize_sets_the_super_admin() {
+    let mut test = RaffleTest::start().await;
+    test.initialize().await.unwrap();
+
+    let global = test.global_state().await;
+    assert_eq!(global.super_admin, test.admin.pubkey());
+    assert_eq!(global.min_duration, DAY);
+    assert_eq!(global.max_duration, 0);
+    assert!(!global.paused);
+    assert_eq!(global.bump, global_authority().1);
+
+    // the global authority PDA can only be created once
+    assert!(test.initialize().await.is_err());
+}
+
+#[tokio::test]
+async fn add_collection_skips_duplicates() {
+    let mut test = RaffleTest::start_initialized().await;
+    let collection_id = test.collection_id;
+    test.add_collection(&collection_id).await.unwrap();
+    let other_collection = Pubkey::new_unique();
+    test.add_collection(&other_collection).await.unwrap();
+
+    let collection = test.collection_state().await;
+    assert_eq!(collection.count, 2);
+    assert_eq!(collection.collections[0], collection_id);
+    assert_eq!(collection.collections[1], other_collection);
+}
+
+#[tokio::test]
+async fn add_collection_is_admin_only() {
+    let mut test = RaffleTest::start_initialized().await;
+    let attacker = test.new_wallet(LAMPORTS_PER_SOL);
+    let instruction = Instruction {
+        program_id: raffle::id(),
+        accounts: raffle::accounts::AddCollection {
+            admin: attacker.pubkey(),
+            global_authority: global_authority().0,
+            collection: test.collection,
+            collection_id: Pubkey::new_unique(),
+        }
+        .to_account_metas(None),
+        data: raffle::instruction::AddCollection {}.data(),
+    };
+    let result = test.process(&[instruction], &[&attacker]).await;
+    assert_raffle_error(result, RaffleError::NotAdmin);
+    assert_eq!(test.collection_state().await.count, 1);
+}
+
+#[tokio::test]
+async fn set_paused_by_the_pauser() {
+    let mut test = RaffleTest::start_initialized().await;
+    let pauser = test.new_wallet(LAMPORTS_PER_SOL);
+
+    let result = test.set_paused_by(&pauser, true).await;
+    assert_raffle_error(result, RaffleError::NotPauser);
+
+    test.set_pauser(&pauser.pubkey()).await.unwrap();
+    assert_eq!(test.global_state().await.pauser, pauser.pubkey());
+    test.set_paused_by(&pauser, true).await.unwrap();
+    assert!(test.global_state().await.paused);
+    test.set_paused_by(&pauser, false).await.unwrap();
+    assert!(!test.global_state().await.paused);
+
+    // the super admin can still pause besides the pauser
+    test.set_paused(true).await.unwrap();
+    assert!(test.global_state().await.paused);
+}
+
+#[tokio::test]
+async fn set_referral_share_errors() {
+    let mut test = RaffleTest::start_initialized().await;
+    let result = test.set_referral_share(101).await;
+    assert_raffle_error(result, RaffleError::InvalidReferralShare);
+
+    test.set_referral_share(100).await.unwrap();
+    assert_eq!(test.global_state().await.referral_share, 100);
+}
+
+#[tokio::test]
+async fn create_raffle_escrows_the_nft() {
+    let mut test = RaffleTest::start_initialized().await;
+    let creator = test.new_wallet(10 * LAMPORTS_PER_SOL);
+    let nft = test.create_collection_nft(&creator.pubkey());
+    let params = CreateRaffleParams {
+        nonce: 7,
+        start_timestamp: NOW + 100,
+        end_timestamp: NOW + 100 + DAY,
+        discounts: vec![DiscountTier {
+            min_tickets: 10,
+            discount_percent: 10,
+        }],
+        ..raffle_params()
+    };
+    let raffle = test.create_raffle(&creator, &nft, params).await.unwrap();
+
+    let state = test.raffle_state(&raffle).await;
+    assert_eq!(state.creator, creator.pubkey());
+    assert_eq!(state.nft_mint, nft.mint);
+    assert_eq!(state.nonce, 7);
+    assert_eq!(state.ticket_price_sol, TICKET_PRICE);
+    assert_eq!(state.start_timestamp, NOW + 100);
+    assert_eq!(state.end_timestamp, NOW + 100 + DAY);
+    assert_eq!(state.max_entrants, 100);
+    assert_eq!(state.discount_count, 1);
+    assert_eq!(state.settle_bounty, SETTLE_BOUNTY);
+    assert_eq!(state.count, 0);
+    assert_eq!(state.claimed, 0);
+
+    assert_eq!(test.token_amount(&nft.token_account).await, 0);
+    let escrow_nft = escrow_token_account(&raffle, &nft.mint);
+    assert_eq!(test.token_amount(&escrow_nft).await, 1);
+}
+
+#[tokio::test]
+async fn allocate_raffle_grows_the_raffle_in_steps() {
+    let mut test = RaffleTest::start_initialized().await;
+    let creator = test.new_wallet(10 * LAMPORTS_PER_SOL);
+    let nft = test.create_collection_nft(&creator.pubkey());
+    let raffle = raffle_address(&creator.pubkey(), &nft.mint, 0);
+
+    let instruction = test.allocate_raffle_ix(&creator.pubkey(), &nft.mint, 0);
+    test.process(&[instruction], &[]).await.unwrap();
+    let account = test.get_account(&raffle).await.unwrap();
+    assert_eq!(account.owner, raffle::id());
+    assert_eq!(account.data.len(), MAX_PERMITTED_DATA_INCREASE);
+
+    // the raffle can't be created before it is fully allocated
+    let instruction = test.create_raffle_ix(&creator.pubkey(), &nft, raffle_params());
+    let result = test.process(&[instruction], &[&creator]).await;
+    assert_raffle_error(result, RaffleError::RaffleNotAllocated);
+
+    // allocating more than the steps left does nothing
+    test.allocate_raffle(&creator.pubkey(), &nft.mint, 0)
+        .await
+        .unwrap();
+    let account = test.get_account(&raffle).await.unwrap();
+    assert_eq!(account.data.len(), RafflePool::LEN);
+    assert_eq!(
+        account.lamports,
+        Rent::default().minimum_balance(RafflePool::LEN)
+    );
+
+    let instruction = test.create_raffle_ix(&creator.pubkey(), &nft, raffle_params());
+    test.process(&[instruction], &[&creator]).await.unwrap();
+    assert_eq!(test.raffle_state(&raffle).await.creator, creator.pubkey());
+}
+
+#[tokio::test]
+async fn create_raffle_starts_now_when_the_start_passed() {
+    let mut test = RaffleTest::start_initialized().await;
+    let (_, _, raffle) = test
+        .create_default_raffle(CreateRaffleParams {
+            start_timestamp: NOW - 100,
+            ..raffle_params()
+        })
+        .await;
+    assert_eq!(test.raffle_state(&raffle).await.start_timestamp, NOW);
+}
+
+#[tokio::test]
+async fn create_raffle_errors() {
+    let mut test = RaffleTest::start_initialized().await;
+    let creator = test.new_wallet(10 * LAMPORTS_PER_SOL);
+    let nft = test.create_collection_nft(&creator.pubkey());
+
+    let result = test
+        .create_raffle(
+            &creator,
+            &nft,
+            CreateRaffleParams {
+                payment_mode: PAYMENT_BURN_NFT + 1,
+                ..raffle_params()
+            },
+        )
+        .await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::InvalidPaymentMode);
+
+    let result = test
+        .create_raffle(
+            &creator,
+            &nft,
+            CreateRaffleParams {
+                max_entrants: MAX_ENTRANTS as u64 + 1,
+                ..raffle_params()
+            },
+        )
+        .await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::MaxEntrantsTooLarge);
+
+    let result = test
+        .create_raffle(
+            &creator,
+            &nft,
+            CreateRaffleParams {
+                end_timestamp: NOW + DAY - 1,
+                ..raffle_params()
+            },
+        )
+        .await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::EndTimeError);
+
+    // the scheduled start counts for the duration
+    let result = test
+        .create_raffle(
+            &creator,
+            &nft,
+            CreateRaffleParams {
+                start_timestamp: NOW + DAY,
+                end_timestamp: NOW + 2 * DAY - 1,
+                ..raffle_params()
+            },
+        )
+        .await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::EndTimeError);
+
+    test.set_raffle_duration(DAY, 7 * DAY).await.unwrap();
+    let result = test
+        .create_raffle(
+            &creator,
+            &nft,
+            CreateRaffleParams {
+                end_timestamp: NOW + 7 * DAY + 1,
+                ..raffle_params()
+            },
+        )
+        .await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::EndTimeError);
+
+    // a late start locks the NFT for longer than the maximum
+    let result = test
+        .create_raffle(
+            &creator,
+            &nft,
+            CreateRaffleParams {
+                start_timestamp: NOW + 7 * DAY,
+                end_timestamp: NOW + 8 * DAY,
+                ..raffle_params()
+            },
+        )
+        .await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::EndTimeError);
+
+    let result = test
+        .create_raffle(
+            &creator,
+            &nft,
+            CreateRaffleParams {
+                discounts: vec![
+                    DiscountTier {
+                        min_tickets: 10,
+                        discount_percent: 10,
+                    },
+                    DiscountTier {
+                        min_tickets: 5,
+                        discount_percent: 20,
+                    },
+                ],
+                ..raffle_params()
+            },
+        )
+        .await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::InvalidDiscountTiers);
+
+    let result = test
+        .create_raffle(
+            &creator,
+            &nft,
+            CreateRaffleParams {
+                royalties: vec![RoyaltyShare {
+                    recipient: Pubkey::new_unique(),
+                    basis_points: BASIS_POINTS + 1,
+                }],
+                ..raffle_params()
+            },
+        )
+        .await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::InvalidRoyaltySplit);
+
+    let result = test
+        .create_raffle(
+            &creator,
+            &nft,
+            CreateRaffleParams {
+                use_nft_creators: true,
+                royalties: vec![RoyaltyShare {
+                    recipient: Pubkey::new_unique(),
+                    basis_points: 100,
+                }],
+                ..raffle_params()
+            },
+        )
+        .await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::InvalidRoyaltySplit);
+
+    // the duration override must be the PDA of the NFT's collection
+    let mut instruction = test.create_raffle_ix(&creator.pubkey(), &nft, raffle_params());
+    instruction.accounts[5].pubkey = collection_duration(&Pubkey::new_unique());
+    let result = test.process(&[instruction], &[&creator]).await;
+    assert_raffle_error(result, RaffleError::InvalidCollectionDuration);
+
+    // the metadata must be the metaplex PDA of the NFT mint
+    let mut wrong_metadata_nft = test.create_collection_nft(&creator.pubkey());
+    wrong_metadata_nft.metadata = Pubkey::new_unique();
+    test.set_metadata(&wrong_metadata_nft.metadata, &wrong_metadata_nft.mint, None);
+    let result = test
+        .create_raffle(&creator, &wrong_metadata_nft, raffle_params())
+        .await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::InvaliedMetadata);
+
+    let unverified_nft = test.create_nft(
+        &creator.pubkey(),
+        Some(vec![Creator {
+            address: test.collection_id,
+            verified: false,
+            share: 100,
+        }]),
+    );
+    let result = test
+        .create_raffle(&creator, &unverified_nft, raffle_params())
+        .await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::InvalidCollection);
+
+    let no_creators_nft = test.create_nft(&creator.pubkey(), None);
+    let result = test
+        .create_raffle(&creator, &no_creators_nft, raffle_params())
+        .await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::MetadataCreatorParseError);
+
+    test.set_paused(true).await.unwrap();
+    let result = test.create_raffle(&creator, &nft, raffle_params()).await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::ProgramPaused);
+}
+
+#[tokio::test]
+async fn create_raffle_uses_the_collection_duration() {
+    let mut test = RaffleTest::start_initialized().await;
+    let collection_id = test.collection_id;
+    test.init_collection_duration(&collection_id, 60 * 60, DAY)
+        .await
+        .unwrap();
+    let creator = test.new_wallet(10 * LAMPORTS_PER_SOL);
+
+    // one-hour flash raffles are below the global minimum of one day
+    let nft = test.create_collection_nft(&creator.pubkey());
+    test.create_raffle(
+        &creator,
+        &nft,
+        CreateRaffleParams {
+            end_timestamp: NOW + 60 * 60,
+            ..raffle_params()
+        },
+    )
+    .await
+    .unwrap();
+
+    let nft = test.create_collection_nft(&creator.pubkey());
+    let result = test
+        .create_raffle(
+            &creator,
+            &nft,
+            CreateRaffleParams {
+                end_timestamp: NOW + DAY + 1,
+                ..raffle_params()
+            },
+        )
+        .await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::EndTimeError);
+
+    let admin = clone_keypair(&test.admin);
+    test.set_collection_duration(&admin, &collection_id, 60 * 60, 0)
+        .await
+        .unwrap();
+    test.create_raffle(
+        &creator,
+        &nft,
+        CreateRaffleParams {
+            end_timestamp: NOW + 30 * DAY,
+            ..raffle_params()
+        },
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn raffle_duration_errors() {
+    let mut test = RaffleTest::start_initialized().await;
+    let collection_id = test.collection_id;
+
+    let result = test.set_raffle_duration(-1, 0).await;
+    assert_raffle_error(result, RaffleError::InvalidDuration);
+    let result = test.set_raffle_duration(DAY, DAY - 1).await;
+    assert_raffle_error(result, RaffleError::InvalidDuration);
+    let result = test
+        .init_collection_duration(&collection_id, DAY, 60 * 60)
+        .await;
+    assert_raffle_error(result, RaffleError::InvalidDuration);
+
+    test.init_collection_duration(&collection_id, DAY, 0)
+        .await
+        .unwrap();
+    let admin = clone_keypair(&test.admin);
+    let result = test
+        .set_collection_duration(&admin, &collection_id, 0, -1)
+        .await;
+    assert_raffle_error(result, RaffleError::InvalidDuration);
+
+    let stranger = test.new_wallet(LAMPORTS_PER_SOL);
+    let result = test
+        .set_collection_duration(&stranger, &collection_id, 60 * 60, 0)
+        .await;
+    assert_raffle_error(result, RaffleError::NotAdmin);
+}
+
+#[tokio::test]
+async fn buy_tickets_splits_the_payment() {
+    let mut test = RaffleTest::start_initialized().await;
+    let (creator, _, raffle) = test.create_default_raffle(raffle_params()).await;
+    let buyer = test.new_wallet(10 * LAMPORTS_PER_SOL);
+    let treasury = treasury_wallet();
+
+    let creator_balance = test.balance(&creator.pubkey()).await;
+    let treasury_balance = test.balance(&treasury).await;
+    test.buy_tickets(&buyer, &raffle, 5).await.unwrap();
+    test.buy_tickets(&buyer, &raffle, 2).await.unwrap();
+
+    let (creator_amount, fee_amount) = split_commission(5 * TICKET_PRICE).unwrap();
+    let (creator_amount2, fee_amount2) = split_commission(2 * TICKET_PRICE).unwrap();
+    assert_eq!(
+        test.balance(&creator.pubkey()).await,
+        creator_balance + creator_amount + creator_amount2
+    );
+    assert_eq!(
+        test.balance(&treasury).await,
+        treasury_balance + fee_amount + fee_amount2
+    );
+    // the buyer also funds the receipt of the SOL paid at the first purchase
+    let receipt_rent = Rent::default().minimum_balance(TicketReceipt::LEN);
+    assert_eq!(
+        test.balance(&buyer.pubkey()).await,
+        10 * LAMPORTS_PER_SOL - 7 * TICKET_PRICE - receipt_rent
+    );
+    assert_eq!(
+        test.receipt_state(&raffle, &buyer.pubkey()).await.paid,
+        7 * TICKET_PRICE
+    );
+
+    let state = test.raffle_state(&raffle).await;
+    assert_eq!(state.count, 7);
+    assert_eq!(state.no_repeat, 1);
+    assert_eq!(state.total_paid, 7 * TICKET_PRICE);
+    assert!(state.entrants[..7]
+        .iter()
+        .all(|entrant| *entrant == buyer.pubkey()));
+}
+
+#[tokio::test]
+async fn buy_tickets_applies_the_discount() {
+    let mut test = RaffleTest::start_initialized().await;
+    let (_, _, raffle) = test
+        .create_default_raffle(CreateRaffleParams {
+            discounts: vec![DiscountTier {
+                min_tickets: 10,
+                discount_percent: 20,
+            }],
+            ..raffle_params()
+        })
+        .await;
+    let buyer = test.new_wallet(10 * LAMPORTS_PER_SOL);
+    test.buy_tickets(&buyer, &raffle, 10).await.unwrap();
+
+    assert_eq!(
+        test.balance(&buyer.pubkey()).await,
+        10 * LAMPORTS_PER_SOL
+            - 8 * TICKET_PRICE
+            - Rent::default().minimum_balance(TicketReceipt::LEN)
+    );
+}
+
+#[tokio::test]
+async fn buy_tickets_errors() {
+    let mut test = RaffleTest::start_initialized().await;
+    let (creator, _, raffle) = test
+        .create_default_raffle(CreateRaffleParams {
+            start_timestamp: NOW + 100,
+            end_timestamp: NOW + 100 + DAY,
+            max_entrants: 10,
+            ..raffle_params()
+        })
+        .await;
+    let buyer = test.new_wallet(10 * LAMPORTS_PER_SOL);
+
+    let result = test.buy_tickets(&buyer, &raffle, 1).await;
+    assert_raffle_error(result, RaffleError::RaffleNotStarted);
+
+    test.set_timestamp(NOW + 100).await;
+    let result = test.buy_tickets(&buyer, &raffle, 11).await;
+    assert_raffle_error(result, RaffleError::NotEnoughTicketsLeft);
+
+    let poor_buyer = test.new_wallet(TICKET_PRICE / 2);
+    let result = test.buy_tickets(&poor_buyer, &raffle, 1).await;
+    assert_raffle_error(result, RaffleError::NotEnoughSOL);
+
+    // the referrer pool must be owned by the program
+    let referrer = Pubkey::new_unique();
+    let instruction = test.buy_tickets_ix(
+        &buyer.pubkey(),
+        &raffle,
+        &creator.pubkey(),
+        1,
+        &[referrer, Pubkey::new_unique()],
+    );
+    let result = test.process(&[instruction], &[&buyer]).await;
+    assert_raffle_error(result, RaffleError::InvalidReferrer);
+
+    // the creator share can't be redirected
+    let instruction = test.buy_tickets_ix(&buyer.pubkey(), &raffle, &buyer.pubkey(), 1, &[]);
+    let result = test.process(&[instruction], &[&buyer]).await;
+    assert_raffle_error(result, RaffleError::NotCreator);
+
+    test.set_raffle_frozen(&raffle, true).await.unwrap();
+    let result = test.buy_tickets(&buyer, &raffle, 1).await;
+    assert_raffle_error(result, RaffleError::RaffleFrozen);
+    test.set_raffle_frozen(&raffle, false).await.unwrap();
+
+    test.set_paused(true).await.unwrap();
+    let result = test.buy_tickets(&buyer, &raffle, 1).await;
+    assert_raffle_error(result, RaffleError::ProgramPaused);
+    test.set_paused(false).await.unwrap();
+
+    test.buy_tickets(&buyer, &raffle, 10).await.unwrap();
+    let result = test.buy_tickets(&buyer, &raffle, 1).await;
+    assert_raffle_error(result, RaffleError::NotEnoughTicketsLeft);
+
+    test.set_timestamp(NOW + 100 + DAY + 1).await;
+    let result = test.buy_tickets(&buyer, &raffle, 1).await;
+    assert_raffle_error(result, RaffleError::RaffleEnded);
+}
+
+#[tokio::test]
+async fn buy_tickets_rejects_overflowing_costs() {
+    let mut test = RaffleTest::start_initialized().await;
+    let (_, _, raffle) = test
+        .create_default_raffle(CreateRaffleParams {
+            ticket_price_sol: u64::MAX / 2,
+            ..raffle_params()
+        })
+        .await;
+    let buyer = test.new_wallet(10 * LAMPORTS_PER_SOL);
+
+    let result = test.buy_tickets(&buyer, &raffle, 3).await;
+    assert_raffle_error(result, RaffleError::InvalidCalculation);
+}
+
+#[tokio::test]
+async fn buy_tickets_checks_the_payment_mode_and_royalties() {
+    let mut test = RaffleTest::start_initialized().await;
+    let buyer = test.new_wallet(10 * LAMPORTS_PER_SOL);
+
+    let (_, _, burn_raffle) = test
+        .create_default_raffle(CreateRaffleParams {
+            payment_mode: PAYMENT_BURN_TOKEN,
+            burn_target: Pubkey::new_unique(),
+            ..raffle_params()
+        })
+        .await;
+    let result = test.buy_tickets(&buyer, &burn_raffle, 1).await;
+    assert_raffle_error(result, RaffleError::InvalidPaymentMode);
+
+    let recipient = Pubkey::new_unique();
+    let (creator, _, raffle) = test
+        .create_default_raffle(CreateRaffleParams {
+            royalties: vec![RoyaltyShare {
+                recipient,
+                basis_points: 1000,
+            }],
+            ..raffle_params()
+        })
+        .await;
+    let result = test.buy_tickets(&buyer, &raffle, 1).await;
+    assert_raffle_error(result, RaffleError::InvalidRoyaltyRecipient);
+
+    let wrong_recipient = Pubkey::new_unique();
+    let instruction = test.buy_tickets_ix(
+        &buyer.pubkey(),
+        &raffle,
+        &creator.pubkey(),
+        1,
+        &[wrong_recipient],
+    );
+    let result = test.process(&[instruction], &[&buyer]).await;
+    assert_raffle_error(result, RaffleError::InvalidRoyaltyRecipient);
+
+    let instruction = test.buy_tickets_ix(
+        &buyer.pubkey(),
+        &raffle,
+        &creator.pubkey(),
+        10,
+        &[recipient],
+    );
+    test.process(&[instruction], &[&buyer]).await.unwrap();
+    let (creator_amount, _) = split_commission(10 * TICKET_PRICE).unwrap();
+    assert_eq!(test.balance(&recipient).await, creator_amount / 10);
+}
+
+#[tokio::test]
+async fn burn_tokens_for_tickets_burns_the_ticket_price() {
+    let mut test = RaffleTest::start_initialized().await;
+    let buyer = test.new_wallet(LAMPORTS_PER_SOL);
+    let (burn_mint, buyer_tokens) = test.create_token(&buyer.pubkey(), 100);
+    let (_, _, raffle) = test
+        .create_default_raffle(CreateRaffleParams {
+            ticket_price_sol: 10,
+            payment_mode: PAYMENT_BURN_TOKEN,
+            burn_target: burn_mint,
+            ..raffle_params()
+        })
+        .await;
+
+    test.burn_tokens_for_tickets(&buyer, &raffle, &burn_mint, &buyer_tokens, 3)
+        .await
+        .unwrap();
+    assert_eq!(test.token_amount(&buyer_tokens).await, 70);
+    assert_eq!(test.mint_supply(&burn_mint).await, 70);
+    let state = test.raffle_state(&raffle).await;
+    assert_eq!(state.count, 3);
+    assert_eq!(state.no_repeat, 1);
+    assert_eq!(state.entrants[2], buyer.pubkey());
+    // burned tickets are not paid in SOL so they have no receipt to refund
+    assert_eq!(state.total_paid, 0);
+    let receipt = receipt_address(&raffle, &buyer.pubkey());
+    assert!(test.get_account(&receipt).await.is_none());
+}
+
+#[tokio::test]
+async fn burn_tokens_for_tickets_errors() {
+    let mut test = RaffleTest::start_initialized().await;
+    let buyer = test.new_wallet(LAMPORTS_PER_SOL);
+    let (burn_mint, buyer_tokens) = test.create_token(&buyer.pubkey(), 50);
+    let (other_mint, other_tokens) = test.create_token(&buyer.pubkey(), 50);
+    let (_, _, raffle) = test
+        .create_default_raffle(CreateRaffleParams {
+            ticket_price_sol: 10,
+            payment_mode: PAYMENT_BURN_TOKEN,
+            burn_target: burn_mint,
+            ..raffle_params()
+        })
+        .await;
+    let (_, _, sol_raffle) = test.create_default_raffle(raffle_params()).await;
+
+    let result = test
+        .burn_tokens_for_tickets(&buyer, &sol_raffle, &burn_mint, &buyer_tokens, 1)
+        .await;
+    assert_raffle_error(result, RaffleError::InvalidPaymentMode);
+
+    let result = test
+        .burn_tokens_for_tickets(&buyer, &raffle, &other_mint, &other_tokens, 1)
+        .await;
+    assert_raffle_error(result, RaffleError::InvalidBurnToken);
+
+    // the buyer can't burn more tokens than they hold
+    assert!(test
+        .burn_tokens_for_tickets(&buyer, &raffle, &burn_mint, &buyer_tokens, 6)
+        .await
+        .is_err());
+
+    // the token account must be the buyer's
+    let stranger = test.new_wallet(LAMPORTS_PER_SOL);
+    assert!(test
+        .burn_tokens_for_tickets(&stranger, &raffle, &burn_mint, &buyer_tokens, 1)
+        .await
+        .is_err());
+
+    test.set_raffle_frozen(&raffle, true).await.unwrap();
+    let result = test
+        .burn_tokens_for_tickets(&buyer, &raffle, &burn_mint, &buyer_tokens, 1)
+        .await;
+    assert_raffle_error(result, RaffleError::RaffleFrozen);
+    test.set_raffle_frozen(&raffle, false).await.unwrap();
+
+    test.set_timestamp(NOW + 3 * DAY).await;
+    let result = test
+        .burn_tokens_for_tickets(&buyer, &raffle, &burn_mint, &buyer_tokens, 1)
+        .await;
+    assert_raffle_error(result, RaffleError::RaffleEnded);
+
+    assert_eq!(test.token_amount(&buyer_tokens).await, 50);
+    assert_eq!(test.raffle_state(&raffle).await.count, 0);
+}
+
+#[tokio::test]
+async fn burn_nft_for_ticket_burns_an_nft_of_the_burn_collection() {
+    let mut test = RaffleTest::start_initialized().await;
+    let burn_collection = Pubkey::new_unique();
+    let (_, _, raffle) = test
+        .create_default_raffle(CreateRaffleParams {
+            payment_mode: PAYMENT_BURN_NFT,
+            burn_target: burn_collection,
+            ..raffle_params()
+        })
+        .await;
+    let buyer = test.new_wallet(LAMPORTS_PER_SOL);
+    let creators = vec![Creator {
+        address: burn_collection,
+        verified: true,
+        share: 100,
+    }];
+    let nft = test.create_nft(&buyer.pubkey(), Some(creators));
+
+    test.burn_nft_for_ticket(&buyer, &raffle, &nft)
+        .await
+        .unwrap();
+    assert_eq!(test.token_amount(&nft.token_account).await, 0);
+    assert_eq!(test.mint_supply(&nft.mint).await, 0);
+    let state = test.raffle_state(&raffle).await;
+    assert_eq!(state.count, 1);
+    assert_eq!(state.entrants[0], buyer.pubkey());
+    assert_eq!(state.total_paid, 0);
+
+    // the NFT is gone so it buys one ticket only
+    assert!(test
+        .burn_nft_for_ticket(&buyer, &raffle, &nft)
+        .await
+        .is_err());
+    assert_eq!(test.raffle_state(&raffle).await.count, 1);
+}
+
+#[tokio::test]
+async fn burn_nft_for_ticket_errors() {
+    let mut test = RaffleTest::start_initialized().await;
+    let burn_collection = Pubkey::new_unique();
+    let (_, _, raffle) = test
+        .create_default_raffle(CreateRaffleParams {
+            payment_mode: PAYMENT_BURN_NFT,
+            burn_target: burn_collection,
+            ..raffle_params()
+        })
+        .await;
+    let buyer = test.new_wallet(LAMPORTS_PER_SOL);
+
+    // an NFT of the raffle collection is not in the burn collection
+    let other_nft = test.create_collection_nft(&buyer.pubkey());
+    let result = test.burn_nft_for_ticket(&buyer, &raffle, &other_nft).await;
+    assert_raffle_error(result, RaffleError::InvalidCollection);
+
+    // the burn collection must have verified the NFT
+    let unverified_nft = test.create_nft(
+        &buyer.pubkey(),
+        Some(vec![Creator {
+            address: burn_collection,
+            verified: false,
+            share: 100,
+        }]),
+    );
+    let result = test
+        .burn_nft_for_ticket(&buyer, &raffle, &unverified_nft)
+        .await;
+    assert_raffle_error(result, RaffleError::InvalidCollection);
+
+    // the metadata must be the PDA of the burned mint
+    let creators = vec![Creator {
+        address: burn_collection,
+        verified: true,
+        share: 100,
+    }];
+    let nft = test.create_nft(&buyer.pubkey(), Some(creators));
+    let forged_nft = Nft {
+        metadata: nft.metadata,
+        ..test.create_nft(&buyer.pubkey(), None)
+    };
+    let result = test.burn_nft_for_ticket(&buyer, &raffle, &forged_nft).await;
+    assert_raffle_error(result, RaffleError::InvaliedMetadata);
+
+    let (_, _, sol_raffle) = test.create_default_raffle(raffle_params()).await;
+    let result = test.burn_nft_for_ticket(&buyer, &sol_raffle, &nft).await;
+    assert_raffle_error(result, RaffleError::InvalidPaymentMode);
+
+    assert_eq!(test.token_amount(&nft.token_account).await, 1);
+    assert_eq!(test.raffle_state(&raffle).await.count, 0);
+}
+
+#[tokio::test]
+async fn claim_free_ticket_with_a_signed_voucher() {
+    let mut test = RaffleTest::start_initialized().await;
+    let (_, _, raffle) = test.create_default_raffle(raffle_params()).await;
+    let voucher_authority = Keypair::new();
+    test.set_voucher_authority(&voucher_authority.pubkey())
+        .await
+        .unwrap();
+    let claimer = test.new_wallet(LAMPORTS_PER_SOL);
+
+    test.claim_free_ticket(&claimer, &raffle, 7, 2, &voucher_authority)
+        .await
+        .unwrap();
+    let state = test.raffle_state(&raffle).await;
+    assert_eq!(state.count, 2);
+    assert_eq!(state.entrants[0], claimer.pubkey());
+    assert_eq!(state.total_paid, 0);
+
+    // the voucher PDA exists now so the voucher can't be claimed twice
+    assert!(test
+        .claim_free_ticket(&claimer, &raffle, 7, 2, &voucher_authority)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn claim_free_ticket_rejects_forged_vouchers() {
+    let mut test = RaffleTest::start_initialized().await;
+    let (_, _, raffle) = test.create_default_raffle(raffle_params()).await;
+    let voucher_authority = Keypair::new();
+    let forger = Keypair::new();
+    let claimer = test.new_wallet(LAMPORTS_PER_SOL);
+
+    // vouchers are off while the voucher authority is not set
+    let result = test
+        .claim_free_ticket(&claimer, &raffle, 1, 1, &forger)
+        .await;
+    assert_raffle_error(result, RaffleError::InvalidVoucher);
+
+    test.set_voucher_authority(&voucher_authority.pubkey())
+        .await
+        .unwrap();
+    let result = test
+        .claim_free_ticket(&claimer, &raffle, 1, 1, &forger)
+        .await;
+    assert_raffle_error(result, RaffleError::InvalidVoucher);
+
+    // a voucher of the authority for another amount doesn't match the claim
+    let message = voucher_message(&raffle, &claimer.pubkey(), 1, 2);
+    let instruction = test.claim_free_ticket_ix(&claimer.pubkey(), &raffle, 2, 50);
+    let result = test
+        .process(
+            &[
+                ed25519_instruction(&voucher_authority, &message),
+                instruction,
+            ],
+            &[&claimer],
+        )
+        .await;
+    assert_raffle_error(result, RaffleError::InvalidVoucher);
+
+    // the voucher must be verified by the ed25519 instruction right before the claim
+    let instruction = test.claim_free_ticket_ix(&claimer.pubkey(), &raffle, 3, 1);
+    let result = test.process(&[instruction], &[&claimer]).await;
+    assert_raffle_error(result, RaffleError::InvalidVoucher);
+
+    assert_eq!(test.raffle_state(&raffle).await.count, 0);
+}
+
+#[tokio::test]
+async fn reveal_winner_errors() {
+    let mut test = RaffleTest::start_initialized().await;
+    let (_, _, empty_raffle) = test.create_default_raffle(raffle_params()).await;
+    let (_, _, raffle) = test.create_default_raffle(raffle_params()).await;
+    let buyer = test.new_wallet(10 * LAMPORTS_PER_SOL);
+    test.buy_tickets(&buyer, &raffle, 3).await.unwrap();
+
+    let result = test.reveal_winner(&raffle).await;
+    assert_raffle_error(result, RaffleError::RaffleNotEnded);
+
+    test.set_timestamp(NOW + 2 * DAY).await;
+    let result = test.reveal_winner(&empty_raffle).await;
+    assert_raffle_error(result, RaffleError::InvalidRevealedData);
+
+    test.set_raffle_frozen(&raffle, true).await.unwrap();
+    let result = test.reveal_winner(&raffle).await;
+    assert_raffle_error(result, RaffleError::RaffleFrozen);
+    test.set_raffle_frozen(&raffle, false).await.unwrap();
+
+    test.reveal_winner(&raffle).await.unwrap();
+    let result = test.reveal_winner(&raffle).await;
+    assert_raffle_error(result, RaffleError::WinnersAlreadyDrawn);
+}
+
+#[tokio::test]
+async fn reveal_winner_and_claim_reward() {
+    let mut test = RaffleTest::start_initialized().await;
+    let (_, nft, raffle) = test.create_default_raffle(raffle_params()).await;
+    let alice = test.new_wallet(10 * LAMPORTS_PER_SOL);
+    let bob = test.new_wallet(10 * LAMPORTS_PER_SOL);
+    test.buy_tickets(&alice, &raffle, 3).await.unwrap();
+    test.buy_tickets(&bob, &raffle, 4).await.unwrap();
+    test.buy_tickets(&alice, &raffle, 2).await.unwrap();
+
+    let state = test.raffle_state(&raffle).await;
+    assert_eq!(state.count, 9);
+    assert_eq!(state.no_repeat, 2);
+
+    let reveal_time = NOW + 2 * DAY + 30;
+    test.set_timestamp(reveal_time).await;
+    test.reveal_winner(&raffle).await.unwrap();
+
+    let state = test.raffle_state(&raffle).await;
+    let winner_index = draw_random(reveal_time, 9);
+    assert_eq!(state.claimed, 2);
+    assert_eq!(state.winner_index, winner_index);
+    assert_eq!(state.winner, state.entrants[winner_index as usize]);
+
+    let (winner, loser) = if state.winner == alice.pubkey() {
+        (alice, bob)
+    } else {
+        (bob, alice)
+    };
+    let result = test.claim_reward(&loser, &raffle).await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::NotWinner);
+
+    // the prize stays in the escrow while the raffle is frozen
+    test.set_raffle_frozen(&raffle, true).await.unwrap();
+    let result = test.claim_reward(&winner, &raffle).await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::RaffleFrozen);
+    test.set_raffle_frozen(&raffle, false).await.unwrap();
+
+    let winner_balance = test.balance(&winner.pubkey()).await;
+    let winner_nft = test.claim_reward(&winner, &raffle).await.unwrap();
+    assert_eq!(test.token_amount(&winner_nft).await, 1);
+    assert_eq!(
+        test.token_amount(&escrow_token_account(&raffle, &nft.mint))
+            .await,
+        0
+    );
+    // the winner moved the NFT out of the escrow so they receive the settle bounty
+    assert_eq!(
+        test.balance(&winner.pubkey()).await,
+        winner_balance + SETTLE_BOUNTY
+    );
+    let state = test.raffle_state(&raffle).await;
+    assert_eq!(state.claimed, 1);
+    assert_eq!(state.settle_bounty, 0);
+
+    // the escrow is empty so the prize can't be claimed twice
+    assert!(test.claim_reward(&winner, &raffle).await.is_err());
+}
+
+#[tokio::test]
+async fn instant_win_draws_the_winner_when_sold_out() {
+    let mut test = RaffleTest::start_initialized().await;
+    test.set_claim_window(DAY).await.unwrap();
+    let (_, nft, raffle) = test
+        .create_default_raffle(CreateRaffleParams {
+            max_entrants: 3,
+            instant_win: true,
+            ..raffle_params()
+        })
+        .await;
+    let alice = test.new_wallet(10 * LAMPORTS_PER_SOL);
+    let bob = test.new_wallet(10 * LAMPORTS_PER_SOL);
+    let carol = test.new_wallet(10 * LAMPORTS_PER_SOL);
+
+    let result = test.buy_tickets(&alice, &raffle, 0).await;
+    assert_raffle_error(result, RaffleError::InvalidTicketAmount);
+    test.buy_tickets(&alice, &raffle, 2).await.unwrap();
+    assert_eq!(test.raffle_state(&raffle).await.claimed, 0);
+
+    test.buy_tickets(&bob, &raffle, 1).await.unwrap();
+    let state = test.raffle_state(&raffle).await;
+    let winner_index = draw_random(NOW, 3);
+    assert_eq!(state.claimed, 2);
+    assert_eq!(state.winner_index, winner_index);
+    assert_eq!(state.winner, state.entrants[winner_index as usize]);
+    assert_eq!(state.claim_deadline, NOW + DAY);
+
+    // the raffle is drawn so no more tickets are sold, even zero of them
+    let result = test.buy_tickets(&carol, &raffle, 1).await;
+    assert_raffle_error(result, RaffleError::NotEnoughTicketsLeft);
+    let result = test.buy_tickets(&carol, &raffle, 0).await;
+    assert_raffle_error(result, RaffleError::InvalidTicketAmount);
+    let state = test.raffle_state(&raffle).await;
+    assert_eq!(state.count, 3);
+    assert_eq!(state.winner_index, winner_index);
+
+    // the winner claims before the end of the raffle
+    let winner = if state.winner == alice.pubkey() {
+        alice
+    } else {
+        bob
+    };
+    let winner_nft = test.claim_reward(&winner, &raffle).await.unwrap();
+    assert_eq!(test.token_amount(&winner_nft).await, 1);
+    assert_eq!(
+        test.token_amount(&escrow_token_account(&raffle, &nft.mint))
+            .await,
+        0
+    );
+    assert_eq!(test.raffle_state(&raffle).await.claimed, 1);
+
+    test.set_timestamp(NOW + 2 * DAY).await;
+    let result = test.reveal_winner(&raffle).await;
+    assert_raffle_error(result, RaffleError::WinnersAlreadyDrawn);
+}
+
+#[tokio::test]
+async fn sold_out_raffles_without_instant_win_wait_for_the_end() {
+    let mut test = RaffleTest::start_initialized().await;
+    let (_, _, raffle) = test
+        .create_default_raffle(CreateRaffleParams {
+            max_entrants: 2,
+            ..raffle_params()
+        })
+        .await;
+    let buyer = test.new_wallet(10 * LAMPORTS_PER_SOL);
+    test.buy_tickets(&buyer, &raffle, 2).await.unwrap();
+    assert_eq!(test.raffle_state(&raffle).await.claimed, 0);
+
+    let result = test.claim_reward(&buyer, &raffle).await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::RaffleNotEnded);
+    let result = test.reveal_winner(&raffle).await;
+    assert_raffle_error(result, RaffleError::RaffleNotEnded);
+}
+
+#[tokio::test]
+async fn settle_raffle_delivers_the_prize_and_pays_the_bounty() {
+    let mut test = RaffleTest::start_initialized().await;
+    let (_, nft, raffle) = test.create_default_raffle(raffle_params()).await;
+    let buyer = test.new_wallet(10 * LAMPORTS_PER_SOL);
+    test.buy_tickets(&buyer, &raffle, 2).await.unwrap();
+    test.set_timestamp(NOW + 2 * DAY).await;
+    test.reveal_winner(&raffle).await.unwrap();
+
+    // anyone can settle, the cranker pays the winner's ATA and receives the bounty
+    let cranker = test.new_wallet(LAMPORTS_PER_SOL);
+    let cranker_balance = test.balance(&cranker.pubkey()).await;
+    let winner_nft = test
+        .settle_raffle(&cranker, &raffle, &buyer.pubkey())
+        .await
+        .unwrap();
+    assert_eq!(test.token_amount(&winner_nft).await, 1);
+    assert_eq!(
+        test.token_amount(&escrow_token_account(&raffle, &nft.mint))
+            .await,
+        0
+    );
+    let ata_rent = Rent::default().minimum_balance(spl_token::state::Account::LEN);
+    assert_eq!(
+        test.balance(&cranker.pubkey()).await,
+        cranker_balance - ata_rent + SETTLE_BOUNTY
+    );
+    let state = test.raffle_state(&raffle).await;
+    assert_eq!(state.claimed, 1);
+    assert_eq!(state.settle_bounty, 0);
+
+    let result = test.settle_raffle(&cranker, &raffle, &buyer.pubkey()).await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::WinnerNotDrawn);
+}
+
+#[tokio::test]
+async fn settle_raffle_errors() {
+    let mut test = RaffleTest::start_initialized().await;
+    let (_, nft, raffle) = test.create_default_raffle(raffle_params()).await;
+    let alice = test.new_wallet(10 * LAMPORTS_PER_SOL);
+    let bob = test.new_wallet(10 * LAMPORTS_PER_SOL);
+    test.buy_tickets(&alice, &raffle, 1).await.unwrap();
+    test.buy_tickets(&bob, &raffle, 1).await.unwrap();
+    let cranker = test.new_wallet(LAMPORTS_PER_SOL);
+
+    // no winner is stored before the draw
+    let result = test
+        .settle_raffle(&cranker, &raffle, &Pubkey::default())
+        .await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::WinnerNotDrawn);
+
+    test.set_timestamp(NOW + 2 * DAY).await;
+    test.reveal_winner(&raffle).await.unwrap();
+    let (winner, loser) = if test.raffle_state(&raffle).await.winner == alice.pubkey() {
+        (alice, bob)
+    } else {
+        (bob, alice)
+    };
+
+    // the prize only goes to the drawn winner
+    assert!(test
+        .settle_raffle(&cranker, &raffle, &loser.pubkey())
+        .await
+        .is_err());
+
+    test.set_raffle_frozen(&raffle, true).await.unwrap();
+    let result = test
+        .settle_raffle(&cranker, &raffle, &winner.pubkey())
+        .await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::RaffleFrozen);
+    test.set_raffle_frozen(&raffle, false).await.unwrap();
+
+    // an existing winner's ATA is used as it is
+    let winner_nft = get_associated_token_address(&winner.pubkey(), &nft.mint);
+    test.set_token_account(&winner_nft, &nft.mint, &winner.pubkey(), 0);
+    test.settle_raffle(&cranker, &raffle, &winner.pubkey())
+        .await
+        .unwrap();
+    assert_eq!(test.token_amount(&winner_nft).await, 1);
+
+    // the winner can't claim the prize again
+    let result = test.claim_reward(&winner, &raffle).await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn reroll_winner_draws_another_winner_after_the_claim_deadline() {
+    let mut test = RaffleTest::start_initialized().await;
+    test.set_claim_window(DAY).await.unwrap();
+    let (_, _, raffle) = test.create_default_raffle(raffle_params()).await;
+    let alice = test.new_wallet(10 * LAMPORTS_PER_SOL);
+    let bob = test.new_wallet(10 * LAMPORTS_PER_SOL);
+    test.buy_tickets(&alice, &raffle, 1).await.unwrap();
+    test.buy_tickets(&bob, &raffle, 1).await.unwrap();
+
+    let reveal_time = NOW + 2 * DAY;
+    test.set_timestamp(reveal_time).await;
+    test.reveal_winner(&raffle).await.unwrap();
+    let state = test.raffle_state(&raffle).await;
+    assert_eq!(state.claim_deadline, reveal_time + DAY);
+    let (first_winner, second_winner) = if state.winner == alice.pubkey() {
+        (alice, bob)
+    } else {
+        (bob, alice)
+    };
+
+    let caller = test.new_wallet(LAMPORTS_PER_SOL);
+    test.set_timestamp(reveal_time + DAY).await;
+    let result = test.reroll_winner(&caller, &raffle).await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::ClaimDeadlineNotPassed);
+
+    let reroll_time = reveal_time + DAY + 1;
+    test.set_timestamp(reroll_time).await;
+    test.reroll_winner(&caller, &raffle).await.unwrap();
+    let state = test.raffle_state(&raffle).await;
+    assert_eq!(state.claimed, 2);
+    assert_eq!(state.winner, second_winner.pubkey());
+    assert_eq!(state.claim_deadline, reroll_time + DAY);
+    assert_eq!(state.active_tickets(), 1);
+
+    // the first winner lost the prize, the new winner claims it before the new deadline
+    let result = test.claim_reward(&first_winner, &raffle).await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::NotWinner);
+    let result = test.reroll_winner(&caller, &raffle).await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::ClaimDeadlineNotPassed);
+    let winner_nft = test.claim_reward(&second_winner, &raffle).await.unwrap();
+    assert_eq!(test.token_amount(&winner_nft).await, 1);
+}
+
+#[tokio::test]
+async fn reroll_winner_returns_the_unclaimed_nft() {
+    let mut test = RaffleTest::start_initialized().await;
+    test.set_claim_window(DAY).await.unwrap();
+    let (_, nft, raffle) = test
+        .create_default_raffle(CreateRaffleParams {
+            return_unclaimed: true,
+            ..raffle_params()
+        })
+        .await;
+    let alice = test.new_wallet(10 * LAMPORTS_PER_SOL);
+    let bob = test.new_wallet(10 * LAMPORTS_PER_SOL);
+    test.buy_tickets(&alice, &raffle, 1).await.unwrap();
+    test.buy_tickets(&bob, &raffle, 1).await.unwrap();
+    test.set_timestamp(NOW + 2 * DAY).await;
+    test.reveal_winner(&raffle).await.unwrap();
+
+    // other tickets are left but the raffle returns unclaimed prizes
+    test.set_timestamp(NOW + 3 * DAY + 1).await;
+    let caller = test.new_wallet(LAMPORTS_PER_SOL);
+    let caller_balance = test.balance(&caller.pubkey()).await;
+    let creator_nft = test.reroll_winner(&caller, &raffle).await.unwrap();
+    assert_eq!(test.token_amount(&creator_nft).await, 1);
+    assert_eq!(
+        test.token_amount(&escrow_token_account(&raffle, &nft.mint))
+            .await,
+        0
+    );
+    assert_eq!(
+        test.balance(&caller.pubkey()).await,
+        caller_balance + SETTLE_BOUNTY
+    );
+    let state = test.raffle_state(&raffle).await;
+    assert_eq!(state.claimed, 3);
+    assert_eq!(state.winner, Pubkey::default());
+
+    let result = test.reroll_winner(&caller, &raffle).await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::WinnerNotDrawn);
+}
+
+#[tokio::test]
+async fn reroll_winner_errors() {
+    let mut test = RaffleTest::start_initialized().await;
+    let result = test.set_claim_window(-1).await;
+    assert_raffle_error(result, RaffleError::InvalidClaimWindow);
+
+    let (_, _, raffle) = test.create_default_raffle(raffle_params()).await;
+    let buyer = test.new_wallet(10 * LAMPORTS_PER_SOL);
+    test.buy_tickets(&buyer, &raffle, 1).await.unwrap();
+    let caller = test.new_wallet(LAMPORTS_PER_SOL);
+
+    let result = test.reroll_winner(&caller, &raffle).await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::WinnerNotDrawn);
+
+    // without a claim window the winner has no deadline
+    test.set_timestamp(NOW + 2 * DAY).await;
+    test.reveal_winner(&raffle).await.unwrap();
+    assert_eq!(test.raffle_state(&raffle).await.claim_deadline, 0);
+    test.set_timestamp(NOW + 30 * DAY).await;
+    let result = test.reroll_winner(&caller, &raffle).await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::ClaimDeadlineNotPassed);
+
+    test.set_claim_window(DAY).await.unwrap();
+    let (_, _, raffle) = test
+        .create_default_raffle(CreateRaffleParams {
+            end_timestamp: NOW + 32 * DAY,
+            ..raffle_params()
+        })
+        .await;
+    test.buy_tickets(&buyer, &raffle, 1).await.unwrap();
+    test.set_timestamp(NOW + 32 * DAY).await;
+    test.reveal_winner(&raffle).await.unwrap();
+    test.set_timestamp(NOW + 34 * DAY).await;
+    test.set_raffle_frozen(&raffle, true).await.unwrap();
+    let result = test.reroll_winner(&caller, &raffle).await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::RaffleFrozen);
+
+    // the only buyer didn't claim so the NFT goes back to the creator
+    test.set_raffle_frozen(&raffle, false).await.unwrap();
+    let creator_nft = test.reroll_winner(&caller, &raffle).await.unwrap();
+    assert_eq!(test.token_amount(&creator_nft).await, 1);
+    assert_eq!(test.raffle_state(&raffle).await.claimed, 3);
+}
+
+#[tokio::test]
+async fn claim_reward_errors() {
+    let mut test = RaffleTest::start_initialized().await;
+    let (_, _, raffle) = test.create_default_raffle(raffle_params()).await;
+    let buyer = test.new_wallet(10 * LAMPORTS_PER_SOL);
+    test.buy_tickets(&buyer, &raffle, 1).await.unwrap();
+
+    let result = test.claim_reward(&buyer, &raffle).await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::RaffleNotEnded);
+
+    // nobody won before the draw
+    test.set_timestamp(NOW + 2 * DAY).await;
+    let result = test.claim_reward(&buyer, &raffle).await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::NotWinner);
+
+    test.set_raffle_frozen(&raffle, true).await.unwrap();
+    test.force_cancel_raffle(&raffle).await.unwrap();
+    let result = test.claim_reward(&buyer, &raffle).await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::RaffleCancelled);
+}
+
+#[tokio::test]
+async fn force_cancel_raffle_returns_the_nft_and_deposits_the_refunds() {
+    let mut test = RaffleTest::start_initialized().await;
+    let (_, nft, raffle) = test.create_default_raffle(raffle_params()).await;
+    let buyer = test.new_wallet(10 * LAMPORTS_PER_SOL);
+    test.buy_tickets(&buyer, &raffle, 3).await.unwrap();
+
+    let result = test.force_cancel_raffle(&raffle).await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::RaffleNotFrozen);
+
+    test.set_raffle_frozen(&raffle, true).await.unwrap();
+    let admin = test.admin.pubkey();
+    let admin_balance = test.balance(&admin).await;
+    let raffle_balance = test.balance(&raffle).await;
+    let creator_nft = test.force_cancel_raffle(&raffle).await.unwrap();
+
+    assert_eq!(test.token_amount(&creator_nft).await, 1);
+    assert_eq!(
+        test.token_amount(&escrow_token_account(&raffle, &nft.mint))
+            .await,
+        0
+    );
+    // the admin deposits the ticket payments and receives the settle bounty
+    assert_eq!(
+        test.balance(&raffle).await,
+        raffle_balance + 3 * TICKET_PRICE - SETTLE_BOUNTY
+    );
+    assert_eq!(
+        test.balance(&admin).await,
+        admin_balance - 3 * TICKET_PRICE + SETTLE_BOUNTY
+    );
+    let state = test.raffle_state(&raffle).await;
+    assert_eq!(state.claimed, 4);
+    assert_eq!(state.settle_bounty, 0);
+
+    let result = test.force_cancel_raffle(&raffle).await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::RaffleCancelled);
+    let result = test.set_raffle_frozen(&raffle, false).await;
+    assert_raffle_error(result, RaffleError::RaffleCancelled);
+}
+
+#[tokio::test]
+async fn claim_refund_returns_the_sol_paid() {
+    let mut test = RaffleTest::start_initialized().await;
+    let (_, _, raffle) = test
+        .create_default_raffle(CreateRaffleParams {
+            discounts: vec![DiscountTier {
+                min_tickets: 4,
+                discount_percent: 50,
+            }],
+            ..raffle_params()
+        })
+        .await;
+    let full_price_buyer = test.new_wallet(10 * LAMPORTS_PER_SOL);
+    let discount_buyer = test.new_wallet(10 * LAMPORTS_PER_SOL);
+    test.buy_tickets(&full_price_buyer, &raffle, 1)
+        .await
+        .unwrap();
+    test.buy_tickets(&full_price_buyer, &raffle, 1)
+        .await
+        .unwrap();
+    test.buy_tickets(&discount_buyer, &raffle, 4).await.unwrap();
+    assert_eq!(
+        test.receipt_state(&raffle, &full_price_buyer.pubkey())
+            .await
+            .paid,
+        2 * TICKET_PRICE
+    );
+    assert_eq!(
+        test.receipt_state(&raffle, &discount_buyer.pubkey())
+            .await
+            .paid,
+        2 * TICKET_PRICE
+    );
+
+    let result = test.claim_refund(&full_price_buyer, &raffle).await;
+    assert_raffle_error(result, RaffleError::RaffleNotCancelled);
+
+    test.set_raffle_frozen(&raffle, true).await.unwrap();
+    test.force_cancel_raffle(&raffle).await.unwrap();
+
+    // each buyer gets what they paid back with the rent of the closed receipt
+    let receipt_rent = Rent::default().minimum_balance(TicketReceipt::LEN);
+    for buyer in [&full_price_buyer, &discount_buyer] {
+        let balance = test.balance(&buyer.pubkey()).await;
+        test.claim_refund(buyer, &raffle).await.unwrap();
+        assert_eq!(
+            test.balance(&buyer.pubkey()).await,
+            balance + 2 * TICKET_PRICE + receipt_rent
+        );
+        let receipt = receipt_address(&raffle, &buyer.pubkey());
+        assert!(test.get_account(&receipt).await.is_none());
+        assert!(test.claim_refund(buyer, &raffle).await.is_err());
+    }
+    assert_eq!(test.raffle_state(&raffle).await.active_tickets(), 0);
+
+    // nobody else paid for tickets
+    let stranger = test.new_wallet(LAMPORTS_PER_SOL);
+    assert!(test.claim_refund(&stranger, &raffle).await.is_err());
+}
+
+#[tokio::test]
+async fn claim_refund_of_free_tickets() {
+    let mut test = RaffleTest::start_initialized().await;
+    let (_, _, raffle) = test
+        .create_default_raffle(CreateRaffleParams {
+            ticket_price_sol: 0,
+            ..raffle_params()
+        })
+        .await;
+    let buyer = test.new_wallet(LAMPORTS_PER_SOL);
+    test.buy_tickets(&buyer, &raffle, 2).await.unwrap();
+    assert_eq!(test.receipt_state(&raffle, &buyer.pubkey()).await.paid, 0);
+
+    test.set_raffle_frozen(&raffle, true).await.unwrap();
+    test.force_cancel_raffle(&raffle).await.unwrap();
+    let result = test.claim_refund(&buyer, &raffle).await;
+    assert_raffle_error(result, RaffleError::NoRefund);
+}
+
+#[tokio::test]
+async fn withdraw_nft_without_tickets() {
+    let mut test = RaffleTest::start_initialized().await;
+    let (creator, nft, raffle) = test.create_default_raffle(raffle_params()).await;
+    let stranger = test.new_wallet(LAMPORTS_PER_SOL);
+
+    let result = test.withdraw_nft(&creator, &raffle).await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::RaffleNotEnded);
+
+    test.set_timestamp(NOW + DAY).await;
+    let result = test.withdraw_nft(&stranger, &raffle).await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::NotCreator);
+
+    let creator_nft = test.withdraw_nft(&creator, &raffle).await.unwrap();
+    assert_eq!(test.token_amount(&creator_nft).await, 1);
+    assert_eq!(
+        test.token_amount(&escrow_token_account(&raffle, &nft.mint))
+            .await,
+        0
+    );
+    assert_eq!(test.raffle_state(&raffle).await.claimed, 3);
+
+    let result = test.withdraw_nft(&creator, &raffle).await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::NoPrize);
+    // nobody can buy the tickets of the withdrawn raffle before its end
+    let buyer = test.new_wallet(10 * LAMPORTS_PER_SOL);
+    let result = test.buy_tickets(&buyer, &raffle, 1).await;
+    assert_raffle_error(result, RaffleError::WinnersAlreadyDrawn);
+}
+
+#[tokio::test]
+async fn withdraw_nft_rejects_token_accounts_of_other_programs() {
+    let mut test = RaffleTest::start_initialized().await;
+    let (creator, nft, raffle) = test.create_default_raffle(raffle_params()).await;
+    test.set_timestamp(NOW + DAY).await;
+
+    // the layout of a token account owned by another program
+    let fake_nft_token_account = Pubkey::new_unique();
+    test.set_token_account(&fake_nft_token_account, &nft.mint, &creator.pubkey(), 0);
+    let mut account = test.get_account(&fake_nft_token_account).await.unwrap();
+    account.owner = Pubkey::new_unique();
+    test.set_account(&fake_nft_token_account, account);
+
+    let instruction = Instruction {
+        program_id: raffle::id(),
+        accounts: raffle::accounts::WithdrawNft {
+            claimer: creator.pubkey(),
+            raffle,
+            escrow_authority: escrow_authority(&raffle),
+            claimer_nft_token_account: fake_nft_token_account,
+            src_nft_token_account: escrow_token_account(&raffle, &nft.mint),
+            nft_mint_address: nft.mint,
+            token_program: spl_token::id(),
+        }
+        .to_account_metas(None),
+        data: raffle::instruction::WithdrawNft {}.data(),
+    };
+    let result = test.process(&[instruction], &[&creator]).await;
+    assert_anchor_error(result, ErrorCode::AccountOwnedByWrongProgram);
+    assert_eq!(
+        test.token_amount(&escrow_token_account(&raffle, &nft.mint))
+            .await,
+        1
+    );
+}
+
+#[tokio::test]
+async fn withdraw_nft_waits_for_the_scheduled_start() {
+    let mut test = RaffleTest::start_initialized().await;
+    let (creator, _, raffle) = test
+        .create_default_raffle(CreateRaffleParams {
+            start_timestamp: NOW + DAY,
+            end_timestamp: NOW + 3 * DAY,
+            ..raffle_params()
+        })
+        .await;
+
+    // one day after the creation isn't one day after the start
+    test.set_timestamp(NOW + DAY).await;
+    let result = test.withdraw_nft(&creator, &raffle).await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::RaffleNotEnded);
+    test.set_timestamp(NOW + 2 * DAY - 1).await;
+    let result = test.withdraw_nft(&creator, &raffle).await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::RaffleNotEnded);
+
+    // the raffle still runs but nobody bought a ticket in its first day
+    test.set_timestamp(NOW + 2 * DAY).await;
+    test.withdraw_nft(&creator, &raffle).await.unwrap();
+}
+
+#[tokio::test]
+async fn withdraw_nft_of_frozen_or_cancelled_raffles() {
+    let mut test = RaffleTest::start_initialized().await;
+    let (creator, _, raffle) = test.create_default_raffle(raffle_params()).await;
+    test.set_raffle_frozen(&raffle, true).await.unwrap();
+
+    test.set_timestamp(NOW + 2 * DAY).await;
+    let result = test.withdraw_nft(&creator, &raffle).await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::RaffleFrozen);
+
+    // the cancellation already returned the NFT
+    test.force_cancel_raffle(&raffle).await.unwrap();
+    let result = test.withdraw_nft(&creator, &raffle).await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::RaffleCancelled);
+}
+
+#[tokio::test]
+async fn withdraw_nft_with_other_entrants() {
+    let mut test = RaffleTest::start_initialized().await;
+    let (creator, _, raffle) = test.create_default_raffle(raffle_params()).await;
+    let buyer = test.new_wallet(10 * LAMPORTS_PER_SOL);
+    test.buy_tickets(&buyer, &raffle, 1).await.unwrap();
+
+    test.set_timestamp(NOW + 2 * DAY).await;
+    let result = test.withdraw_nft(&creator, &raffle).await;
+    assert_raffle_error(result.map(|_| ()), RaffleError::OtherEntrants);
+}
+
+#[tokio::test]
+async fn migrate_raffle_remaps_the_baseline_layout() {
+    let mut test = RaffleTest::start_initialized().await;
+    let creator = test.new_wallet(10 * LAMPORTS_PER_SOL);
+    let nft = test.create_collection_nft(&creator.pubkey());
+    let buyers = [
+        test.new_wallet(10 * LAMPORTS_PER_SOL),
+        test.new_wallet(10 * LAMPORTS_PER_SOL),
+    ];
+    let entrants = [buyers[0].pubkey(), buyers[1].pubkey(), buyers[0].pubkey()];
+    let raffle = test.set_baseline_raffle(&creator.pubkey(), &nft.mint, &entrants, 0);
+    assert_eq!(RafflePool::LEGACY_LEN, 64168);
+
+    // the instructions reject the raffle until it's migrated instead of failing to load it
+    let instruction = test.buy_tickets_ix(&buyers[1].pubkey(), &raffle, &creator.pubkey(), 1, &[]);
+    let result = test.process(&[instruction], &[&buyers[1]]).await;
+    assert_raffle_error(result, RaffleError::AccountNotMigrated);
+
+    let payer = test.new_wallet(LAMPORTS_PER_SOL);
+    test.migrate_raffle(&payer, &raffle, &nft.mint)
+        .await
+        .unwrap();
+    let account = test.get_account(&raffle).await.unwrap();
+    assert_eq!(account.data.len(), RafflePool::LEN);
+    assert_eq!(
+        account.lamports,
+        Rent::default().minimum_balance(RafflePool::LEN)
+    );
+    let state = test.raffle_state(&raffle).await;
+    assert_eq!(state.version, RAFFLE_VERSION);
+    assert_eq!(state.creator, creator.pubkey());
+    assert_eq!(state.nft_mint, nft.mint);
+    assert_eq!(state.count, 3);
+    assert_eq!(state.no_repeat, 2);
+    assert_eq!(state.max_entrants, 100);
+    assert_eq!(state.start_timestamp, NOW);
+    assert_eq!(state.end_timestamp, NOW + DAY);
+    assert_eq!(state.ticket_price_sol, TICKET_PRICE);
+    assert_eq!(state.claimed, 0);
+    assert_eq!(state.winner, Pubkey::default());
+    // the first release kept no receipts to refund the tickets from
+    assert_eq!(state.total_paid, 0);
+    assert_eq!(state.payment_mode, PAYMENT_SOL);
+    assert_eq!(state.discount_count, 0);
+    assert_eq!(state.royalty_count, 0);
+    assert_eq!(state.settle_bounty, 0);
+    assert_eq!(state.nonce, 0);
+    assert_eq!(state.entrants[..3], entrants);
+    assert!(state.entrants[3..]
+        .iter()
+        .all(|entrant| *entrant == Pubkey::default()));
+    // the NFT moved to the escrow
+    assert_eq!(
+        test.token_amount(&legacy_nft_token_account(&nft.mint))
+            .await,
+        0
+    );
+    assert_eq!(
+        test.token_amount(&escrow_token_account(&raffle, &nft.mint))
+            .await,
+        1
+    );
+
+    // the migrated raffle keeps working up to the prize
+    test.buy_tickets(&buyers[1], &raffle, 1).await.unwrap();
+    assert_eq!(test.raffle_state(&raffle).await.count, 4);
+    test.set_timestamp(NOW + DAY).await;
+    test.reveal_winner(&raffle).await.unwrap();
+    let state = test.raffle_state(&raffle).await;
+    let winner = buyers
+        .iter()
+        .find(|buyer| buyer.pubkey() == state.winner)
+        .unwrap();
+    let winner_nft = test.claim_reward(winner, &raffle).await.unwrap();
+    assert_eq!(test.token_amount(&winner_nft).await, 1);
+
+    let result = test.migrate_raffle(&payer, &raffle, &nft.mint).await;
+    assert_raffle_error(result, RaffleError::AlreadyMigrated);
+}
+
+#[tokio::test]
+async fn migrate_raffle_checks_the_nft_accounts() {
+    let mut test = RaffleTest::start_initialized().await;
+    let creator = test.new_wallet(10 * LAMPORTS_PER_SOL);
+    let nft = test.create_collection_nft(&creator.pubkey());
+    let buyer = test.new_wallet(10 * LAMPORTS_PER_SOL);
+    let payer = test.new_wallet(LAMPORTS_PER_SOL);
+
+    // a drawn raffle still holds the NFT, it must come from the global authority
+    let raffle = test.set_baseline_raffle(&creator.pubkey(), &nft.mint, &[buyer.pubkey()], 2);
+    test.set_token_account(
+        &legacy_nft_token_account(&nft.mint),
+        &nft.mint,
+        &buyer.pubkey(),
+        1,
+    );
+    let result = test.migrate_raffle(&payer, &raffle, &nft.mint).await;
+    assert_raffle_error(result, RaffleError::InvalidNftTokenAccount);
+
+    // a claimed raffle has no NFT left to move
+    let raffle = test.set_baseline_raffle(&creator.pubkey(), &nft.mint, &[buyer.pubkey()], 1);
+    test.migrate_raffle(&payer, &raffle, &nft.mint)
+        .await
+        .unwrap();
+    let state = test.raffle_state(&raffle).await;
+    assert_eq!(state.claimed, 1);
+    assert_eq!(state.winner, buyer.pubkey());
+    assert_eq!(
+        test.token_amount(&escrow_token_account(&raffle, &nft.mint))
+            .await,
+        0
+    );
+
+    // a drawn raffle stayed drawn after its winner claimed the NFT
+    let raffle = test.set_baseline_raffle(&creator.pubkey(), &nft.mint, &[buyer.pubkey()], 2);
+    test.set_token_account(
+        &legacy_nft_token_account(&nft.mint),
+        &nft.mint,
+        &global_authority().0,
+        0,
+    );
+    test.migrate_raffle(&payer, &raffle, &nft.mint)
+        .await
+        .unwrap();
+    let state = test.raffle_state(&raffle).await;
+    assert_eq!(state.claimed, 2);
+    assert_eq!(state.winner, buyer.pubkey());
+    assert_eq!(
+        test.token_amount(&escrow_token_account(&raffle, &nft.mint))
+            .await,
+        0
+    );
+
+    // a raffle of the current layout isn't migrated twice
+    let (_, nft, raffle) = test.create_default_raffle(raffle_params()).await;
+    let result = test.migrate_raffle(&payer, &raffle, &nft.mint).await;
+    assert_raffle_error(result, RaffleError::AlreadyMigrated);
+}
+
+#[tokio::test]
+async fn migrate_raffle_rejects_unknown_layouts() {
+    let mut test = RaffleTest::start_initialized().await;
+    let payer = test.new_wallet(LAMPORTS_PER_SOL);
+    let nft_mint = Pubkey::new_unique();
+
+    // a raffle discriminator with neither the legacy nor the current length
+    let mut data = RafflePool::discriminator().to_vec();
+    data.resize(RafflePool::LEGACY_LEN - 32, 0);
+    let raffle = Pubkey::new_unique();
+    test.set_account(
+        &raffle,
+        Account {
+            lamports: Rent::default().minimum_balance(data.len()),
+            data,
+            owner: raffle::id(),
+            ..Account::default()
+        },
+    );
+    let result = test.migrate_raffle(&payer, &raffle, &nft_mint).await;
+    assert_raffle_error(result, RaffleError::InvalidAccountLayout);
+
+    // another account of the program isn't a raffle
+    let mut data = CollectionPool::discriminator().to_vec();
+    data.resize(RafflePool::LEGACY_LEN, 0);
+    let collection = Pubkey::new_unique();
+    test.set_account(
+        &collection,
+        Account {
+            lamports: Rent::default().minimum_balance(data.len()),
+            data,
+            owner: raffle::id(),
+            ..Account::default()
+        },
+    );
+    let result = test.migrate_raffle(&payer, &collection, &nft_mint).await;
+    assert_raffle_error(result, RaffleError::InvalidAccountLayout);
+}
+
+#[tokio::test]
+async fn force_cancel_raffle_of_a_migrated_raffle() {
+    let mut test = RaffleTest::start_initialized().await;
+    let creator = test.new_wallet(10 * LAMPORTS_PER_SOL);
+    let nft = test.create_collection_nft(&creator.pubkey());
+    let legacy_buyer = test.new_wallet(10 * LAMPORTS_PER_SOL);
+    let buyer = test.new_wallet(10 * LAMPORTS_PER_SOL);
+    let raffle = test.set_baseline_raffle(
+        &creator.pubkey(),
+        &nft.mint,
+        &[legacy_buyer.pubkey(), legacy_buyer.pubkey()],
+        0,
+    );
+    let payer = test.new_wallet(LAMPORTS_PER_SOL);
+    test.migrate_raffle(&payer, &raffle, &nft.mint)
+        .await
+        .unwrap();
+    test.buy_tickets(&buyer, &raffle, 1).await.unwrap();
+    let raffle_balance = test.balance(&raffle).await;
+
+    // only the tickets bought since the migration are deposited for the refunds
+    test.set_raffle_frozen(&raffle, true).await.unwrap();
+    let creator_nft = test.force_cancel_raffle(&raffle).await.unwrap();
+    assert_eq!(test.token_amount(&creator_nft).await, 1);
+    assert_eq!(test.balance(&raffle).await, raffle_balance + TICKET_PRICE);
+
+    let buyer_balance = test.balance(&buyer.pubkey()).await;
+    test.claim_refund(&buyer, &raffle).await.unwrap();
+    let receipt_rent = Rent::default().minimum_balance(TicketReceipt::LEN);
+    assert_eq!(
+        test.balance(&buyer.pubkey()).await,
+        buyer_balance + TICKET_PRICE + receipt_rent
+    );
+    assert_eq!(test.balance(&raffle).await, raffle_balance);
+    assert!(test.claim_refund(&legacy_buyer, &raffle).await.is_err());
+}
+
+#[tokio::test]
+async fn migrate_global_grows_the_baseline_global_authority() {
+    let mut test = RaffleTest::start().await;
+    test.set_baseline_global();
+
+    let other = test.new_wallet(LAMPORTS_PER_SOL);
+    let result = test.migrate_global(&other).await;
+    assert_raffle_error(result, RaffleError::NotAdmin);
+
+    let admin = clone_keypair(&test.admin);
+    test.migrate_global(&admin).await.unwrap();
+    let account = test.get_account(&global_authority().0).await.unwrap();
+    assert_eq!(account.data.len(), GlobalPool::LEN);
+    assert_eq!(
+        account.lamports,
+        Rent::default().minimum_balance(GlobalPool::LEN)
+    );
+    let global = test.global_state().await;
+    assert_eq!(global.super_admin, test.admin.pubkey());
+    assert_eq!(global.referral_share, 0);
+    assert!(!global.paused);
+    assert_eq!(global.min_duration, DAY);
+    assert_eq!(global.max_duration, 0);
+    assert_eq!(global.bump, global_authority().1);
+    // the instructions checking the stored bump accept the migrated account
+    test.set_claim_window(DAY).await.unwrap();
+    assert_eq!(test.global_state().await.claim_window, DAY);
+
+    let result = test.migrate_global(&admin).await;
+    assert_raffle_error(result, RaffleError::AlreadyMigrated);
+}
+
+#[tokio::test]
+async fn migrate_collection_is_admin_only() {
+    let mut test = RaffleTest::start_initialized().await;
+    assert_eq!(test.collection_state().await.version, COLLECTION_VERSION);
+    let collection = test.collection;
+    test.set_legacy_layout(&collection).await;
+    let collection_id = Pubkey::new_unique();
+    let result = test.add_collection(&collection_id).await;
+    assert_raffle_error(result, RaffleError::AccountNotMigrated);
+
+    let other = test.new_wallet(LAMPORTS_PER_SOL);
+    let result = test.migrate_collection(&other).await;
+    assert_raffle_error(result, RaffleError::NotAdmin);
+
+    let admin = clone_keypair(&test.admin);
+    test.migrate_collection(&admin).await.unwrap();
+    let state = test.collection_state().await;
+    assert_eq!(state.version, COLLECTION_VERSION);
+    assert_eq!(state.count, 1);
+    assert_eq!(state.collections[0], test.collection_id);
+}
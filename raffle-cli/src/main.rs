@@ -0,0 +1,580 @@
+//! Operates the raffle program from the command line, every command prints JSON,
+//! failures print `{"error": ...}` and exit with 1.
+//!
+//!     raffle-cli --keypair admin.json --url https://api.devnet.solana.com init
+//!     raffle-cli create --nft-mint <MINT> --price 0.1 --end 1700000000 --max-entrants 100
+//!     raffle-cli show --raffle <RAFFLE>
+
+mod output;
+
+use std::str::FromStr;
+
+use anchor_lang::Discriminator;
+use anchor_spl::associated_token::get_associated_token_address;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{ArgEnum, Parser, Subcommand};
+use metaplex_token_metadata::{
+    state::{Key, Metadata, MAX_METADATA_LEN},
+    utils::try_from_slice_checked,
+};
+use raffle_client::{
+    account::{CollectionPool, CreateRaffleParams, DiscountTier, RafflePool, RoyaltyShare},
+    constants::*,
+    instructions, pda, state,
+};
+use serde_json::{json, Value};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, MemcmpEncodedBytes, RpcFilterType},
+};
+use solana_sdk::{
+    bs58,
+    commitment_config::CommitmentConfig,
+    instruction::Instruction,
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair, Signature, Signer},
+    system_instruction,
+    transaction::Transaction,
+};
+use spl_associated_token_account::create_associated_token_account;
+
+use crate::output::*;
+
+#[derive(Parser)]
+#[clap(name = "raffle-cli", version, about = "Operates the raffle program")]
+struct Cli {
+    /// Keypair paying and signing the transactions [default: ~/.config/solana/id.json]
+    #[clap(long, short, global = true)]
+    keypair: Option<String>,
+    /// RPC URL of the cluster
+    #[clap(
+        long,
+        short,
+        global = true,
+        default_value = "https://api.devnet.solana.com"
+    )]
+    url: String,
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Allocate the collection pool and initialize the global authority
+    Init,
+    /// Whitelist a collection by the verified creator of its NFTs
+    AddCollection {
+        #[clap(long)]
+        collection_id: Pubkey,
+    },
+    /// Create a raffle of an NFT held by the keypair
+    Create {
+        #[clap(long)]
+        nft_mint: Pubkey,
+        #[clap(long, default_value_t = 0)]
+        nonce: u64,
+        /// Ticket price in SOL, or in tokens for burning raffles
+        #[clap(long)]
+        price: f64,
+        /// Start of the ticket sales, 0 starts now
+        #[clap(long, default_value_t = 0)]
+        start: i64,
+        #[clap(long)]
+        end: i64,
+        #[clap(long)]
+        max_entrants: u64,
+        /// Quantity discount as MIN_TICKETS:PERCENT, repeatable
+        #[clap(long = "discount", parse(try_from_str = parse_discount))]
+        discounts: Vec<DiscountTier>,
+        /// Pay the NFT's metaplex royalties from the creator share
+        #[clap(long)]
+        use_nft_creators: bool,
+        /// Custom royalty as RECIPIENT:BASIS_POINTS, repeatable
+        #[clap(long = "royalty", parse(try_from_str = parse_royalty))]
+        royalties: Vec<RoyaltyShare>,
+        #[clap(long, arg_enum, default_value = "sol")]
+        payment_mode: PaymentMode,
+        /// Token mint or NFT collection burnt for tickets
+        #[clap(long)]
+        burn_target: Option<Pubkey>,
+        /// Return the NFT to the creator when the winner doesn't claim it
+        #[clap(long)]
+        return_unclaimed: bool,
+        /// Draw the winner when the last ticket is sold
+        #[clap(long)]
+        instant_win: bool,
+    },
+    /// Buy tickets of a raffle with SOL
+    Buy {
+        #[clap(long)]
+        raffle: Pubkey,
+        #[clap(long)]
+        amount: u64,
+        #[clap(long)]
+        referrer: Option<Pubkey>,
+    },
+    /// Draw the winner of an ended raffle
+    Reveal {
+        #[clap(long)]
+        raffle: Pubkey,
+    },
+    /// Claim the NFT as the winner
+    Claim {
+        #[clap(long)]
+        raffle: Pubkey,
+    },
+    /// Withdraw the NFT as the creator
+    Withdraw {
+        #[clap(long)]
+        raffle: Pubkey,
+    },
+    /// Print the state of a raffle with its entrants
+    Show {
+        #[clap(long)]
+        raffle: Pubkey,
+    },
+    /// Print the global state and all raffles, optionally of one creator
+    List {
+        #[clap(long)]
+        creator: Option<Pubkey>,
+    },
+    /// Grow the global authority of the first release to the current layout as the admin
+    MigrateGlobal,
+    /// Convert a raffle created before the version header to the current layout
+    MigrateRaffle {
+        #[clap(long)]
+        raffle: Pubkey,
+    },
+    /// Convert the collection pool created before the version header as the admin
+    MigrateCollection,
+}
+
+#[derive(ArgEnum, Clone, Copy)]
+enum PaymentMode {
+    Sol,
+    BurnToken,
+    BurnNft,
+}
+
+impl PaymentMode {
+    fn value(self) -> u64 {
+        match self {
+            PaymentMode::Sol => PAYMENT_SOL,
+            PaymentMode::BurnToken => PAYMENT_BURN_TOKEN,
+            PaymentMode::BurnNft => PAYMENT_BURN_NFT,
+        }
+    }
+}
+
+fn parse_discount(arg: &str) -> Result<DiscountTier> {
+    let (min_tickets, discount_percent) = arg
+        .split_once(':')
+        .ok_or_else(|| anyhow!("expected MIN_TICKETS:PERCENT"))?;
+    Ok(DiscountTier {
+        min_tickets: min_tickets.parse()?,
+        discount_percent: discount_percent.parse()?,
+    })
+}
+
+fn parse_royalty(arg: &str) -> Result<RoyaltyShare> {
+    let (recipient, basis_points) = arg
+        .split_once(':')
+        .ok_or_else(|| anyhow!("expected RECIPIENT:BASIS_POINTS"))?;
+    Ok(RoyaltyShare {
+        recipient: Pubkey::from_str(recipient)?,
+        basis_points: basis_points.parse()?,
+    })
+}
+
+struct Client {
+    rpc: RpcClient,
+    payer: Keypair,
+}
+
+impl Client {
+    fn send(&self, instructions: &[Instruction]) -> Result<Signature> {
+        let blockhash = self.rpc.get_latest_blockhash()?;
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.payer.pubkey()),
+            &[&self.payer],
+            blockhash,
+        );
+        Ok(self.rpc.send_and_confirm_transaction(&transaction)?)
+    }
+
+    fn exists(&self, address: &Pubkey) -> Result<bool> {
+        Ok(self
+            .rpc
+            .get_account_with_commitment(address, self.rpc.commitment())?
+            .value
+            .is_some())
+    }
+
+    fn data_len(&self, address: &Pubkey) -> Result<usize> {
+        Ok(self
+            .rpc
+            .get_account_with_commitment(address, self.rpc.commitment())?
+            .value
+            .map_or(0, |account| account.data.len()))
+    }
+
+    fn raffle_state(&self, raffle: &Pubkey) -> Result<RafflePool> {
+        let data = self
+            .rpc
+            .get_account_data(raffle)
+            .with_context(|| format!("raffle {} not found", raffle))?;
+        Ok(state::raffle_pool(&data)?)
+    }
+
+    // only the raffles of the current and the first release layout are fetched
+    fn raffles(&self) -> Result<Vec<(Pubkey, RafflePool)>> {
+        let mut raffles = vec![];
+        for len in [RafflePool::LEN, RafflePool::LEGACY_LEN] {
+            let config = RpcProgramAccountsConfig {
+                filters: Some(vec![
+                    RpcFilterType::DataSize(len as u64),
+                    RpcFilterType::Memcmp(Memcmp {
+                        offset: 0,
+                        bytes: MemcmpEncodedBytes::Base58(
+                            bs58::encode(RafflePool::discriminator()).into_string(),
+                        ),
+                        encoding: None,
+                    }),
+                ]),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64Zstd),
+                    ..RpcAccountInfoConfig::default()
+                },
+                ..RpcProgramAccountsConfig::default()
+            };
+            for (address, account) in self
+                .rpc
+                .get_program_accounts_with_config(&raffle_client::ID, config)?
+            {
+                if let Ok(raffle_state) = state::raffle_pool(&account.data) {
+                    raffles.push((address, raffle_state));
+                }
+            }
+        }
+        Ok(raffles)
+    }
+
+    // the collection pool is allocated by the super admin in `init`
+    fn collection_state(&self) -> Result<(Pubkey, CollectionPool)> {
+        let global = state::global_pool(&self.rpc.get_account_data(&pda::global_authority().0)?)?;
+        let collection = pda::collection_pool(&global.super_admin)?;
+        let data = self.rpc.get_account_data(&collection)?;
+        Ok((collection, state::collection_pool(&data)?))
+    }
+
+    // the payer's ATA of the mint, created ahead when missing
+    fn token_account(&self, mint: &Pubkey, instructions: &mut Vec<Instruction>) -> Result<Pubkey> {
+        let owner = self.payer.pubkey();
+        let token_account = get_associated_token_address(&owner, mint);
+        if !self.exists(&token_account)? {
+            instructions.push(create_associated_token_account(&owner, &owner, mint));
+        }
+        Ok(token_account)
+    }
+}
+
+// the first verified creator of the NFT which is a whitelisted collection, like `create_raffle` matches it
+fn nft_collection(metadata: &Metadata, collection: &CollectionPool) -> Result<Pubkey> {
+    let collections = &collection.collections[..collection.count as usize];
+    metadata
+        .data
+        .creators
+        .iter()
+        .flatten()
+        .find(|creator| creator.verified && collections.contains(&creator.address))
+        .map(|creator| creator.address)
+        .ok_or_else(|| anyhow!("the NFT is not in the allowed collections"))
+}
+
+// SOL raffles are priced in SOL like the TS client does, burning raffles in whole tokens
+fn ticket_price(price: f64, payment_mode: u64) -> Result<u64> {
+    let price = if payment_mode == PAYMENT_SOL {
+        (price * LAMPORTS_PER_SOL as f64).round()
+    } else {
+        price
+    };
+    if !(0.0..=u64::MAX as f64).contains(&price) {
+        bail!("the ticket price is out of range");
+    }
+    if price.fract() != 0.0 {
+        bail!("burning raffles are priced in whole tokens or NFTs");
+    }
+    Ok(price as u64)
+}
+
+fn transaction_json(signature: Signature, extra: Value) -> Value {
+    let mut value = json!({ "signature": signature.to_string() });
+    if let (Some(value), Value::Object(extra)) = (value.as_object_mut(), extra) {
+        value.extend(extra);
+    }
+    value
+}
+
+fn run(client: &Client, command: Command) -> Result<Value> {
+    let payer = client.payer.pubkey();
+    match command {
+        Command::Init => {
+            let collection = pda::collection_pool(&payer)?;
+            let space = CollectionPool::LEN;
+            let lamports = client.rpc.get_minimum_balance_for_rent_exemption(space)?;
+            let signature = client.send(&[
+                system_instruction::create_account_with_seed(
+                    &payer,
+                    &collection,
+                    &payer,
+                    pda::COLLECTION_POOL_SEED,
+                    lamports,
+                    space as u64,
+                    &raffle_client::ID,
+                ),
+                instructions::initialize(&payer, &collection),
+            ])?;
+            Ok(transaction_json(
+                signature,
+                json!({
+                    "globalAuthority": pda::global_authority().0.to_string(),
+                    "collection": collection.to_string(),
+                }),
+            ))
+        }
+        Command::AddCollection { collection_id } => {
+            let collection = pda::collection_pool(&payer)?;
+            let signature = client.send(&[instructions::add_collection(
+                &payer,
+                &collection,
+                &collection_id,
+            )])?;
+            Ok(transaction_json(signature, json!({})))
+        }
+        Command::Create {
+            nft_mint,
+            nonce,
+            price,
+            start,
+            end,
+            max_entrants,
+            discounts,
+            use_nft_creators,
+            royalties,
+            payment_mode,
+            burn_target,
+            return_unclaimed,
+            instant_win,
+        } => {
+            let (collection, collection_state) = client.collection_state()?;
+            let metadata_data = client
+                .rpc
+                .get_account_data(&pda::metadata_address(&nft_mint))
+                .context("the NFT has no metadata")?;
+            let metadata: Metadata =
+                try_from_slice_checked(&metadata_data, Key::MetadataV1, MAX_METADATA_LEN)?;
+            let collection_id = nft_collection(&metadata, &collection_state)?;
+
+            let payment_mode = payment_mode.value();
+            if payment_mode != PAYMENT_SOL && burn_target.is_none() {
+                bail!("burning raffles need a --burn-target");
+            }
+            let ticket_price_sol = ticket_price(price, payment_mode)?;
+
+            // the raffle PDA is allocated by its own transaction ahead
+            let raffle = pda::raffle_address(&payer, &nft_mint, nonce).0;
+            if client.data_len(&raffle)? < RafflePool::LEN {
+                client.send(&instructions::allocate_raffle_steps(
+                    &payer, &payer, &nft_mint, nonce,
+                ))?;
+            }
+            // the escrow's ATA is left over when a previous create failed after creating it
+            let mut ixs = vec![];
+            if !client.exists(&pda::escrow_token_account(&raffle, &nft_mint))? {
+                ixs.push(instructions::create_escrow_token_account(
+                    &payer, &raffle, &nft_mint,
+                ));
+            }
+            ixs.push(instructions::create_raffle(
+                &payer,
+                &collection,
+                &collection_id,
+                &get_associated_token_address(&payer, &nft_mint),
+                &nft_mint,
+                CreateRaffleParams {
+                    nonce,
+                    ticket_price_sol,
+                    start_timestamp: start,
+                    end_timestamp: end,
+                    max_entrants,
+                    discounts,
+                    use_nft_creators,
+                    royalties,
+                    payment_mode,
+                    burn_target: burn_target.unwrap_or_default(),
+                    return_unclaimed,
+                    instant_win,
+                },
+            ));
+            let signature = client.send(&ixs)?;
+            Ok(transaction_json(
+                signature,
+                json!({ "raffle": raffle.to_string() }),
+            ))
+        }
+        Command::Buy {
+            raffle,
+            amount,
+            referrer,
+        } => {
+            let raffle_state = client.raffle_state(&raffle)?;
+            let signature = client.send(&[instructions::buy_tickets(
+                &payer,
+                &raffle,
+                &raffle_state,
+                amount,
+                referrer.as_ref(),
+            )])?;
+            Ok(transaction_json(signature, json!({ "amount": amount })))
+        }
+        Command::Reveal { raffle } => {
+            let signature = client.send(&[instructions::reveal_winner(&payer, &raffle)])?;
+            let raffle_state = client.raffle_state(&raffle)?;
+            Ok(transaction_json(
+                signature,
+                json!({
+                    "winnerIndex": raffle_state.winner_index,
+                    "winner": raffle_state.winner.to_string(),
+                }),
+            ))
+        }
+        Command::Claim { raffle } => {
+            let raffle_state = client.raffle_state(&raffle)?;
+            let mut ixs = vec![];
+            let nft_account = client.token_account(&raffle_state.nft_mint, &mut ixs)?;
+            ixs.push(instructions::claim_reward(
+                &payer,
+                &raffle,
+                &raffle_state,
+                &nft_account,
+            ));
+            let signature = client.send(&ixs)?;
+            Ok(transaction_json(
+                signature,
+                json!({ "nftAccount": nft_account.to_string() }),
+            ))
+        }
+        Command::Withdraw { raffle } => {
+            let raffle_state = client.raffle_state(&raffle)?;
+            let mut ixs = vec![];
+            let nft_account = client.token_account(&raffle_state.nft_mint, &mut ixs)?;
+            ixs.push(instructions::withdraw_nft(
+                &payer,
+                &raffle,
+                &raffle_state,
+                &nft_account,
+            ));
+            let signature = client.send(&ixs)?;
+            Ok(transaction_json(
+                signature,
+                json!({ "nftAccount": nft_account.to_string() }),
+            ))
+        }
+        Command::Show { raffle } => {
+            let raffle_state = client.raffle_state(&raffle)?;
+            Ok(raffle_json(&raffle, &raffle_state, true))
+        }
+        Command::List { creator } => {
+            let global_authority = pda::global_authority().0;
+            let global = state::global_pool(&client.rpc.get_account_data(&global_authority)?)?;
+            let raffles: Vec<Value> = client
+                .raffles()?
+                .into_iter()
+                .filter(|(_, raffle_state)| {
+                    creator.is_none_or(|creator| raffle_state.creator == creator)
+                })
+                .map(|(address, raffle_state)| raffle_json(&address, &raffle_state, false))
+                .collect();
+            Ok(json!({
+                "global": global_json(&global_authority, &global),
+                "raffles": raffles,
+            }))
+        }
+        Command::MigrateGlobal => {
+            let signature = client.send(&[instructions::migrate_global(&payer)])?;
+            Ok(transaction_json(
+                signature,
+                json!({ "global": pda::global_authority().0.to_string() }),
+            ))
+        }
+        Command::MigrateRaffle { raffle } => {
+            let raffle_state = client.raffle_state(&raffle)?;
+            // the NFT of a running or drawn raffle moves to the escrow's ATA
+            let mut ixs = vec![];
+            let escrow_token_account = pda::escrow_token_account(&raffle, &raffle_state.nft_mint);
+            if (raffle_state.claimed == 0 || raffle_state.claimed == 2)
+                && !client.exists(&escrow_token_account)?
+            {
+                ixs.push(instructions::create_escrow_token_account(
+                    &payer,
+                    &raffle,
+                    &raffle_state.nft_mint,
+                ));
+            }
+            ixs.push(instructions::migrate_raffle(&payer, &raffle, &raffle_state));
+            let signature = client.send(&ixs)?;
+            Ok(transaction_json(
+                signature,
+                json!({ "version": client.raffle_state(&raffle)?.version }),
+            ))
+        }
+        Command::MigrateCollection => {
+            let (collection, _) = client.collection_state()?;
+            let signature =
+                client.send(&[instructions::migrate_collection(&payer, &collection)])?;
+            Ok(transaction_json(
+                signature,
+                json!({ "collection": collection.to_string() }),
+            ))
+        }
+    }
+}
+
+fn try_main(cli: Cli) -> Result<Value> {
+    let keypair_path = match cli.keypair {
+        Some(path) => path,
+        None => format!("{}/.config/solana/id.json", std::env::var("HOME")?),
+    };
+    let payer = read_keypair_file(&keypair_path)
+        .map_err(|err| anyhow!("failed to read keypair {}: {}", keypair_path, err))?;
+    let client = Client {
+        rpc: RpcClient::new_with_commitment(cli.url, CommitmentConfig::confirmed()),
+        payer,
+    };
+
+    run(&client, cli.command)
+}
+
+// errors are printed as JSON too, with a failing exit code
+fn main() {
+    let output = match Cli::try_parse() {
+        Ok(cli) => try_main(cli).map_err(|err| format!("{:#}", err)),
+        // --help and --version aren't errors
+        Err(err) if !err.use_stderr() => err.exit(),
+        Err(err) => Err(err.to_string()),
+    };
+    match output {
+        Ok(output) => println!("{}", serde_json::to_string_pretty(&output).unwrap()),
+        Err(error) => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&json!({ "error": error })).unwrap()
+            );
+            std::process::exit(1);
+        }
+    }
+}
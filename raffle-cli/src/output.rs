@@ -0,0 +1,87 @@
+use raffle_client::account::{GlobalPool, RafflePool};
+use serde_json::{json, Value};
+use solana_sdk::pubkey::Pubkey;
+
+// the `claimed` states of `RafflePool`
+pub fn raffle_status(claimed: u64) -> &'static str {
+    match claimed {
+        0 => "running",
+        1 => "claimed",
+        2 => "drawn",
+        3 => "returned",
+        4 => "cancelled",
+        _ => "unknown",
+    }
+}
+
+pub fn global_json(address: &Pubkey, global: &GlobalPool) -> Value {
+    json!({
+        "address": address.to_string(),
+        "superAdmin": global.super_admin.to_string(),
+        "referralShare": global.referral_share,
+        "pauser": global.pauser.to_string(),
+        "paused": global.paused,
+        "voucherAuthority": global.voucher_authority.to_string(),
+        "claimWindow": global.claim_window,
+        "minDuration": global.min_duration,
+        "maxDuration": global.max_duration,
+    })
+}
+
+// the entrants are only listed on request, a raffle holds up to 2000 tickets
+pub fn raffle_json(address: &Pubkey, raffle: &RafflePool, with_entrants: bool) -> Value {
+    let discounts: Vec<Value> = raffle.discounts[..raffle.discount_count as usize]
+        .iter()
+        .map(|discount| {
+            json!({
+                "minTickets": discount.min_tickets,
+                "discountPercent": discount.discount_percent,
+            })
+        })
+        .collect();
+    let royalties: Vec<Value> = raffle.royalties[..raffle.royalty_count as usize]
+        .iter()
+        .map(|royalty| {
+            json!({
+                "recipient": royalty.recipient.to_string(),
+                "basisPoints": royalty.basis_points,
+            })
+        })
+        .collect();
+    let mut value = json!({
+        "address": address.to_string(),
+        "version": raffle.version,
+        "creator": raffle.creator.to_string(),
+        "nftMint": raffle.nft_mint.to_string(),
+        "nonce": raffle.nonce,
+        "status": raffle_status(raffle.claimed),
+        "count": raffle.count,
+        "noRepeat": raffle.no_repeat,
+        "maxEntrants": raffle.max_entrants,
+        "startTimestamp": raffle.start_timestamp,
+        "endTimestamp": raffle.end_timestamp,
+        "ticketPriceSol": raffle.ticket_price_sol,
+        "paymentMode": raffle.payment_mode,
+        "burnTarget": raffle.burn_target.to_string(),
+        "discounts": discounts,
+        "royalties": royalties,
+        "frozen": raffle.frozen != 0,
+        "totalPaid": raffle.total_paid,
+        "returnUnclaimed": raffle.return_unclaimed != 0,
+        "instantWin": raffle.instant_win != 0,
+        "settleBounty": raffle.settle_bounty,
+        "claimDeadline": raffle.claim_deadline,
+    });
+    // the winner is only set once drawn
+    if raffle.claimed != 0 {
+        value["winnerIndex"] = json!(raffle.winner_index);
+        value["winner"] = json!(raffle.winner.to_string());
+    }
+    if with_entrants {
+        value["entrants"] = raffle.entrants[..raffle.count as usize]
+            .iter()
+            .map(|entrant| entrant.to_string())
+            .collect();
+    }
+    value
+}
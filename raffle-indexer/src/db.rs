@@ -0,0 +1,399 @@
+use std::mem::size_of;
+
+use anyhow::{anyhow, Result};
+use raffle::account::{RafflePool, U64_LENGTH};
+use raffle_client::state;
+use rusqlite::{params, Connection, OptionalExtension};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{replay::Event, source::RaffleTransaction};
+
+// the replayed `RafflePool` is kept in `state` to continue the replay on the next run,
+// `raffle_accounts` is the last fetched on-chain state of every raffle account
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS transactions (
+    signature TEXT PRIMARY KEY,
+    slot INTEGER NOT NULL,
+    block_time INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS raffles (
+    address TEXT PRIMARY KEY,
+    creator TEXT NOT NULL,
+    nft_mint TEXT NOT NULL,
+    nonce INTEGER NOT NULL,
+    ticket_price INTEGER NOT NULL,
+    payment_mode INTEGER NOT NULL,
+    start_timestamp INTEGER NOT NULL,
+    end_timestamp INTEGER NOT NULL,
+    max_entrants INTEGER NOT NULL,
+    tickets INTEGER NOT NULL,
+    total_paid INTEGER NOT NULL,
+    status TEXT NOT NULL,
+    frozen INTEGER NOT NULL,
+    winner TEXT,
+    created_signature TEXT NOT NULL,
+    created_slot INTEGER NOT NULL,
+    state BLOB NOT NULL
+);
+CREATE TABLE IF NOT EXISTS purchases (
+    signature TEXT NOT NULL,
+    instruction_index INTEGER NOT NULL,
+    slot INTEGER NOT NULL,
+    block_time INTEGER NOT NULL,
+    raffle TEXT NOT NULL,
+    buyer TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    cost INTEGER,
+    PRIMARY KEY (signature, instruction_index)
+);
+CREATE TABLE IF NOT EXISTS draws (
+    signature TEXT NOT NULL,
+    instruction_index INTEGER NOT NULL,
+    slot INTEGER NOT NULL,
+    block_time INTEGER NOT NULL,
+    raffle TEXT NOT NULL,
+    caller TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    tickets INTEGER,
+    winner_index INTEGER,
+    winner TEXT,
+    PRIMARY KEY (signature, instruction_index)
+);
+CREATE TABLE IF NOT EXISTS claims (
+    signature TEXT NOT NULL,
+    instruction_index INTEGER NOT NULL,
+    slot INTEGER NOT NULL,
+    block_time INTEGER NOT NULL,
+    raffle TEXT NOT NULL,
+    caller TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    winner TEXT NOT NULL,
+    PRIMARY KEY (signature, instruction_index)
+);
+CREATE TABLE IF NOT EXISTS withdrawals (
+    signature TEXT NOT NULL,
+    instruction_index INTEGER NOT NULL,
+    slot INTEGER NOT NULL,
+    block_time INTEGER NOT NULL,
+    raffle TEXT NOT NULL,
+    caller TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    recipient TEXT,
+    PRIMARY KEY (signature, instruction_index)
+);
+CREATE TABLE IF NOT EXISTS refunds (
+    signature TEXT NOT NULL,
+    instruction_index INTEGER NOT NULL,
+    slot INTEGER NOT NULL,
+    block_time INTEGER NOT NULL,
+    raffle TEXT NOT NULL,
+    buyer TEXT NOT NULL,
+    tickets INTEGER,
+    PRIMARY KEY (signature, instruction_index)
+);
+CREATE TABLE IF NOT EXISTS raffle_accounts (
+    address TEXT PRIMARY KEY,
+    slot INTEGER NOT NULL,
+    version INTEGER NOT NULL,
+    creator TEXT NOT NULL,
+    nft_mint TEXT NOT NULL,
+    ticket_price INTEGER NOT NULL,
+    end_timestamp INTEGER NOT NULL,
+    tickets INTEGER NOT NULL,
+    total_paid INTEGER NOT NULL,
+    status TEXT NOT NULL,
+    frozen INTEGER NOT NULL,
+    winner TEXT
+);
+CREATE INDEX IF NOT EXISTS purchases_raffle ON purchases (raffle);
+CREATE INDEX IF NOT EXISTS draws_raffle ON draws (raffle);
+";
+
+// the `claimed` states of `RafflePool`
+fn raffle_status(claimed: u64) -> &'static str {
+    match claimed {
+        0 => "running",
+        1 => "claimed",
+        2 => "drawn",
+        3 => "returned",
+        4 => "cancelled",
+        _ => "unknown",
+    }
+}
+
+pub struct Db {
+    connection: Connection,
+}
+
+impl Db {
+    pub fn open(path: &str) -> Result<Db> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(Db { connection })
+    }
+
+    pub fn begin(&self) -> Result<()> {
+        self.connection.execute_batch("BEGIN")?;
+        Ok(())
+    }
+
+    pub fn commit(&self) -> Result<()> {
+        self.connection.execute_batch("COMMIT")?;
+        Ok(())
+    }
+
+    pub fn is_indexed(&self, signature: &str) -> Result<bool> {
+        Ok(self
+            .connection
+            .query_row(
+                "SELECT 1 FROM transactions WHERE signature = ?1",
+                params![signature],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some())
+    }
+
+    // the newest indexed transaction to continue fetching from
+    pub fn last_signature(&self) -> Result<Option<String>> {
+        Ok(self
+            .connection
+            .query_row(
+                "SELECT signature FROM transactions ORDER BY slot DESC LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    pub fn insert_transaction(&self, transaction: &RaffleTransaction) -> Result<()> {
+        self.connection.execute(
+            "INSERT INTO transactions (signature, slot, block_time) VALUES (?1, ?2, ?3)",
+            params![
+                transaction.signature,
+                transaction.slot as i64,
+                transaction.block_time
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn raffle_state(&self, raffle: &Pubkey) -> Result<Option<RafflePool>> {
+        let data: Option<Vec<u8>> = self
+            .connection
+            .query_row(
+                "SELECT state FROM raffles WHERE address = ?1",
+                params![raffle.to_string()],
+                |row| row.get(0),
+            )
+            .optional()?;
+        data.map(|data| {
+            // the states stored before the version header are read with version 0
+            let mut state = RafflePool::default();
+            let bytes = bytemuck::bytes_of_mut(&mut state);
+            if data.len() == size_of::<RafflePool>() {
+                bytes.copy_from_slice(&data);
+            } else if data.len() == size_of::<RafflePool>() - U64_LENGTH {
+                bytes[U64_LENGTH..].copy_from_slice(&data);
+            } else {
+                return Err(anyhow!("the stored state of {} has another layout", raffle));
+            }
+            Ok(state)
+        })
+        .transpose()
+    }
+
+    pub fn insert_raffle(
+        &self,
+        transaction: &RaffleTransaction,
+        raffle: &Pubkey,
+        state: &RafflePool,
+    ) -> Result<()> {
+        self.connection.execute(
+            "INSERT OR REPLACE INTO raffles (
+                address, creator, nft_mint, nonce, ticket_price, payment_mode,
+                start_timestamp, end_timestamp, max_entrants, tickets, total_paid,
+                status, frozen, winner, created_signature, created_slot, state
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, 0, 0, ?10, 0, NULL, ?11, ?12, ?13)",
+            params![
+                raffle.to_string(),
+                state.creator.to_string(),
+                state.nft_mint.to_string(),
+                state.nonce as i64,
+                state.ticket_price_sol as i64,
+                state.payment_mode as i64,
+                state.start_timestamp,
+                state.end_timestamp,
+                state.max_entrants as i64,
+                raffle_status(state.claimed),
+                transaction.signature,
+                transaction.slot as i64,
+                bytemuck::bytes_of(state),
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn update_raffle(&self, raffle: &Pubkey, state: &RafflePool) -> Result<()> {
+        // the winner is only set once drawn
+        let winner = (state.winner != Pubkey::default()).then(|| state.winner.to_string());
+        self.connection.execute(
+            "UPDATE raffles SET tickets = ?2, total_paid = ?3, status = ?4, frozen = ?5,
+                winner = ?6, state = ?7
+            WHERE address = ?1",
+            params![
+                raffle.to_string(),
+                state.active_tickets() as i64,
+                state.total_paid as i64,
+                raffle_status(state.claimed),
+                state.frozen != 0,
+                winner,
+                bytemuck::bytes_of(state),
+            ],
+        )?;
+        Ok(())
+    }
+
+    // the first release raffles are read with version 0 until they are migrated
+    pub fn index_raffle_account(&self, address: &Pubkey, slot: u64, data: &[u8]) -> Result<()> {
+        let account = state::raffle_pool(data)
+            .map_err(|err| anyhow!("{} is not a raffle account: {}", address, err))?;
+        let winner = (account.winner != Pubkey::default()).then(|| account.winner.to_string());
+        self.connection.execute(
+            "INSERT OR REPLACE INTO raffle_accounts (
+                address, slot, version, creator, nft_mint, ticket_price, end_timestamp,
+                tickets, total_paid, status, frozen, winner
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![
+                address.to_string(),
+                slot as i64,
+                account.version as i64,
+                account.creator.to_string(),
+                account.nft_mint.to_string(),
+                account.ticket_price_sol as i64,
+                account.end_timestamp,
+                account.active_tickets() as i64,
+                account.total_paid as i64,
+                raffle_status(account.claimed),
+                account.frozen != 0,
+                winner,
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn insert_event(
+        &self,
+        transaction: &RaffleTransaction,
+        instruction_index: usize,
+        raffle: &Pubkey,
+        event: &Event,
+    ) -> Result<()> {
+        let signature = &transaction.signature;
+        let index = instruction_index as i64;
+        let slot = transaction.slot as i64;
+        let block_time = transaction.block_time;
+        let raffle = raffle.to_string();
+        match event {
+            Event::Purchase {
+                buyer,
+                amount,
+                cost,
+                kind,
+            } => self.connection.execute(
+                "INSERT INTO purchases (
+                    signature, instruction_index, slot, block_time, raffle, buyer, kind, amount, cost
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    signature,
+                    index,
+                    slot,
+                    block_time,
+                    raffle,
+                    buyer.to_string(),
+                    kind,
+                    *amount as i64,
+                    cost.map(|cost| cost as i64),
+                ],
+            ),
+            Event::Draw {
+                caller,
+                kind,
+                tickets,
+                winner_index,
+                winner,
+            } => self.connection.execute(
+                "INSERT INTO draws (
+                    signature, instruction_index, slot, block_time, raffle, caller, kind,
+                    tickets, winner_index, winner
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                params![
+                    signature,
+                    index,
+                    slot,
+                    block_time,
+                    raffle,
+                    caller.to_string(),
+                    kind,
+                    tickets.map(|tickets| tickets as i64),
+                    winner_index.map(|winner_index| winner_index as i64),
+                    winner.map(|winner| winner.to_string()),
+                ],
+            ),
+            Event::Claim {
+                caller,
+                kind,
+                winner,
+            } => self.connection.execute(
+                "INSERT INTO claims (
+                    signature, instruction_index, slot, block_time, raffle, caller, kind, winner
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    signature,
+                    index,
+                    slot,
+                    block_time,
+                    raffle,
+                    caller.to_string(),
+                    kind,
+                    winner.to_string(),
+                ],
+            ),
+            Event::Withdrawal {
+                caller,
+                kind,
+                recipient,
+            } => self.connection.execute(
+                "INSERT INTO withdrawals (
+                    signature, instruction_index, slot, block_time, raffle, caller, kind, recipient
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    signature,
+                    index,
+                    slot,
+                    block_time,
+                    raffle,
+                    caller.to_string(),
+                    kind,
+                    recipient.map(|recipient| recipient.to_string()),
+                ],
+            ),
+            Event::Refund { buyer, tickets } => self.connection.execute(
+                "INSERT INTO refunds (
+                    signature, instruction_index, slot, block_time, raffle, buyer, tickets
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    signature,
+                    index,
+                    slot,
+                    block_time,
+                    raffle,
+                    buyer.to_string(),
+                    tickets.map(|tickets| tickets as i64),
+                ],
+            ),
+        }?;
+        Ok(())
+    }
+}
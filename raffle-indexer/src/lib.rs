@@ -0,0 +1,9 @@
+//! Decodes and replays the raffle program's transactions for `raffle-indexer`.
+//!
+//! - `source` reads the transactions from the RPC or a dump
+//! - `replay` decodes the raffle instructions and replays them on `RafflePool`
+//! - `db` writes the raffles and their events into SQLite
+
+pub mod db;
+pub mod replay;
+pub mod source;
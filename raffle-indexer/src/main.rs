@@ -0,0 +1,102 @@
+//! Replays the raffle program's transactions into SQLite.
+//!
+//! The raffles are replayed with the `RafflePool` methods of the program from their
+//! `create_raffle` on, so the history stays queryable after the accounts change.
+//! Indexing from the RPC also stores the current state of every raffle account.
+//!
+//!     raffle-indexer --db raffles.sqlite --url http://127.0.0.1:8899
+//!     raffle-indexer --db raffles.sqlite --dump transactions.jsonl
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use raffle_indexer::{
+    db::Db,
+    replay,
+    source::{self, RaffleTransaction},
+};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+
+#[derive(Parser)]
+#[clap(
+    name = "raffle-indexer",
+    version,
+    about = "Indexes the raffle program history into SQLite"
+)]
+struct Cli {
+    /// SQLite database, created when missing
+    #[clap(long, default_value = "raffles.sqlite")]
+    db: String,
+    /// RPC URL of the cluster, only the transactions after the last indexed one are fetched
+    #[clap(long, short, default_value = "http://127.0.0.1:8899")]
+    url: String,
+    /// Replay a dump of `getTransaction` JSON results, one per line, instead of the RPC
+    #[clap(long)]
+    dump: Option<String>,
+}
+
+fn index_transaction(db: &Db, transaction: &RaffleTransaction) -> Result<()> {
+    for (index, instruction) in transaction.instructions.iter().enumerate() {
+        let (raffle, action) = match replay::decode(instruction)? {
+            Some(decoded) => decoded,
+            None => continue,
+        };
+        let created = matches!(action, replay::Action::Create { .. });
+        let mut state = db.raffle_state(&raffle)?;
+        let events = replay::apply(action, transaction.block_time, &mut state)?;
+
+        if let Some(state) = &state {
+            if created {
+                db.insert_raffle(transaction, &raffle, state)?;
+            }
+            db.update_raffle(&raffle, state)?;
+        }
+        for event in &events {
+            db.insert_event(transaction, index, &raffle, event)?;
+        }
+    }
+    db.insert_transaction(transaction)
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let db = Db::open(&cli.db)?;
+
+    let rpc = RpcClient::new_with_commitment(cli.url.clone(), CommitmentConfig::confirmed());
+    let transactions = match &cli.dump {
+        Some(path) => source::load_dump(path)?,
+        None => source::fetch_transactions(&rpc, db.last_signature()?.as_deref())?,
+    };
+
+    let mut indexed = 0;
+    for value in &transactions {
+        let transaction = match source::parse_transaction(value)? {
+            Some(transaction) => transaction,
+            None => continue,
+        };
+        if db.is_indexed(&transaction.signature)? {
+            continue;
+        }
+        // a transaction is indexed completely or not at all
+        db.begin()?;
+        index_transaction(&db, &transaction)
+            .with_context(|| format!("failed to index {}", transaction.signature))?;
+        db.commit()?;
+        indexed += 1;
+    }
+    println!("indexed {} transactions into {}", indexed, cli.db);
+
+    // a dump has no accounts, the replayed states are all there is
+    if cli.dump.is_none() {
+        let (slot, accounts) = source::fetch_raffle_accounts(&rpc)?;
+        for (address, account) in &accounts {
+            db.index_raffle_account(address, slot, &account.data)?;
+        }
+        println!(
+            "indexed {} raffle accounts at slot {}",
+            accounts.len(),
+            slot
+        );
+    }
+    Ok(())
+}
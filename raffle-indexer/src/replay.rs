@@ -0,0 +1,400 @@
+use anchor_lang::{solana_program::hash::hash, AnchorDeserialize};
+use anyhow::{anyhow, Result};
+use raffle::{
+    account::RafflePool,
+    constants::*,
+    instruction::{
+        BurnTokensForTickets, BuyTickets, ClaimFreeTicket, CreateRaffle, SetRaffleFrozen,
+    },
+    utils::checked_add,
+};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::source::RaffleInstruction;
+
+// the raffle instructions which change a raffle
+pub enum Action {
+    Create {
+        creator: Pubkey,
+        nft_mint: Pubkey,
+        args: CreateRaffle,
+    },
+    Purchase {
+        buyer: Pubkey,
+        amount: u64,
+        kind: &'static str,
+    },
+    Reveal {
+        caller: Pubkey,
+    },
+    Reroll {
+        caller: Pubkey,
+    },
+    Claim {
+        claimer: Pubkey,
+    },
+    Settle {
+        cranker: Pubkey,
+        winner: Pubkey,
+    },
+    Withdraw {
+        claimer: Pubkey,
+    },
+    ForceCancel {
+        admin: Pubkey,
+    },
+    Freeze {
+        frozen: bool,
+    },
+    Refund {
+        buyer: Pubkey,
+    },
+    // the first release raffles have no replayed state, their accounts are indexed instead
+    Migrate,
+}
+
+// the rows written for an action, values unknown without the raffle's state are None
+pub enum Event {
+    Purchase {
+        buyer: Pubkey,
+        amount: u64,
+        cost: Option<u64>,
+        kind: &'static str,
+    },
+    Draw {
+        caller: Pubkey,
+        kind: &'static str,
+        tickets: Option<u64>,
+        winner_index: Option<u64>,
+        winner: Option<Pubkey>,
+    },
+    Claim {
+        caller: Pubkey,
+        kind: &'static str,
+        winner: Pubkey,
+    },
+    Withdrawal {
+        caller: Pubkey,
+        kind: &'static str,
+        recipient: Option<Pubkey>,
+    },
+    Refund {
+        buyer: Pubkey,
+        tickets: Option<u64>,
+    },
+}
+
+fn sighash(name: &str) -> [u8; 8] {
+    let mut sighash = [0u8; 8];
+    sighash.copy_from_slice(&hash(format!("global:{}", name).as_bytes()).to_bytes()[..8]);
+    sighash
+}
+
+fn account_at(instruction: &RaffleInstruction, index: usize) -> Result<Pubkey> {
+    instruction
+        .accounts
+        .get(index)
+        .copied()
+        .ok_or_else(|| anyhow!("missing account {}", index))
+}
+
+// the raffle and the action of an instruction, the account indexes follow the `Accounts` structs
+pub fn decode(instruction: &RaffleInstruction) -> Result<Option<(Pubkey, Action)>> {
+    if instruction.data.len() < 8 {
+        return Ok(None);
+    }
+    let (discriminator, mut args) = instruction.data.split_at(8);
+    let ix = |name: &str| discriminator == sighash(name);
+    let account = |index: usize| account_at(instruction, index);
+
+    let decoded = if ix("create_raffle") {
+        let raffle = account(2)?;
+        (
+            raffle,
+            Action::Create {
+                creator: account(0)?,
+                nft_mint: account(8)?,
+                args: CreateRaffle::deserialize(&mut args)?,
+            },
+        )
+    } else if ix("buy_tickets") {
+        let args = BuyTickets::deserialize(&mut args)?;
+        (
+            account(1)?,
+            Action::Purchase {
+                buyer: account(0)?,
+                amount: args.amount,
+                kind: "sol",
+            },
+        )
+    } else if ix("burn_tokens_for_tickets") {
+        let args = BurnTokensForTickets::deserialize(&mut args)?;
+        (
+            account(1)?,
+            Action::Purchase {
+                buyer: account(0)?,
+                amount: args.amount,
+                kind: "burn_token",
+            },
+        )
+    } else if ix("burn_nft_for_ticket") {
+        (
+            account(1)?,
+            Action::Purchase {
+                buyer: account(0)?,
+                amount: 1,
+                kind: "burn_nft",
+            },
+        )
+    } else if ix("claim_free_ticket") {
+        let args = ClaimFreeTicket::deserialize(&mut args)?;
+        (
+            account(2)?,
+            Action::Purchase {
+                buyer: account(0)?,
+                amount: args.amount,
+                kind: "voucher",
+            },
+        )
+    } else if ix("reveal_winner") {
+        (
+            account(1)?,
+            Action::Reveal {
+                caller: account(0)?,
+            },
+        )
+    } else if ix("reroll_winner") {
+        (
+            account(2)?,
+            Action::Reroll {
+                caller: account(0)?,
+            },
+        )
+    } else if ix("claim_reward") {
+        (
+            account(1)?,
+            Action::Claim {
+                claimer: account(0)?,
+            },
+        )
+    } else if ix("settle_raffle") {
+        (
+            account(1)?,
+            Action::Settle {
+                cranker: account(0)?,
+                winner: account(3)?,
+            },
+        )
+    } else if ix("withdraw_nft") {
+        (
+            account(1)?,
+            Action::Withdraw {
+                claimer: account(0)?,
+            },
+        )
+    } else if ix("force_cancel_raffle") {
+        (account(2)?, Action::ForceCancel { admin: account(0)? })
+    } else if ix("set_raffle_frozen") {
+        let args = SetRaffleFrozen::deserialize(&mut args)?;
+        (
+            account(2)?,
+            Action::Freeze {
+                frozen: args.frozen,
+            },
+        )
+    } else if ix("claim_refund") {
+        (account(1)?, Action::Refund { buyer: account(0)? })
+    } else if ix("migrate_raffle") {
+        (account(2)?, Action::Migrate)
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(decoded))
+}
+
+// the draw of the winner with the state after it
+fn draw(caller: Pubkey, kind: &'static str, tickets: u64, raffle: &RafflePool) -> Event {
+    Event::Draw {
+        caller,
+        kind,
+        tickets: Some(tickets),
+        winner_index: Some(raffle.winner_index),
+        winner: Some(raffle.winner),
+    }
+}
+
+/// Replays a succeeded instruction on the raffle's state like the program handler did.
+/// The clock's timestamp is the block time, so the draws give the same winners.
+/// The claim deadline is not replayed, the handlers already checked it.
+pub fn apply(
+    action: Action,
+    block_time: i64,
+    state: &mut Option<RafflePool>,
+) -> Result<Vec<Event>> {
+    let mut events = vec![];
+    match action {
+        Action::Create {
+            creator,
+            nft_mint,
+            args,
+        } => {
+            let mut raffle = RafflePool {
+                creator,
+                nft_mint,
+                nonce: args.nonce,
+                ticket_price_sol: args.ticket_price_sol,
+                start_timestamp: args.start_timestamp.max(block_time),
+                end_timestamp: args.end_timestamp,
+                max_entrants: args.max_entrants,
+                ..RafflePool::default()
+            };
+            raffle.set_discounts(&args.discounts)?;
+            // the royalties of the NFT's creators are read from its metadata
+            if !args.use_nft_creators {
+                raffle.set_royalties(&args.royalties)?;
+            }
+            raffle.payment_mode = args.payment_mode;
+            raffle.burn_target = args.burn_target;
+            raffle.return_unclaimed = u64::from(args.return_unclaimed);
+            raffle.instant_win = u64::from(args.instant_win);
+            raffle.settle_bounty = SETTLE_BOUNTY;
+            *state = Some(raffle);
+        }
+        Action::Purchase {
+            buyer,
+            amount,
+            kind,
+        } => {
+            let raffle = match state {
+                Some(raffle) => raffle,
+                None => {
+                    events.push(Event::Purchase {
+                        buyer,
+                        amount,
+                        cost: None,
+                        kind,
+                    });
+                    return Ok(events);
+                }
+            };
+            let cost = match kind {
+                "sol" | "burn_token" => raffle.ticket_cost(amount)?,
+                "burn_nft" => 1,
+                _ => 0,
+            };
+            raffle.add_entrant(buyer, amount)?;
+            if kind == "sol" {
+                raffle.total_paid = checked_add(raffle.total_paid, cost)?;
+            }
+            events.push(Event::Purchase {
+                buyer,
+                amount,
+                cost: Some(cost),
+                kind,
+            });
+            let tickets = raffle.active_tickets();
+            raffle.draw_if_sold_out(block_time, 0)?;
+            if raffle.claimed == 2 {
+                events.push(draw(buyer, "instant", tickets, raffle));
+            }
+        }
+        Action::Reveal { caller } => match state {
+            Some(raffle) => {
+                let tickets = raffle.active_tickets();
+                raffle.draw_winner(block_time)?;
+                events.push(draw(caller, "reveal", tickets, raffle));
+            }
+            None => events.push(Event::Draw {
+                caller,
+                kind: "reveal",
+                tickets: None,
+                winner_index: None,
+                winner: None,
+            }),
+        },
+        Action::Reroll { caller } => match state {
+            Some(raffle) => {
+                let previous_winner = raffle.winner;
+                raffle.remove_entrant(previous_winner);
+                let tickets = raffle.active_tickets();
+                if raffle.return_unclaimed == 0 && tickets > 0 {
+                    raffle.draw_winner(block_time)?;
+                    events.push(draw(caller, "reroll", tickets, raffle));
+                } else {
+                    raffle.winner = Pubkey::default();
+                    raffle.claimed = 3;
+                    raffle.take_settle_bounty();
+                    events.push(Event::Withdrawal {
+                        caller,
+                        kind: "return",
+                        recipient: Some(raffle.creator),
+                    });
+                }
+            }
+            // without the state the reroll can't tell a new draw from a return
+            None => events.push(Event::Draw {
+                caller,
+                kind: "reroll",
+                tickets: None,
+                winner_index: None,
+                winner: None,
+            }),
+        },
+        Action::Claim { claimer } => {
+            if let Some(raffle) = state {
+                raffle.claimed = 1;
+                raffle.take_settle_bounty();
+            }
+            events.push(Event::Claim {
+                caller: claimer,
+                kind: "claim",
+                winner: claimer,
+            });
+        }
+        Action::Settle { cranker, winner } => {
+            if let Some(raffle) = state {
+                raffle.claimed = 1;
+                raffle.take_settle_bounty();
+            }
+            events.push(Event::Claim {
+                caller: cranker,
+                kind: "settle",
+                winner,
+            });
+        }
+        Action::Withdraw { claimer } => {
+            if let Some(raffle) = state {
+                raffle.claimed = 3;
+                raffle.take_settle_bounty();
+            }
+            events.push(Event::Withdrawal {
+                caller: claimer,
+                kind: "withdraw",
+                recipient: Some(claimer),
+            });
+        }
+        Action::ForceCancel { admin } => {
+            let recipient = state.as_mut().map(|raffle| {
+                raffle.claimed = 4;
+                raffle.take_settle_bounty();
+                raffle.creator
+            });
+            events.push(Event::Withdrawal {
+                caller: admin,
+                kind: "cancel",
+                recipient,
+            });
+        }
+        Action::Freeze { frozen } => {
+            if let Some(raffle) = state {
+                raffle.frozen = u64::from(frozen);
+            }
+        }
+        Action::Refund { buyer } => {
+            let tickets = state.as_mut().map(|raffle| raffle.remove_entrant(buyer));
+            events.push(Event::Refund { buyer, tickets });
+        }
+        Action::Migrate => {}
+    }
+    Ok(events)
+}
@@ -0,0 +1,186 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    str::FromStr,
+};
+
+use anchor_lang::Discriminator;
+use anyhow::{anyhow, Context, Result};
+use raffle::account::RafflePool;
+use serde_json::{json, Value};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient},
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, MemcmpEncodedBytes, RpcFilterType},
+    rpc_request::RpcRequest,
+};
+use solana_sdk::{account::Account, pubkey::Pubkey, signature::Signature};
+
+// an instruction of the raffle program, top level or invoked by another program
+pub struct RaffleInstruction {
+    pub accounts: Vec<Pubkey>,
+    pub data: Vec<u8>,
+}
+
+pub struct RaffleTransaction {
+    pub signature: String,
+    pub slot: u64,
+    // the clock's unix timestamp the program saw
+    pub block_time: i64,
+    pub instructions: Vec<RaffleInstruction>,
+}
+
+// a succeeded transaction in the `getTransaction` JSON encoding, failed ones changed nothing
+pub fn parse_transaction(value: &Value) -> Result<Option<RaffleTransaction>> {
+    let meta = &value["meta"];
+    if !meta["err"].is_null() {
+        return Ok(None);
+    }
+    let transaction = &value["transaction"];
+    let signature = transaction["signatures"][0]
+        .as_str()
+        .ok_or_else(|| anyhow!("transaction without signature"))?
+        .to_string();
+    let slot = value["slot"]
+        .as_u64()
+        .ok_or_else(|| anyhow!("{} has no slot", signature))?;
+    let block_time = value["blockTime"]
+        .as_i64()
+        .ok_or_else(|| anyhow!("{} has no block time", signature))?;
+
+    let account_keys = transaction["message"]["accountKeys"]
+        .as_array()
+        .ok_or_else(|| anyhow!("{} has no account keys", signature))?
+        .iter()
+        .map(|key| Ok(Pubkey::from_str(key.as_str().unwrap_or_default())?))
+        .collect::<Result<Vec<Pubkey>>>()?;
+
+    let top_level = transaction["message"]["instructions"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    let inner = meta["innerInstructions"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+
+    // the inner instructions run within their top level instruction
+    let mut instructions = vec![];
+    for (index, instruction) in top_level.iter().enumerate() {
+        instructions.extend(raffle_instruction(instruction, &account_keys)?);
+        for inner_set in inner
+            .iter()
+            .filter(|set| set["index"].as_u64() == Some(index as u64))
+        {
+            for instruction in inner_set["instructions"].as_array().into_iter().flatten() {
+                instructions.extend(raffle_instruction(instruction, &account_keys)?);
+            }
+        }
+    }
+
+    Ok(Some(RaffleTransaction {
+        signature,
+        slot,
+        block_time,
+        instructions,
+    }))
+}
+
+fn raffle_instruction(value: &Value, account_keys: &[Pubkey]) -> Result<Option<RaffleInstruction>> {
+    let key = |index: &Value| {
+        index
+            .as_u64()
+            .and_then(|index| account_keys.get(index as usize))
+            .copied()
+            .ok_or_else(|| anyhow!("invalid account index {}", index))
+    };
+    if key(&value["programIdIndex"])? != raffle_client::ID {
+        return Ok(None);
+    }
+    let accounts = value["accounts"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(key)
+        .collect::<Result<Vec<Pubkey>>>()?;
+    let data = bs58::decode(value["data"].as_str().unwrap_or_default()).into_vec()?;
+    Ok(Some(RaffleInstruction { accounts, data }))
+}
+
+// one `getTransaction` JSON result per line, in the order they were executed
+pub fn load_dump(path: &str) -> Result<Vec<Value>> {
+    let file = File::open(path).with_context(|| format!("failed to open {}", path))?;
+    BufReader::new(file)
+        .lines()
+        .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(|line| Ok(serde_json::from_str(&line?)?))
+        .collect()
+}
+
+// the program's transactions after `until`, oldest first
+pub fn fetch_transactions(rpc: &RpcClient, until: Option<&str>) -> Result<Vec<Value>> {
+    let until = until.map(Signature::from_str).transpose()?;
+    let mut signatures = vec![];
+    let mut before = None;
+    loop {
+        let page = rpc.get_signatures_for_address_with_config(
+            &raffle_client::ID,
+            GetConfirmedSignaturesForAddress2Config {
+                before,
+                until,
+                limit: None,
+                commitment: Some(rpc.commitment()),
+            },
+        )?;
+        let last = match page.last() {
+            Some(last) => Signature::from_str(&last.signature)?,
+            None => break,
+        };
+        signatures.extend(
+            page.into_iter()
+                .filter(|status| status.err.is_none())
+                .map(|status| status.signature),
+        );
+        before = Some(last);
+    }
+
+    signatures
+        .iter()
+        .rev()
+        .map(|signature| {
+            Ok(rpc.send(
+                RpcRequest::GetTransaction,
+                json!([signature, { "encoding": "json", "commitment": "confirmed" }]),
+            )?)
+        })
+        .collect()
+}
+
+// the data of the raffle accounts of the current and the first release layout,
+// with the slot they were fetched at
+pub fn fetch_raffle_accounts(rpc: &RpcClient) -> Result<(u64, Vec<(Pubkey, Account)>)> {
+    let slot = rpc.get_slot()?;
+    let mut accounts = vec![];
+    for len in [RafflePool::LEN, RafflePool::LEGACY_LEN] {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![
+                RpcFilterType::DataSize(len as u64),
+                RpcFilterType::Memcmp(Memcmp {
+                    offset: 0,
+                    bytes: MemcmpEncodedBytes::Base58(
+                        bs58::encode(RafflePool::discriminator()).into_string(),
+                    ),
+                    encoding: None,
+                }),
+            ]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64Zstd),
+                ..RpcAccountInfoConfig::default()
+            },
+            ..RpcProgramAccountsConfig::default()
+        };
+        accounts.extend(rpc.get_program_accounts_with_config(&raffle_client::ID, config)?);
+    }
+    Ok((slot, accounts))
+}
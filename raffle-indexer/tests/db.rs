@@ -0,0 +1,234 @@
+use anchor_lang::Discriminator;
+use raffle::{
+    account::{RafflePool, DISCRIMINATOR_LENGTH},
+    constants::*,
+};
+use raffle_indexer::{db::Db, replay::Event, source::RaffleTransaction};
+use rusqlite::{params, Connection};
+use solana_sdk::pubkey::Pubkey;
+
+const NOW: i64 = 1_700_000_000;
+
+// a fresh database file of the test, read back with its own connection
+fn open(name: &str) -> (Db, Connection) {
+    let path = std::env::temp_dir().join(format!("raffle-indexer-{}.sqlite", name));
+    let _ = std::fs::remove_file(&path);
+    let path = path.to_str().unwrap();
+    (Db::open(path).unwrap(), Connection::open(path).unwrap())
+}
+
+fn transaction(signature: &str, slot: u64) -> RaffleTransaction {
+    RaffleTransaction {
+        signature: signature.to_string(),
+        slot,
+        block_time: NOW + slot as i64,
+        instructions: vec![],
+    }
+}
+
+fn raffle_state(creator: Pubkey) -> RafflePool {
+    RafflePool {
+        version: RAFFLE_VERSION,
+        creator,
+        nft_mint: Pubkey::new_unique(),
+        ticket_price_sol: 1_000_000,
+        start_timestamp: NOW,
+        end_timestamp: NOW + DAY,
+        max_entrants: 10,
+        ..RafflePool::default()
+    }
+}
+
+#[test]
+fn db_keeps_the_replayed_raffle_state() {
+    let (db, connection) = open("replayed-state");
+    let raffle = Pubkey::new_unique();
+    let buyer = Pubkey::new_unique();
+    let mut state = raffle_state(Pubkey::new_unique());
+
+    let create = transaction("create", 1);
+    db.insert_raffle(&create, &raffle, &state).unwrap();
+    db.insert_transaction(&create).unwrap();
+    state.add_entrant(buyer, 3).unwrap();
+    state.total_paid = 3_000_000;
+    state.frozen = 1;
+    db.update_raffle(&raffle, &state).unwrap();
+
+    assert!(db.is_indexed("create").unwrap());
+    assert!(!db.is_indexed("purchase").unwrap());
+    assert_eq!(db.last_signature().unwrap().as_deref(), Some("create"));
+    let stored = db.raffle_state(&raffle).unwrap().unwrap();
+    assert_eq!(stored.count, 3);
+    assert_eq!(stored.entrants[2], buyer);
+    assert!(db.raffle_state(&Pubkey::new_unique()).unwrap().is_none());
+
+    let row: (i64, i64, String, bool, Option<String>, String) = connection
+        .query_row(
+            "SELECT tickets, total_paid, status, frozen, winner, created_signature
+            FROM raffles WHERE address = ?1",
+            params![raffle.to_string()],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                    row.get(5)?,
+                ))
+            },
+        )
+        .unwrap();
+    assert_eq!(
+        row,
+        (
+            3,
+            3_000_000,
+            "running".to_string(),
+            true,
+            None,
+            "create".to_string()
+        )
+    );
+}
+
+#[test]
+fn db_writes_one_row_per_event() {
+    let (db, connection) = open("events");
+    let raffle = Pubkey::new_unique();
+    let buyer = Pubkey::new_unique();
+    let purchase = transaction("purchase", 2);
+
+    let events = [
+        Event::Purchase {
+            buyer,
+            amount: 2,
+            cost: Some(2_000_000),
+            kind: "sol",
+        },
+        Event::Draw {
+            caller: buyer,
+            kind: "instant",
+            tickets: Some(2),
+            winner_index: Some(1),
+            winner: Some(buyer),
+        },
+    ];
+    for event in &events {
+        db.insert_event(&purchase, 0, &raffle, event).unwrap();
+    }
+    let refund = transaction("refund", 3);
+    db.insert_event(
+        &refund,
+        1,
+        &raffle,
+        &Event::Refund {
+            buyer,
+            tickets: Some(2),
+        },
+    )
+    .unwrap();
+    // the replayed event is known without the raffle's state
+    db.insert_event(
+        &refund,
+        2,
+        &raffle,
+        &Event::Refund {
+            buyer,
+            tickets: None,
+        },
+    )
+    .unwrap();
+
+    let cost: Option<i64> = connection
+        .query_row(
+            "SELECT cost FROM purchases WHERE signature = 'purchase'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(cost, Some(2_000_000));
+    let winner: String = connection
+        .query_row(
+            "SELECT winner FROM draws WHERE raffle = ?1",
+            params![raffle.to_string()],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(winner, buyer.to_string());
+    let refunds: Vec<(i64, Option<i64>)> = connection
+        .prepare("SELECT instruction_index, tickets FROM refunds ORDER BY instruction_index")
+        .unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(refunds, vec![(1, Some(2)), (2, None)]);
+
+    // an instruction is only indexed once
+    assert!(db.insert_event(&purchase, 0, &raffle, &events[0]).is_err());
+}
+
+#[test]
+fn db_indexes_the_decoded_raffle_accounts() {
+    let (db, connection) = open("accounts");
+    let creator = Pubkey::new_unique();
+    let winner = Pubkey::new_unique();
+    let mut state = raffle_state(creator);
+    state.add_entrant(winner, 4).unwrap();
+    state.draw_winner(NOW + DAY).unwrap();
+    state.total_paid = 4_000_000;
+    let mut data = RafflePool::discriminator().to_vec();
+    data.extend_from_slice(bytemuck::bytes_of(&state));
+
+    let raffle = Pubkey::new_unique();
+    db.index_raffle_account(&raffle, 100, &data).unwrap();
+    let row: (i64, i64, String, String, i64, String, Option<String>) = connection
+        .query_row(
+            "SELECT slot, version, creator, status, tickets, nft_mint, winner
+            FROM raffle_accounts WHERE address = ?1",
+            params![raffle.to_string()],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                    row.get(5)?,
+                    row.get(6)?,
+                ))
+            },
+        )
+        .unwrap();
+    assert_eq!(
+        row,
+        (
+            100,
+            RAFFLE_VERSION as i64,
+            creator.to_string(),
+            "drawn".to_string(),
+            4,
+            state.nft_mint.to_string(),
+            Some(winner.to_string()),
+        )
+    );
+
+    // the next fetch replaces the state of the account
+    state.claimed = 1;
+    data[DISCRIMINATOR_LENGTH..].copy_from_slice(bytemuck::bytes_of(&state));
+    db.index_raffle_account(&raffle, 101, &data).unwrap();
+    let (slot, status): (i64, String) = connection
+        .query_row("SELECT slot, status FROM raffle_accounts", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .unwrap();
+    assert_eq!((slot, status.as_str()), (101, "claimed"));
+
+    // other accounts of the program are not raffles
+    let mut other = vec![0u8; data.len()];
+    other[..8].copy_from_slice(&[1; 8]);
+    assert!(db
+        .index_raffle_account(&Pubkey::new_unique(), 101, &other)
+        .is_err());
+}
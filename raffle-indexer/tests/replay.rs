@@ -0,0 +1,155 @@
+use raffle::{account::RafflePool, constants::*, instruction::CreateRaffle};
+use raffle_indexer::replay::{apply, Action, Event};
+use solana_sdk::pubkey::Pubkey;
+
+const NOW: i64 = 1_700_000_000;
+
+fn create(max_entrants: u64, instant_win: bool) -> Action {
+    Action::Create {
+        creator: Pubkey::new_unique(),
+        nft_mint: Pubkey::new_unique(),
+        args: CreateRaffle {
+            nonce: 0,
+            ticket_price_sol: 1_000_000,
+            start_timestamp: 0,
+            end_timestamp: NOW + DAY,
+            max_entrants,
+            discounts: vec![],
+            use_nft_creators: false,
+            royalties: vec![],
+            payment_mode: PAYMENT_SOL,
+            burn_target: Pubkey::default(),
+            return_unclaimed: false,
+            instant_win,
+        },
+    }
+}
+
+fn purchase(buyer: Pubkey, amount: u64) -> Action {
+    Action::Purchase {
+        buyer,
+        amount,
+        kind: "sol",
+    }
+}
+
+#[test]
+fn instant_win_purchases_draw_the_program_winner() {
+    let buyers: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+    let purchases = [(0, 3), (1, 1), (2, 4), (0, 2), (3, 2)];
+
+    // the last purchase sells out the raffle at another time in every run
+    for sold_out_time in NOW..NOW + 50 {
+        let mut state = None;
+        apply(create(12, true), NOW, &mut state).unwrap();
+        let mut expected = RafflePool {
+            max_entrants: 12,
+            ..RafflePool::default()
+        };
+
+        for (i, (buyer, amount)) in purchases.iter().enumerate() {
+            let buyer = buyers[*buyer];
+            let block_time = if i == purchases.len() - 1 {
+                sold_out_time
+            } else {
+                NOW + i as i64
+            };
+            let events = apply(purchase(buyer, *amount), block_time, &mut state).unwrap();
+            expected.add_entrant(buyer, *amount).unwrap();
+            if i < purchases.len() - 1 {
+                assert_eq!(events.len(), 1);
+                assert_eq!(state.as_ref().unwrap().claimed, 0);
+            }
+        }
+        expected.draw_winner(sold_out_time).unwrap();
+
+        let raffle = state.as_ref().unwrap();
+        assert_eq!(raffle.claimed, 2);
+        assert_eq!(raffle.winner_index, expected.winner_index);
+        assert_eq!(raffle.winner, expected.winner);
+
+        // the program rejects purchases after the instant draw
+        assert!(apply(purchase(buyers[1], 1), sold_out_time, &mut state).is_err());
+    }
+}
+
+#[test]
+fn the_sold_out_purchase_records_the_instant_draw() {
+    let buyers: Vec<Pubkey> = (0..2).map(|_| Pubkey::new_unique()).collect();
+    let mut state = None;
+    apply(create(5, true), NOW, &mut state).unwrap();
+    apply(purchase(buyers[0], 2), NOW, &mut state).unwrap();
+    let events = apply(purchase(buyers[1], 3), NOW + 60, &mut state).unwrap();
+
+    let mut expected = RafflePool {
+        max_entrants: 5,
+        ..RafflePool::default()
+    };
+    expected.add_entrant(buyers[0], 2).unwrap();
+    expected.add_entrant(buyers[1], 3).unwrap();
+    expected.draw_winner(NOW + 60).unwrap();
+
+    assert_eq!(events.len(), 2);
+    match &events[0] {
+        Event::Purchase {
+            buyer,
+            amount,
+            cost,
+            ..
+        } => {
+            assert_eq!((*buyer, *amount), (buyers[1], 3));
+            assert_eq!(*cost, Some(3_000_000));
+        }
+        _ => panic!("expected the purchase"),
+    }
+    match &events[1] {
+        Event::Draw {
+            caller,
+            kind,
+            tickets,
+            winner_index,
+            winner,
+        } => {
+            assert_eq!(*caller, buyers[1]);
+            assert_eq!(*kind, "instant");
+            assert_eq!(*tickets, Some(5));
+            assert_eq!(*winner_index, Some(expected.winner_index));
+            assert_eq!(*winner, Some(expected.winner));
+        }
+        _ => panic!("expected the instant draw"),
+    }
+}
+
+#[test]
+fn raffles_without_instant_win_are_drawn_by_the_reveal() {
+    let buyers: Vec<Pubkey> = (0..2).map(|_| Pubkey::new_unique()).collect();
+    let mut state = None;
+    apply(create(5, false), NOW, &mut state).unwrap();
+    apply(purchase(buyers[0], 2), NOW, &mut state).unwrap();
+    let events = apply(purchase(buyers[1], 3), NOW, &mut state).unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(state.as_ref().unwrap().claimed, 0);
+
+    let caller = Pubkey::new_unique();
+    let events = apply(Action::Reveal { caller }, NOW + DAY, &mut state).unwrap();
+    let mut expected = RafflePool {
+        max_entrants: 5,
+        ..RafflePool::default()
+    };
+    expected.add_entrant(buyers[0], 2).unwrap();
+    expected.add_entrant(buyers[1], 3).unwrap();
+    expected.draw_winner(NOW + DAY).unwrap();
+    match &events[..] {
+        [Event::Draw {
+            kind,
+            winner_index,
+            winner,
+            ..
+        }] => {
+            assert_eq!(*kind, "reveal");
+            assert_eq!(*winner_index, Some(expected.winner_index));
+            assert_eq!(*winner, Some(expected.winner));
+        }
+        _ => panic!("expected the reveal"),
+    }
+}
@@ -0,0 +1,83 @@
+use anchor_lang::InstructionData;
+use raffle::instruction::{BuyTickets, RevealWinner};
+use raffle_indexer::source::parse_transaction;
+use serde_json::{json, Value};
+use solana_sdk::pubkey::Pubkey;
+
+fn transaction(err: Value, account_keys: &[Pubkey], instructions: Value, inner: Value) -> Value {
+    json!({
+        "slot": 42,
+        "blockTime": 1_700_000_000,
+        "meta": {
+            "err": err,
+            "innerInstructions": inner,
+        },
+        "transaction": {
+            "signatures": ["5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnbJLgp8uirBgmQpjKhoR4tjF3ZpRzrFmBV6UjKdiSZkQUW"],
+            "message": {
+                "accountKeys": account_keys
+                    .iter()
+                    .map(|key| key.to_string())
+                    .collect::<Vec<String>>(),
+                "instructions": instructions,
+            },
+        },
+    })
+}
+
+#[test]
+fn parse_transaction_keeps_the_raffle_instructions_in_execution_order() {
+    let buyer = Pubkey::new_unique();
+    let raffle = Pubkey::new_unique();
+    let caller_program = Pubkey::new_unique();
+    let account_keys = [buyer, raffle, raffle_client::ID, caller_program];
+    let buy = bs58::encode(BuyTickets { amount: 3 }.data()).into_string();
+    let reveal = bs58::encode(RevealWinner {}.data()).into_string();
+
+    let value = transaction(
+        Value::Null,
+        &account_keys,
+        json!([
+            // a partner program buying through CPI, then a direct reveal
+            { "programIdIndex": 3, "accounts": [0, 1, 2], "data": "" },
+            { "programIdIndex": 2, "accounts": [0, 1], "data": reveal },
+        ]),
+        json!([
+            {
+                "index": 0,
+                "instructions": [
+                    { "programIdIndex": 2, "accounts": [0, 1], "data": buy },
+                ],
+            },
+        ]),
+    );
+    let parsed = parse_transaction(&value).unwrap().unwrap();
+    assert_eq!(parsed.slot, 42);
+    assert_eq!(parsed.block_time, 1_700_000_000);
+    assert_eq!(parsed.instructions.len(), 2);
+    assert_eq!(parsed.instructions[0].accounts, vec![buyer, raffle]);
+    assert_eq!(parsed.instructions[0].data, BuyTickets { amount: 3 }.data());
+    assert_eq!(parsed.instructions[1].data, RevealWinner {}.data());
+}
+
+#[test]
+fn parse_transaction_skips_failed_transactions() {
+    let value = transaction(
+        json!({ "InstructionError": [0, { "Custom": 6000 }] }),
+        &[Pubkey::new_unique(), raffle_client::ID],
+        json!([{ "programIdIndex": 1, "accounts": [0], "data": "" }]),
+        json!([]),
+    );
+    assert!(parse_transaction(&value).unwrap().is_none());
+}
+
+#[test]
+fn parse_transaction_rejects_unknown_account_indexes() {
+    let value = transaction(
+        Value::Null,
+        &[Pubkey::new_unique(), raffle_client::ID],
+        json!([{ "programIdIndex": 1, "accounts": [0, 5], "data": "" }]),
+        json!([]),
+    );
+    assert!(parse_transaction(&value).is_err());
+}
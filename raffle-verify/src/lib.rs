@@ -0,0 +1,110 @@
+//! Recomputes the winner draws of the raffle program step by step.
+//!
+//! The program draws with the unix timestamp of the drawing transaction:
+//! 1. the seed is `RANDOM_SEED` and the timestamp in decimal
+//! 2. the random address is the program address of the seed
+//! 3. the codes of the first 7 characters of the address in base58 are multiplied
+//!    and the code of the 8th character is added
+//! 4. the ticket index is the product modulo the active tickets
+//! 5. the winner is the entrant of that ticket, skipping the tickets removed by rerolls
+//!
+//! The steps are written out here instead of calling the program, so the verifier
+//! can be read on its own.
+
+use raffle::{account::RafflePool, constants::RANDOM_SEED};
+use solana_program::pubkey::Pubkey;
+
+/// The intermediate values of a draw.
+pub struct Draw {
+    pub timestamp: i64,
+    pub random_address: Pubkey,
+    pub product: u64,
+    pub tickets: u64,
+    pub ticket_index: u64,
+    pub winner_index: u64,
+    pub winner: Pubkey,
+}
+
+/// The recomputed draw against the raffle's winner.
+pub struct Verification {
+    pub draw: Draw,
+    pub onchain_winner_index: u64,
+    pub onchain_winner: Pubkey,
+}
+
+impl Verification {
+    pub fn is_valid(&self) -> bool {
+        self.draw.winner_index == self.onchain_winner_index
+            && self.draw.winner == self.onchain_winner
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum VerifyError {
+    // `claimed` is not drawn (2) or claimed (1)
+    WinnerNotDrawn,
+    NoActiveTickets,
+}
+
+pub fn random_address(timestamp: i64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[RANDOM_SEED.as_bytes(), timestamp.to_string().as_bytes()],
+        &raffle::ID,
+    )
+    .0
+}
+
+pub fn character_product(address: &Pubkey) -> u64 {
+    let chars: Vec<char> = address.to_string().chars().collect();
+    let mut product: u64 = 1;
+    for c in &chars[..7] {
+        product *= u64::from(*c);
+    }
+    product + u64::from(chars[7])
+}
+
+// the tickets not removed by rerolls
+pub fn active_entrants(raffle: &RafflePool) -> Vec<(u64, Pubkey)> {
+    raffle.entrants[..raffle.count as usize]
+        .iter()
+        .enumerate()
+        .filter(|(_, entrant)| **entrant != Pubkey::default())
+        .map(|(index, entrant)| (index as u64, *entrant))
+        .collect()
+}
+
+/// Draws among the raffle's active tickets like `reveal_winner` does at `timestamp`.
+pub fn recompute_draw(raffle: &RafflePool, timestamp: i64) -> Result<Draw, VerifyError> {
+    let entrants = active_entrants(raffle);
+    if entrants.is_empty() {
+        return Err(VerifyError::NoActiveTickets);
+    }
+    let random_address = random_address(timestamp);
+    let product = character_product(&random_address);
+    let tickets = entrants.len() as u64;
+    let ticket_index = product % tickets;
+    let (winner_index, winner) = entrants[ticket_index as usize];
+    Ok(Draw {
+        timestamp,
+        random_address,
+        product,
+        tickets,
+        ticket_index,
+        winner_index,
+        winner,
+    })
+}
+
+/// Verifies the raffle's winner was drawn at `timestamp`, the block time of the
+/// transaction which drew it. The entrants are the raffle's after that draw, which
+/// only a later reroll changes.
+pub fn verify(raffle: &RafflePool, timestamp: i64) -> Result<Verification, VerifyError> {
+    if raffle.claimed != 1 && raffle.claimed != 2 {
+        return Err(VerifyError::WinnerNotDrawn);
+    }
+    Ok(Verification {
+        draw: recompute_draw(raffle, timestamp)?,
+        onchain_winner_index: raffle.winner_index,
+        onchain_winner: raffle.winner,
+    })
+}
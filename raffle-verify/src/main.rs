@@ -0,0 +1,127 @@
+//! Verifies the winner of a raffle against the transaction which drew it.
+//!
+//!     raffle-verify --url https://api.mainnet-beta.solana.com --raffle <RAFFLE> --draw-tx <SIGNATURE>
+//!
+//! Prints the recomputed draw as JSON and exits with 1 when the winner doesn't match.
+
+use anyhow::{anyhow, bail, Result};
+use clap::Parser;
+use raffle_verify::{verify, Verification, VerifyError};
+use serde_json::{json, Value};
+use solana_client::{rpc_client::RpcClient, rpc_request::RpcRequest};
+use solana_program::pubkey::Pubkey;
+
+#[derive(Parser)]
+#[clap(
+    name = "raffle-verify",
+    version,
+    about = "Verifies the winner draw of a raffle"
+)]
+struct Cli {
+    /// RPC URL of the cluster
+    #[clap(long, short, default_value = "https://api.devnet.solana.com")]
+    url: String,
+    #[clap(long)]
+    raffle: Pubkey,
+    /// Signature of the transaction which drew the current winner
+    #[clap(long)]
+    draw_tx: String,
+}
+
+// the block time of the draw, which is the timestamp the program saw
+fn draw_timestamp(transaction: &Value, raffle: &Pubkey) -> Result<i64> {
+    if !transaction["meta"]["err"].is_null() {
+        bail!("the draw transaction failed");
+    }
+    let message = &transaction["transaction"]["message"];
+    let account_keys: Vec<&str> = message["accountKeys"]
+        .as_array()
+        .ok_or_else(|| anyhow!("the draw transaction has no account keys"))?
+        .iter()
+        .map(|key| key.as_str().unwrap_or_default())
+        .collect();
+    let key = |index: &Value| {
+        index
+            .as_u64()
+            .and_then(|index| account_keys.get(index as usize))
+            .copied()
+    };
+    let program_id = raffle::ID.to_string();
+    let raffle = raffle.to_string();
+
+    // top level or inner instructions of the program on this raffle
+    let mut instructions: Vec<&Value> = message["instructions"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .collect();
+    for inner in transaction["meta"]["innerInstructions"]
+        .as_array()
+        .into_iter()
+        .flatten()
+    {
+        instructions.extend(inner["instructions"].as_array().into_iter().flatten());
+    }
+    let draws_raffle = instructions.iter().any(|instruction| {
+        key(&instruction["programIdIndex"]) == Some(program_id.as_str())
+            && instruction["accounts"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .any(|index| key(index) == Some(raffle.as_str()))
+    });
+    if !draws_raffle {
+        bail!("the transaction has no raffle instruction on {}", raffle);
+    }
+
+    transaction["blockTime"]
+        .as_i64()
+        .ok_or_else(|| anyhow!("the draw transaction has no block time"))
+}
+
+fn report(raffle: &Pubkey, signature: &str, verification: &Verification) -> Value {
+    let draw = &verification.draw;
+    json!({
+        "raffle": raffle.to_string(),
+        "drawTransaction": signature,
+        "timestamp": draw.timestamp,
+        "randomAddress": draw.random_address.to_string(),
+        "product": draw.product,
+        "activeTickets": draw.tickets,
+        "ticketIndex": draw.ticket_index,
+        "winnerIndex": draw.winner_index,
+        "winner": draw.winner.to_string(),
+        "onchainWinnerIndex": verification.onchain_winner_index,
+        "onchainWinner": verification.onchain_winner.to_string(),
+        "valid": verification.is_valid(),
+    })
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let rpc = RpcClient::new(cli.url);
+
+    let data = rpc.get_account_data(&cli.raffle)?;
+    let raffle = raffle_client::state::raffle_pool(&data)?;
+    let transaction: Value = rpc.send(
+        RpcRequest::GetTransaction,
+        json!([cli.draw_tx, { "encoding": "json", "commitment": "confirmed" }]),
+    )?;
+    if transaction.is_null() {
+        bail!("draw transaction {} not found", cli.draw_tx);
+    }
+    let timestamp = draw_timestamp(&transaction, &cli.raffle)?;
+
+    let verification = verify(&raffle, timestamp).map_err(|err| match err {
+        VerifyError::WinnerNotDrawn => anyhow!("the raffle has no drawn winner"),
+        VerifyError::NoActiveTickets => anyhow!("the raffle has no active tickets"),
+    })?;
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&report(&cli.raffle, &cli.draw_tx, &verification))?
+    );
+    if !verification.is_valid() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
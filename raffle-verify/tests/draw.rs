@@ -0,0 +1,81 @@
+use raffle::{account::RafflePool, utils::draw_random};
+use raffle_verify::{recompute_draw, verify, VerifyError};
+use solana_program::pubkey::Pubkey;
+
+const NOW: i64 = 1_700_000_000;
+
+fn raffle_with_entrants(tickets: &[u64]) -> RafflePool {
+    let mut raffle = RafflePool {
+        max_entrants: 300,
+        ..Default::default()
+    };
+    for amount in tickets {
+        raffle.add_entrant(Pubkey::new_unique(), *amount).unwrap();
+    }
+    raffle
+}
+
+#[test]
+fn recomputed_draws_match_the_program() {
+    let mut raffle = raffle_with_entrants(&[3, 1, 7, 2, 12]);
+    for timestamp in NOW..NOW + 200 {
+        let draw = recompute_draw(&raffle, timestamp).unwrap();
+        assert_eq!(draw.ticket_index, draw_random(timestamp, raffle.count));
+
+        raffle.draw_winner(timestamp).unwrap();
+        assert_eq!(draw.winner_index, raffle.winner_index);
+        assert_eq!(draw.winner, raffle.winner);
+        assert!(verify(&raffle, timestamp).unwrap().is_valid());
+    }
+}
+
+#[test]
+fn rerolled_draws_skip_the_removed_tickets() {
+    let mut raffle = raffle_with_entrants(&[4, 2, 5, 1]);
+    for timestamp in [NOW, NOW + 86_400, NOW + 2 * 86_400] {
+        raffle.draw_winner(timestamp).unwrap();
+        let winner = raffle.winner;
+        raffle.remove_entrant(winner);
+        if raffle.active_tickets() == 0 {
+            break;
+        }
+
+        let reroll_timestamp = timestamp + 3_600;
+        raffle.draw_winner(reroll_timestamp).unwrap();
+        let verification = verify(&raffle, reroll_timestamp).unwrap();
+        assert!(verification.is_valid());
+        assert_eq!(verification.draw.tickets, raffle.active_tickets());
+    }
+}
+
+#[test]
+fn other_timestamps_or_winners_are_reported() {
+    let mut raffle = raffle_with_entrants(&[10, 10, 10]);
+    raffle.draw_winner(NOW).unwrap();
+
+    // a draw one second later picks another ticket for some timestamp
+    let other = (NOW + 1..NOW + 100)
+        .find(|timestamp| recompute_draw(&raffle, *timestamp).unwrap().winner != raffle.winner)
+        .unwrap();
+    assert!(!verify(&raffle, other).unwrap().is_valid());
+
+    raffle.winner = Pubkey::new_unique();
+    assert!(!verify(&raffle, NOW).unwrap().is_valid());
+}
+
+#[test]
+fn raffles_without_winner_are_not_verified() {
+    let raffle = raffle_with_entrants(&[1]);
+    assert_eq!(
+        verify(&raffle, NOW).err(),
+        Some(VerifyError::WinnerNotDrawn)
+    );
+    let empty = RafflePool {
+        claimed: 2,
+        ..Default::default()
+    };
+    assert_eq!(
+        recompute_draw(&empty, NOW).err(),
+        Some(VerifyError::NoActiveTickets)
+    );
+}